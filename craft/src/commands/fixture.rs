@@ -0,0 +1,884 @@
+use anyhow::{Context, Result};
+use colored::*;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use wasm_host_simulator::fixture_check::{self, Severity};
+
+/// JSON-RPC endpoint of the rippled container started by `craft start-rippled`
+pub const DEFAULT_RPC_URL: &str = "http://localhost:5005";
+
+/// Transaction fields returned by the `tx` method that are not part of the transaction itself
+const TX_RESULT_ONLY_FIELDS: &[&str] = &[
+    "meta",
+    "meta_blob",
+    "validated",
+    "ledger_index",
+    "ledger_hash",
+    "inLedger",
+    "date",
+    "ctid",
+    "close_time_iso",
+    "status",
+];
+
+/// ID of the FeeSettings ledger object, which holds the network's base fee
+const FEE_SETTINGS_INDEX: &str = "4BC50C9B0D8515D3EAAE1E74B29A95804346C491EE1A95BF25E4AAB854A6A651";
+
+/// ID of the Amendments ledger object, which lists the enabled amendments
+const AMENDMENTS_INDEX: &str = "7DB0788C020F02780A673DC74757F23823FA3014C1866E72CC4CD8B226CD6EF4";
+
+/// Header fields the `ledger` method may return as strings, which the simulator reads as numbers
+const NUMERIC_HEADER_FIELDS: &[&str] = &["ledger_index", "close_time", "parent_close_time"];
+
+/// What a fixture is captured from
+pub enum CaptureSource {
+    /// A validated EscrowFinish transaction, by hash. Ledger objects are captured as they were
+    /// just before the transaction was applied.
+    Transaction(String),
+    /// An escrow ledger object, by its ID. The fixture describes finishing it in the latest
+    /// validated ledger.
+    Escrow(String),
+}
+
+/// A minimal client for rippled's JSON-RPC API
+pub struct RpcClient {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl RpcClient {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Call `method` and return its `result`, failing if rippled reports an error
+    async fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let result = self.call_raw(method, params).await?;
+        check_status(method, result)
+    }
+
+    /// Like `call`, but return `None` if rippled reports the `not_found` error
+    async fn call_or_missing(
+        &self,
+        method: &str,
+        params: Value,
+        not_found: &str,
+    ) -> Result<Option<Value>> {
+        let result = self.call_raw(method, params).await?;
+        if result.get("error").and_then(Value::as_str) == Some(not_found) {
+            return Ok(None);
+        }
+        check_status(method, result).map(Some)
+    }
+
+    async fn call_raw(&self, method: &str, params: Value) -> Result<Value> {
+        let request_body = json!({
+            "method": method,
+            "params": [params]
+        });
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&request_body)
+            .send()
+            .await
+            .with_context(|| format!("Failed to connect to rippled at {}", self.url))?;
+        let json: Value = response
+            .json()
+            .await
+            .with_context(|| format!("Invalid {method} response from rippled"))?;
+        json.get("result")
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("{} response has no result", method))
+    }
+
+    /// Fetch a ledger entry, or `None` if it does not exist in that ledger
+    async fn ledger_entry(&self, mut params: Value, ledger_index: &Value) -> Result<Option<Value>> {
+        params["ledger_index"] = ledger_index.clone();
+        let Some(result) = self
+            .call_or_missing("ledger_entry", params, "entryNotFound")
+            .await?
+        else {
+            return Ok(None);
+        };
+        let mut node = result
+            .get("node")
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("ledger_entry response has no node"))?;
+        if node.get("index").is_none()
+            && let Some(index) = result.get("index")
+        {
+            node["index"] = index.clone();
+        }
+        Ok(Some(node))
+    }
+}
+
+fn check_status(method: &str, result: Value) -> Result<Value> {
+    if result.get("status").and_then(Value::as_str) == Some("error") {
+        let message = result
+            .get("error_message")
+            .or_else(|| result.get("error"))
+            .and_then(Value::as_str)
+            .unwrap_or("unknown error");
+        anyhow::bail!("{} failed: {}", method, message);
+    }
+    Ok(result)
+}
+
+/// The contents of a fixture directory
+#[derive(Debug)]
+pub struct Fixture {
+    pub tx: Value,
+    pub ledger_object: Value,
+    pub ledger_header: Value,
+    /// Ledger objects by ID
+    pub ledger: BTreeMap<String, Value>,
+    pub nfts: Vec<Value>,
+}
+
+impl Fixture {
+    /// Write the fixture files into `dir`, refusing to replace an existing fixture unless `force`
+    pub fn write(&self, dir: &Path, force: bool) -> Result<()> {
+        if dir.exists() && !force {
+            anyhow::bail!(
+                "Fixture directory {} already exists (use --force to overwrite)",
+                dir.display()
+            );
+        }
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+        let ledger: Vec<Value> = self
+            .ledger
+            .iter()
+            .map(|(index, object)| json!({ index: object }))
+            .collect();
+        let files = [
+            ("tx.json", &self.tx),
+            ("ledger_object.json", &self.ledger_object),
+            ("ledger_header.json", &self.ledger_header),
+            ("ledger.json", &Value::Array(ledger)),
+            ("nfts.json", &Value::Array(self.nfts.clone())),
+        ];
+        for (name, value) in files {
+            let path = dir.join(name);
+            fs::write(&path, serde_json::to_string_pretty(value)? + "\n")
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+fn str_field<'a>(object: &'a Value, field: &str, what: &str) -> Result<&'a str> {
+    object
+        .get(field)
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow::anyhow!("{} has no {} field", what, field))
+}
+
+fn u32_field(object: &Value, field: &str) -> Option<u32> {
+    match object.get(field)? {
+        Value::Number(n) => n.as_u64().and_then(|n| u32::try_from(n).ok()),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Query rippled for everything a fixture needs. Besides the escrow, the fixture's ledger holds
+/// the account roots of every account involved, every object the transaction modified or
+/// deleted, and any `extra_objects` the contract reads.
+pub async fn capture(
+    rpc: &RpcClient,
+    source: &CaptureSource,
+    extra_objects: &[String],
+) -> Result<Fixture> {
+    let mut touched = Vec::new();
+
+    let (tx, escrow, state_ledger, header_ledger) = match source {
+        CaptureSource::Transaction(hash) => {
+            let result = rpc
+                .call("tx", json!({ "transaction": hash, "binary": false }))
+                .await?;
+            // API v2 nests the transaction under tx_json, v1 returns it at the top level
+            let mut tx = result.get("tx_json").cloned().unwrap_or(result.clone());
+            if str_field(&tx, "TransactionType", "Transaction")? != "EscrowFinish" {
+                anyhow::bail!("Transaction {} is not an EscrowFinish", hash);
+            }
+            if result.get("validated").and_then(Value::as_bool) != Some(true) {
+                anyhow::bail!("Transaction {} is not in a validated ledger", hash);
+            }
+            let ledger_index = result
+                .get("ledger_index")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| anyhow::anyhow!("Transaction {} has no ledger_index", hash))?;
+            if let Some(tx) = tx.as_object_mut() {
+                for field in TX_RESULT_ONLY_FIELDS {
+                    tx.remove(*field);
+                }
+                tx.entry("hash").or_insert_with(|| json!(hash));
+            }
+
+            let affected_nodes = result
+                .get("meta")
+                .and_then(|m| m.get("AffectedNodes"))
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default();
+            for node in affected_nodes {
+                // Created objects did not exist when the contract ran
+                let existing = node.get("ModifiedNode").or_else(|| node.get("DeletedNode"));
+                if let Some(index) = existing.and_then(|n| n.get("LedgerIndex")) {
+                    touched.push(json!({ "index": index }));
+                }
+            }
+
+            // The escrow is deleted by a successful finish, so read it from the parent ledger
+            let state_ledger = json!(ledger_index - 1);
+            let owner = str_field(&tx, "Owner", "Transaction")?;
+            let seq = u32_field(&tx, "OfferSequence")
+                .ok_or_else(|| anyhow::anyhow!("Transaction has no OfferSequence"))?;
+            let escrow = rpc
+                .ledger_entry(
+                    json!({ "escrow": { "owner": owner, "seq": seq } }),
+                    &state_ledger,
+                )
+                .await?
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Escrow {}:{} not found in ledger {}",
+                        owner,
+                        seq,
+                        state_ledger
+                    )
+                })?;
+            (tx, escrow, state_ledger, json!(ledger_index))
+        }
+        CaptureSource::Escrow(index) => {
+            // Pin every query to one ledger so the escrow, header and other objects agree
+            let validated = rpc
+                .call("ledger", json!({ "ledger_index": "validated" }))
+                .await?;
+            let ledger_index = validated
+                .get("ledger_index")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| anyhow::anyhow!("ledger response has no ledger_index"))?;
+            let ledger_index = json!(ledger_index);
+            let escrow = rpc
+                .ledger_entry(json!({ "index": index }), &ledger_index)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Escrow {} not found", index))?;
+            if str_field(&escrow, "LedgerEntryType", "Ledger object")? != "Escrow" {
+                anyhow::bail!("Ledger object {} is not an Escrow", index);
+            }
+            let tx = escrow_finish_for(rpc, &escrow).await?;
+            (tx, escrow, ledger_index.clone(), ledger_index)
+        }
+    };
+
+    let header = rpc
+        .call("ledger", json!({ "ledger_index": header_ledger }))
+        .await?
+        .get("ledger")
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("ledger response has no ledger"))?;
+    let fee_settings = rpc
+        .ledger_entry(json!({ "index": FEE_SETTINGS_INDEX }), &state_ledger)
+        .await?;
+    let amendments = rpc
+        .ledger_entry(json!({ "index": AMENDMENTS_INDEX }), &state_ledger)
+        .await?;
+    let header = ledger_header(header, fee_settings.as_ref(), amendments.as_ref())?;
+
+    let mut accounts: Vec<&str> = Vec::new();
+    for account in [
+        tx.get("Account"),
+        tx.get("Owner"),
+        escrow.get("Account"),
+        escrow.get("Destination"),
+    ]
+    .into_iter()
+    .flatten()
+    .filter_map(Value::as_str)
+    {
+        if !accounts.contains(&account) {
+            accounts.push(account);
+        }
+    }
+    for account in &accounts {
+        touched.push(json!({ "account_root": account }));
+    }
+    for index in extra_objects {
+        touched.push(json!({ "index": index }));
+    }
+
+    let mut ledger = BTreeMap::new();
+    let escrow_index = str_field(&escrow, "index", "Escrow")?.to_string();
+    ledger.insert(escrow_index, escrow.clone());
+    for params in touched {
+        if let Some(index) = params.get("index").and_then(Value::as_str)
+            && ledger.contains_key(index)
+        {
+            continue;
+        }
+        match rpc.ledger_entry(params.clone(), &state_ledger).await? {
+            Some(object) => {
+                let index = str_field(&object, "index", "Ledger object")?.to_string();
+                ledger.insert(index, object);
+            }
+            None => println!(
+                "{}",
+                format!("Skipping ledger object not found in ledger: {params}").yellow()
+            ),
+        }
+    }
+
+    let mut nfts = Vec::new();
+    for account in &accounts {
+        nfts.extend(account_nfts(rpc, account, &state_ledger).await?);
+    }
+
+    Ok(Fixture {
+        tx,
+        ledger_object: escrow,
+        ledger_header: header,
+        ledger,
+        nfts,
+    })
+}
+
+/// The `ledger_header.json` for a `ledger` result: numeric fields as numbers, as API v1 returns
+/// `ledger_index` as a string, with `base_fee` from the FeeSettings object and `amendments` from
+/// the Amendments object, when the ledger has them
+fn ledger_header(
+    mut header: Value,
+    fee_settings: Option<&Value>,
+    amendments: Option<&Value>,
+) -> Result<Value> {
+    for field in NUMERIC_HEADER_FIELDS {
+        if let Some(Value::String(text)) = header.get(*field) {
+            let number: u64 = text
+                .parse()
+                .with_context(|| format!("ledger header {field} '{text}' is not a number"))?;
+            header[*field] = json!(number);
+        }
+    }
+    if let Some(fee_settings) = fee_settings {
+        // BaseFeeDrops once XRPFees is enabled, else BaseFee as a hex UInt64
+        let base_fee = match (
+            fee_settings.get("BaseFeeDrops"),
+            fee_settings.get("BaseFee"),
+        ) {
+            (Some(Value::Number(drops)), _) => drops.as_u64(),
+            (Some(Value::String(drops)), _) => drops.parse().ok(),
+            (None, Some(Value::String(hex))) => u64::from_str_radix(hex, 16).ok(),
+            _ => None,
+        }
+        .ok_or_else(|| anyhow::anyhow!("FeeSettings has no readable base fee"))?;
+        header["base_fee"] = json!(base_fee);
+    }
+    if let Some(amendments) = amendments {
+        header["amendments"] = amendments
+            .get("Amendments")
+            .cloned()
+            .unwrap_or_else(|| json!([]));
+    }
+    Ok(header)
+}
+
+/// Build an EscrowFinish for `escrow`, recovering its sequence from the transaction that last
+/// touched it
+async fn escrow_finish_for(rpc: &RpcClient, escrow: &Value) -> Result<Value> {
+    let owner = str_field(escrow, "Account", "Escrow")?;
+    let previous_txn = str_field(escrow, "PreviousTxnID", "Escrow")?;
+    let previous = rpc
+        .call(
+            "tx",
+            json!({ "transaction": previous_txn, "binary": false }),
+        )
+        .await?;
+    let previous = previous.get("tx_json").unwrap_or(&previous);
+    let offer_sequence = match previous.get("TransactionType").and_then(Value::as_str) {
+        Some("EscrowCreate") => u32_field(previous, "Sequence")
+            .filter(|seq| *seq != 0)
+            .or_else(|| u32_field(previous, "TicketSequence")),
+        Some("EscrowFinish") => u32_field(previous, "OfferSequence"),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        anyhow::anyhow!(
+            "Could not determine the escrow's sequence from {}; capture from the EscrowFinish hash with --tx instead",
+            previous_txn
+        )
+    })?;
+
+    Ok(json!({
+        "TransactionType": "EscrowFinish",
+        "Account": escrow.get("Destination").and_then(Value::as_str).unwrap_or(owner),
+        "Owner": owner,
+        "OfferSequence": offer_sequence,
+        "ComputationAllowance": "1000000",
+        "Flags": 0,
+    }))
+}
+
+/// The NFTs `account` owns, in the format `nfts.json` uses
+async fn account_nfts(rpc: &RpcClient, account: &str, ledger_index: &Value) -> Result<Vec<Value>> {
+    let mut nfts = Vec::new();
+    let mut marker: Option<Value> = None;
+    loop {
+        let mut params = json!({ "account": account, "ledger_index": ledger_index, "limit": 400 });
+        if let Some(marker) = marker.take() {
+            params["marker"] = marker;
+        }
+        // The account may not exist in that ledger
+        let Some(result) = rpc
+            .call_or_missing("account_nfts", params, "actNotFound")
+            .await?
+        else {
+            return Ok(nfts);
+        };
+        let resolved_ledger = result.get("ledger_index").cloned().unwrap_or(Value::Null);
+        for nft in result
+            .get("account_nfts")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            nfts.push(json!({
+                "nft_id": nft.get("NFTokenID"),
+                "ledger_index": resolved_ledger,
+                "owner": account,
+                "is_burned": false,
+                "flags": nft.get("Flags"),
+                "transfer_fee": nft.get("TransferFee").cloned().unwrap_or(json!(0)),
+                "issuer": nft.get("Issuer"),
+                "nft_taxon": nft.get("NFTokenTaxon"),
+                "nft_serial": nft.get("nft_serial"),
+                "validated": true,
+                "uri": nft.get("URI").cloned().unwrap_or(json!("")),
+            }));
+        }
+        match result.get("marker") {
+            Some(next) => marker = Some(next.clone()),
+            None => return Ok(nfts),
+        }
+    }
+}
+
+/// `craft fixture capture`: capture a fixture from rippled into projects/<project>/fixtures/<name>/
+pub async fn capture_fixture(
+    project: &str,
+    name: &str,
+    source: CaptureSource,
+    extra_objects: &[String],
+    rpc_url: &str,
+    force: bool,
+) -> Result<()> {
+    let project_dir = std::env::current_dir()?.join("projects").join(project);
+    if !project_dir.exists() {
+        anyhow::bail!("Project directory not found: {}", project_dir.display());
+    }
+    let fixture_dir = project_dir.join("fixtures").join(name);
+    if fixture_dir.exists() && !force {
+        anyhow::bail!(
+            "Fixture directory {} already exists (use --force to overwrite)",
+            fixture_dir.display()
+        );
+    }
+
+    println!(
+        "{}",
+        format!("Capturing fixture '{name}' from {rpc_url}...").cyan()
+    );
+    let rpc = RpcClient::new(rpc_url);
+    let fixture = capture(&rpc, &source, extra_objects).await?;
+    fixture.write(&fixture_dir, force)?;
+
+    println!(
+        "{}",
+        format!(
+            "Wrote fixture with {} ledger object(s) and {} NFT(s) to {}",
+            fixture.ledger.len(),
+            fixture.nfts.len(),
+            fixture_dir.display()
+        )
+        .green()
+    );
+    println!("Run it with: craft test {project} --case {name}");
+    Ok(())
+}
+
+/// `craft fixture check`: validate the fixtures of one project, or of every project that has some
+pub fn check_fixtures(project: Option<&str>) -> Result<()> {
+    let projects = match project {
+        Some(project) => vec![project.to_string()],
+        None => {
+            let cwd = std::env::current_dir()?;
+            let mut projects = Vec::new();
+            for path in crate::utils::find_wasm_projects(&cwd) {
+                if path.join("fixtures").is_dir() {
+                    projects.push(super::project_name(&path)?);
+                }
+            }
+            projects.sort();
+            projects
+        }
+    };
+    if projects.is_empty() {
+        println!("{}", "No projects with fixtures found".yellow());
+        return Ok(());
+    }
+
+    let mut failed = Vec::new();
+    for project in &projects {
+        let fixtures_dir = std::env::current_dir()?
+            .join("projects")
+            .join(project)
+            .join("fixtures");
+        println!("{}", format!("Checking fixtures for {project}").cyan());
+        let cases = fixture_check::check_fixtures(&fixtures_dir)
+            .with_context(|| format!("Cannot read fixtures in {}", fixtures_dir.display()))?;
+
+        let mut errors = 0;
+        let mut warnings = 0;
+        for (test_case, diagnostics) in &cases {
+            let case_errors = diagnostics
+                .iter()
+                .filter(|d| d.severity == Severity::Error)
+                .count();
+            errors += case_errors;
+            warnings += diagnostics.len() - case_errors;
+            if diagnostics.is_empty() {
+                println!("{test_case}: {}", "ok".green());
+                continue;
+            }
+            println!(
+                "{test_case}: {} error(s), {} warning(s)",
+                case_errors,
+                diagnostics.len() - case_errors
+            );
+            for diagnostic in diagnostics {
+                println!("  {diagnostic}");
+            }
+        }
+        println!(
+            "Checked {} fixture(s): {errors} error(s), {warnings} warning(s)",
+            cases.len()
+        );
+        if errors > 0 {
+            failed.push(project.as_str());
+        }
+        println!();
+    }
+
+    if !failed.is_empty() {
+        anyhow::bail!("Invalid fixtures in: {}", failed.join(", "));
+    }
+    println!("{}", "All fixtures are valid".green());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use wasm_host_simulator::data_provider::DataProvider;
+    use wasm_host_simulator::simulator::Fixture as LoadedFixture;
+
+    const OWNER: &str = "rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh";
+    const DESTINATION: &str = "rBmVUQNF6tJy4cLvoKdPXb4BNqKBk5JY1Y";
+    const ESCROW_INDEX: &str = "E00C7B6FF3B0D3DBA05A1B6E7A2F36E6B6F3C01B1C4C1A5A9E2D3C4B5A697887";
+    const OWNER_ROOT: &str = "2B6AC232AA4C4BE41BF49D2459FA4A0347E1B543A4C92FCEE0821C0201E2E9A8";
+    const DEST_ROOT: &str = "3C971C155253A5943BA5CF47191C5194F4667959F31CAC4593004F5C7D20C85C";
+    const AMENDMENT: &str = "ABABABABABABABABABABABABABABABABABABABABABABABABABABABABABABABAB";
+
+    /// Serve JSON-RPC requests from `handler`, returning the server's URL
+    async fn mock_rippled(
+        handler: impl Fn(&str, &Value) -> Value + Send + Sync + 'static,
+    ) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handler = Arc::new(handler);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let handler = handler.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 4096];
+                    let body = loop {
+                        let n = socket.read(&mut buf).await.unwrap();
+                        if n == 0 {
+                            return;
+                        }
+                        request.extend_from_slice(&buf[..n]);
+                        let text = String::from_utf8_lossy(&request);
+                        let Some(header_end) = text.find("\r\n\r\n") else {
+                            continue;
+                        };
+                        let content_length = text[..header_end]
+                            .lines()
+                            .find_map(|l| {
+                                l.to_ascii_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|v| v.trim().parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if request.len() >= header_end + 4 + content_length {
+                            break request[header_end + 4..header_end + 4 + content_length]
+                                .to_vec();
+                        }
+                    };
+                    let call: Value = serde_json::from_slice(&body).unwrap();
+                    let result = handler(call["method"].as_str().unwrap(), &call["params"][0]);
+                    let payload = json!({ "result": result }).to_string();
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        payload.len(),
+                        payload
+                    );
+                    socket.write_all(response.as_bytes()).await.unwrap();
+                });
+            }
+        });
+        url
+    }
+
+    fn account_root(account: &str, index: &str) -> Value {
+        json!({ "Account": account, "LedgerEntryType": "AccountRoot", "index": index })
+    }
+
+    fn rippled(method: &str, params: &Value) -> Value {
+        match method {
+            "tx" => json!({
+                "TransactionType": "EscrowFinish",
+                "Account": DESTINATION,
+                "Owner": OWNER,
+                "OfferSequence": 7,
+                "ComputationAllowance": "1000000",
+                "hash": "ABCD",
+                "ledger_index": 100,
+                "validated": true,
+                "meta": {
+                    "AffectedNodes": [
+                        { "DeletedNode": { "LedgerEntryType": "Escrow", "LedgerIndex": ESCROW_INDEX } },
+                        { "ModifiedNode": { "LedgerEntryType": "AccountRoot", "LedgerIndex": OWNER_ROOT } },
+                        { "CreatedNode": { "LedgerEntryType": "DirectoryNode", "LedgerIndex": "FF" } }
+                    ]
+                },
+                "status": "success"
+            }),
+            "ledger_entry" => {
+                assert_eq!(params["ledger_index"], json!(99));
+                let node = if params.get("escrow").is_some() {
+                    assert_eq!(params["escrow"], json!({ "owner": OWNER, "seq": 7 }));
+                    json!({
+                        "Account": OWNER,
+                        "Destination": DESTINATION,
+                        "LedgerEntryType": "Escrow",
+                        "FinishFunction": "0061736D",
+                        "index": ESCROW_INDEX
+                    })
+                } else if params["account_root"] == OWNER || params["index"] == OWNER_ROOT {
+                    account_root(OWNER, OWNER_ROOT)
+                } else if params["account_root"] == DESTINATION {
+                    account_root(DESTINATION, DEST_ROOT)
+                } else if params["index"] == FEE_SETTINGS_INDEX {
+                    json!({ "LedgerEntryType": "FeeSettings", "BaseFeeDrops": "12", "index": FEE_SETTINGS_INDEX })
+                } else if params["index"] == AMENDMENTS_INDEX {
+                    json!({ "LedgerEntryType": "Amendments", "Amendments": [AMENDMENT], "index": AMENDMENTS_INDEX })
+                } else if params["index"] == "DEADBEEF" {
+                    return json!({ "error": "entryNotFound", "status": "error" });
+                } else {
+                    panic!("unexpected ledger_entry {params}");
+                };
+                json!({ "node": node, "index": node["index"], "status": "success" })
+            }
+            "ledger" => {
+                assert_eq!(params["ledger_index"], json!(100));
+                json!({ "ledger": { "ledger_index": "100", "parent_close_time": 797572860 }, "status": "success" })
+            }
+            "account_nfts" if params["account"] == DESTINATION => json!({
+                "account_nfts": [{
+                    "NFTokenID": "000B0539C35B55AA096BA6D87A6E6C965A6534150DC56E5E12C5D09E0000000C",
+                    "Flags": 11,
+                    "Issuer": OWNER,
+                    "NFTokenTaxon": 1337,
+                    "nft_serial": 12,
+                    "TransferFee": 1337,
+                    "URI": "68747470"
+                }],
+                "ledger_index": 99,
+                "status": "success"
+            }),
+            "account_nfts" => {
+                json!({ "account_nfts": [], "ledger_index": 99, "status": "success" })
+            }
+            _ => panic!("unexpected method {method}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_capture_from_transaction() {
+        let url = mock_rippled(rippled).await;
+        let rpc = RpcClient::new(&url);
+        let fixture = capture(
+            &rpc,
+            &CaptureSource::Transaction("ABCD".into()),
+            &["DEADBEEF".to_string()],
+        )
+        .await
+        .unwrap();
+
+        assert!(fixture.tx.get("meta").is_none());
+        assert!(fixture.tx.get("ledger_index").is_none());
+        assert_eq!(fixture.tx["OfferSequence"], 7);
+        assert_eq!(fixture.ledger_object["index"], ESCROW_INDEX);
+        assert_eq!(fixture.ledger_header["parent_close_time"], 797572860);
+        let mut expected = vec![DEST_ROOT, OWNER_ROOT, ESCROW_INDEX];
+        expected.sort();
+        assert_eq!(fixture.ledger.keys().collect::<Vec<_>>(), expected);
+        assert_eq!(fixture.nfts.len(), 1);
+        assert_eq!(fixture.nfts[0]["owner"], DESTINATION);
+        assert_eq!(fixture.nfts[0]["uri"], "68747470");
+
+        let dir = std::env::temp_dir().join(format!("craft-fixture-{}", std::process::id()));
+        fixture.write(&dir, false).unwrap();
+        assert!(fixture.write(&dir, false).is_err());
+        let ledger: Value =
+            serde_json::from_str(&fs::read_to_string(dir.join("ledger.json")).unwrap()).unwrap();
+        assert_eq!(ledger[2][ESCROW_INDEX]["LedgerEntryType"], "Escrow");
+        for file in [
+            "tx.json",
+            "ledger_object.json",
+            "ledger_header.json",
+            "nfts.json",
+        ] {
+            assert!(dir.join(file).exists());
+        }
+
+        // The captured fixture runs as written, with the header read as the host functions do
+        let mut loaded = LoadedFixture::load(&dir, "captured", None).unwrap();
+        let provider = DataProvider::new(loaded.data.clone());
+        assert_eq!(provider.get_ledger_sqn(), 100);
+        assert_eq!(provider.get_parent_ledger_time(), 797572860);
+        assert_eq!(provider.get_base_fee(), 12);
+        assert_eq!(provider.amendment_enabled(&vec![0xAB; 32]), 1);
+        assert_eq!(provider.amendment_enabled(&vec![0xCD; 32]), 0);
+        loaded.data.advance_ledger(1, 4).unwrap();
+        assert_eq!(DataProvider::new(loaded.data).get_ledger_sqn(), 101);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_capture_from_escrow_pins_one_ledger() {
+        let url = mock_rippled(|method, params| match method {
+            "ledger" if params["ledger_index"] == "validated" => json!({
+                "ledger": { "ledger_index": "101" },
+                "ledger_index": 101,
+                "status": "success"
+            }),
+            "ledger" => {
+                assert_eq!(params["ledger_index"], json!(101));
+                json!({ "ledger": { "ledger_index": "101", "parent_close_time": 797572870 }, "status": "success" })
+            }
+            "ledger_entry" => {
+                assert_eq!(params["ledger_index"], json!(101));
+                let node = if params["index"] == FEE_SETTINGS_INDEX {
+                    json!({ "LedgerEntryType": "FeeSettings", "BaseFee": "000000000000000A", "index": FEE_SETTINGS_INDEX })
+                } else if params["index"] == AMENDMENTS_INDEX {
+                    return json!({ "error": "entryNotFound", "status": "error" });
+                } else if params["index"] == ESCROW_INDEX {
+                    json!({
+                        "Account": OWNER,
+                        "Destination": DESTINATION,
+                        "LedgerEntryType": "Escrow",
+                        "PreviousTxnID": "CREATE",
+                        "index": ESCROW_INDEX
+                    })
+                } else if params["account_root"] == OWNER {
+                    account_root(OWNER, OWNER_ROOT)
+                } else {
+                    account_root(DESTINATION, DEST_ROOT)
+                };
+                json!({ "node": node, "status": "success" })
+            }
+            "tx" => json!({
+                "tx_json": { "TransactionType": "EscrowCreate", "Sequence": 7 },
+                "status": "success"
+            }),
+            "account_nfts" => {
+                assert_eq!(params["ledger_index"], json!(101));
+                json!({ "error": "actNotFound", "status": "error" })
+            }
+            _ => panic!("unexpected method {method}"),
+        })
+        .await;
+        let rpc = RpcClient::new(&url);
+        let fixture = capture(&rpc, &CaptureSource::Escrow(ESCROW_INDEX.into()), &[])
+            .await
+            .unwrap();
+
+        assert_eq!(fixture.tx["OfferSequence"], 7);
+        assert_eq!(fixture.ledger_header["ledger_index"], 101);
+        assert_eq!(fixture.ledger_header["parent_close_time"], 797572870);
+        assert_eq!(fixture.ledger_header["base_fee"], 10);
+        assert!(fixture.ledger_header.get("amendments").is_none());
+        assert_eq!(fixture.ledger.len(), 3);
+        assert!(fixture.nfts.is_empty());
+    }
+
+    #[test]
+    fn test_ledger_header_normalises_fields() {
+        let header = ledger_header(
+            json!({ "ledger_index": "7", "close_time": 5, "parent_hash": "AB" }),
+            Some(&json!({ "BaseFeeDrops": 15 })),
+            Some(&json!({})),
+        )
+        .unwrap();
+        assert_eq!(
+            header,
+            json!({ "ledger_index": 7, "close_time": 5, "parent_hash": "AB", "base_fee": 15, "amendments": [] })
+        );
+        let err = ledger_header(json!({ "ledger_index": "seven" }), None, None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ledger header ledger_index 'seven' is not a number"
+        );
+        assert!(ledger_header(json!({}), Some(&json!({})), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_capture_reports_account_nfts_errors() {
+        let url = mock_rippled(|method, params| match method {
+            "account_nfts" => {
+                json!({ "error": "lgrNotFound", "error_message": "ledgerNotFound", "status": "error" })
+            }
+            _ => rippled(method, params),
+        })
+        .await;
+        let rpc = RpcClient::new(&url);
+        let err = capture(&rpc, &CaptureSource::Transaction("ABCD".into()), &[])
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "account_nfts failed: ledgerNotFound");
+    }
+
+    #[tokio::test]
+    async fn test_capture_reports_rpc_errors() {
+        let url = mock_rippled(|_, _| {
+            json!({ "error": "txnNotFound", "error_message": "Transaction not found.", "status": "error" })
+        })
+        .await;
+        let rpc = RpcClient::new(&url);
+        let err = capture(&rpc, &CaptureSource::Transaction("ABCD".into()), &[])
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "tx failed: Transaction not found.");
+    }
+}
@@ -7,9 +7,12 @@ use std::process::{Command, Output, Stdio};
 
 use crate::config::{BuildMode, Config, OptimizationLevel, WasmTarget};
 use crate::utils;
+use wasm_host_simulator::limits::{self, Limits};
 
+mod fixture;
 mod test;
-pub use test::TestRunner;
+pub use fixture::{CaptureSource, DEFAULT_RPC_URL, capture_fixture, check_fixtures};
+pub use test::{HeaderOverrides, SweepField, SweepSpec, TestRunner};
 
 fn handle_build_output(output: &Output, config: &Config, project_dir: &Path) -> Result<()> {
     let _stdout = String::from_utf8_lossy(&output.stdout);
@@ -146,6 +149,49 @@ pub async fn build(config: &Config) -> Result<PathBuf> {
     Ok(wasm_file)
 }
 
+/// Optimizes a freshly built module at the configured level, then checks its imports and
+/// features against the configured limits, so that the module checked is the one deployed
+pub async fn finish_build(wasm_path: &Path, config: &Config) -> Result<()> {
+    if !matches!(config.optimization_level, OptimizationLevel::None) {
+        optimize(wasm_path, &config.optimization_level).await?;
+    }
+    check_module(wasm_path, None, &config.limits)
+}
+
+/// Checks that the module at `wasm_path` is within a limits profile and, if `function` is given,
+/// exports it as an entry point, failing with every problem found
+pub fn check_module(wasm_path: &Path, function: Option<&str>, limits: &Limits) -> Result<()> {
+    let wasm =
+        fs::read(wasm_path).with_context(|| format!("Failed to read {}", wasm_path.display()))?;
+    let violations = match function {
+        Some(function) => limits::check_module(&wasm, function, limits),
+        None => limits::check(&wasm, limits),
+    };
+    if !violations.is_empty() {
+        let mut message = format!(
+            "{} fails the '{}' checks:",
+            wasm_path.display(),
+            limits.name
+        );
+        for violation in &violations {
+            message.push_str(&format!("\n  • {violation}"));
+        }
+        if violations.iter().any(|v| v.limit != "entry point") {
+            message.push_str("\n\nPass --limits unlimited to allow any imports and features");
+        }
+        anyhow::bail!(message);
+    }
+    let message = match function {
+        Some(function) => format!(
+            "Module exports '{function}' and is within the '{}' limits",
+            limits.name
+        ),
+        None => format!("Module is within the '{}' limits", limits.name),
+    };
+    println!("{}", message.green());
+    Ok(())
+}
+
 pub async fn deploy_to_wasm_devnet(wasm_file: &Path) -> Result<()> {
     println!("{}", "Deploying to WASM Devnet...".cyan());
 
@@ -382,24 +428,22 @@ pub async fn configure() -> Result<Config> {
         build_mode,
         optimization_level,
         project_path,
+        limits: Limits::default(),
     })
 }
 
-pub async fn test(wasm_path: &Path, function: Option<String>) -> Result<()> {
+pub async fn test(
+    wasm_path: &Path,
+    project_name: &str,
+    function: Option<String>,
+    gas_cap: Option<String>,
+) -> Result<()> {
     println!("{}", "Testing WASM contract...".cyan());
 
-    // Extract project name from wasm path
-    let project_name = wasm_path
-        .parent()
-        .and_then(|p| p.parent())
-        .and_then(|p| p.parent())
-        .and_then(|p| p.parent())
-        .and_then(|p| p.file_name())
-        .and_then(|n| n.to_str())
-        .unwrap_or("unknown");
-
     // Use the new TestRunner for a better interface
-    let runner = TestRunner::new(wasm_path, project_name).verbose(false);
+    let runner = TestRunner::new(wasm_path, project_name)
+        .verbose(false)
+        .gas_cap(gas_cap);
 
     // Interactive test case selection
     let test_cases = vec![
@@ -413,7 +457,7 @@ pub async fn test(wasm_path: &Path, function: Option<String>) -> Result<()> {
     match selection {
         "Run all test cases" => {
             let runner = runner.verbose(true);
-            let results = runner.run_all_tests(project_name)?;
+            let results = runner.run_all_tests(project_name, 1)?;
 
             if results.iter().any(|r| !r.success) {
                 anyhow::bail!("Some tests failed");
@@ -428,11 +472,9 @@ pub async fn test(wasm_path: &Path, function: Option<String>) -> Result<()> {
 
             let result = runner.run_test(test_case, function.as_deref())?;
 
-            println!("{}", result.stdout);
+            result.print();
 
             if !result.success {
-                println!("{}", result.stderr.red());
-
                 if let Some(desc) = result.error_description() {
                     println!();
                     println!("{}: {}", "Error".red().bold(), desc);
@@ -600,6 +642,15 @@ pub fn list_fixtures() -> Result<()> {
     Ok(())
 }
 
+/// Returns the project name as used for fixtures, i.e. the project's path relative to `projects/`.
+pub fn project_name(project_path: &Path) -> Result<String> {
+    let projects_dir = std::env::current_dir()?.join("projects");
+    let relative = project_path
+        .strip_prefix(&projects_dir)
+        .unwrap_or(project_path);
+    Ok(relative.to_string_lossy().to_string())
+}
+
 pub fn discover_test_cases(project: &str) -> Result<Vec<String>> {
     // Convention: fixtures must be in projects/<project>/fixtures/<test_case>/
     let fixtures_dir = std::env::current_dir()?
@@ -628,6 +679,26 @@ pub fn discover_test_cases(project: &str) -> Result<Vec<String>> {
     Ok(test_cases)
 }
 
+/// Fails unless the project has a scenario test case of this name, i.e. one with a
+/// `scenario.json`.
+pub fn check_scenario(project: &str, scenario: &str) -> Result<()> {
+    let path = std::env::current_dir()?
+        .join("projects")
+        .join(project)
+        .join("fixtures")
+        .join(scenario)
+        .join("scenario.json");
+    if !path.exists() {
+        anyhow::bail!(
+            "'{}' is not a scenario of project '{}': {} not found",
+            scenario,
+            project,
+            path.display()
+        );
+    }
+    Ok(())
+}
+
 pub async fn build_with_args(config: &Config, cargo_args: &[String]) -> Result<PathBuf> {
     println!("{}", "Building WASM module...".cyan());
 
@@ -701,37 +772,20 @@ pub async fn build_with_args(config: &Config, cargo_args: &[String]) -> Result<P
 }
 
 pub fn run_test(
-    wasm_path: &Path,
+    runner: &TestRunner,
     test_case: &str,
     function: Option<&str>,
-    verbose: bool,
-    _non_interactive: bool,
+    gas_report: bool,
 ) -> Result<()> {
-    // Extract project name from wasm path
-    let project_name = wasm_path
-        .parent()
-        .and_then(|p| p.parent())
-        .and_then(|p| p.parent())
-        .and_then(|p| p.parent())
-        .and_then(|p| p.file_name())
-        .and_then(|n| n.to_str())
-        .unwrap_or("unknown");
-
-    // Use the new TestRunner for consistent interface
-    let runner = TestRunner::new(wasm_path, project_name).verbose(verbose);
     let result = runner.run_test(test_case, function)?;
 
     // Print output
-    println!("{}", result.stdout);
+    result.print();
+    if gas_report {
+        result.print_gas_report();
+    }
 
     if !result.success {
-        println!("{}", result.stderr.red());
-
-        // Special handling for "failure" test cases - they're expected to fail
-        if test_case == "failure" {
-            return Ok(());
-        }
-
         if let Some(desc) = result.error_description() {
             println!();
             println!("{}: {}", "Error".red().bold(), desc);
@@ -749,3 +803,116 @@ pub fn run_test(
 
     Ok(())
 }
+
+/// Runs a test case in the interactive debugger, then prints its result like `craft test`
+pub fn debug(
+    runner: &TestRunner,
+    test_case: &str,
+    function: Option<&str>,
+    breakpoints: &[String],
+) -> Result<()> {
+    let result = runner.run_debug(test_case, function, breakpoints)?;
+    println!();
+    result.print();
+    if !result.success {
+        anyhow::bail!("Test '{}' failed", test_case);
+    }
+    Ok(())
+}
+
+/// Runs a test case, recording its host function calls to `tape`, then prints its result like
+/// `craft test`
+pub fn record(
+    runner: &TestRunner,
+    test_case: &str,
+    function: Option<&str>,
+    tape: &Path,
+) -> Result<()> {
+    let result = runner.run_record(test_case, function, tape)?;
+    result.print();
+    if !result.success {
+        anyhow::bail!("Test '{}' failed", test_case);
+    }
+    Ok(())
+}
+
+/// Replays a tape into the contract and fails if its host calls or result differ from the tape's
+pub fn replay(runner: &TestRunner, tape: &Path) -> Result<()> {
+    let result = runner.run_replay(tape)?;
+    result.print();
+    if !result.matches() {
+        anyhow::bail!("Replay of {} diverged from the tape", tape.display());
+    }
+    Ok(())
+}
+
+/// Runs a test case over a range of ledger sequences or close times and prints where its
+/// result changes
+pub fn run_sweep(
+    runner: &TestRunner,
+    test_case: &str,
+    function: Option<&str>,
+    sweep: &SweepSpec,
+) -> Result<()> {
+    let result = runner
+        .run_sweep(test_case, function, sweep)
+        .map_err(|e| anyhow::anyhow!("Sweep of '{}' failed: {}", test_case, e))?;
+    result.print();
+    Ok(())
+}
+
+/// Runs a test case under varied host conditions and fails if the runs disagree
+pub fn check_determinism(
+    runner: &TestRunner,
+    test_case: &str,
+    function: Option<&str>,
+) -> Result<()> {
+    let result = runner.run_determinism_check(test_case, function)?;
+    result.print();
+    if !result.deterministic() {
+        anyhow::bail!("Test '{}' is not deterministic", test_case);
+    }
+    Ok(())
+}
+
+/// Fuzzes a test case's fixture, printing each kind of failure found and where its minimal
+/// fixture was written
+pub fn fuzz(
+    runner: &TestRunner,
+    test_case: &str,
+    function: Option<&str>,
+    runs: u32,
+    seed: Option<u64>,
+) -> Result<()> {
+    let findings = runner.run_fuzz(test_case, function, runs, seed)?;
+    if findings.is_empty() {
+        println!("{}", "No failures found".green());
+        return Ok(());
+    }
+    for finding in &findings {
+        finding.print();
+    }
+    anyhow::bail!(
+        "Fuzzing '{}' found {} kind(s) of failure",
+        test_case,
+        findings.len()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_name() {
+        let projects = std::env::current_dir().unwrap().join("projects");
+        assert_eq!(
+            project_name(&projects.join("examples/notary")).unwrap(),
+            "examples/notary"
+        );
+        assert_eq!(
+            project_name(Path::new("/elsewhere/notary")).unwrap(),
+            "/elsewhere/notary"
+        );
+    }
+}
@@ -1,21 +1,58 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use colored::*;
+use std::collections::BTreeMap;
 use std::path::Path;
-use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use wasm_host_simulator::data_provider::error_code_to_string;
+use wasm_host_simulator::debugger::{LineMap, Session};
+use wasm_host_simulator::determinism::DeterminismReport;
+use wasm_host_simulator::limits::Limits;
+use wasm_host_simulator::run_log::HostCallStats;
+use wasm_host_simulator::simulator::{
+    self, Fixture, SETUP_ERROR, Simulator, TestOutcome, TestReport,
+};
+use wasm_host_simulator::sweep::{self, Sweep, SweepReport};
+use wasm_host_simulator::tape::{ReplayReport, Tape};
+
+pub use wasm_host_simulator::simulator::HeaderOverrides;
+pub use wasm_host_simulator::sweep::SweepField;
+
+/// A run of the contract over a range of ledger sequences or close times
+#[derive(Debug, Clone)]
+pub struct SweepSpec {
+    pub field: SweepField,
+    /// Inclusive range, as `FROM..TO`
+    pub range: String,
+    /// Run only every Nth value, bisecting between runs whose results differ
+    pub step: u32,
+}
 
-/// A unified test runner that simplifies wasm-host-simulator testing
+/// A unified test runner that runs contracts in the wasm-host-simulator, in process
 pub struct TestRunner {
-    dir: std::path::PathBuf,
+    wasm_path: std::path::PathBuf,
     project: String,
     verbose: bool,
+    gas_report: bool,
+    gas_cap: Option<String>,
+    limits: Limits,
+    header: HeaderOverrides,
 }
 
 impl TestRunner {
-    pub fn new(dir: &Path, project: &str) -> Self {
+    /// `wasm_path` is the exact module under test, usually the artifact returned by
+    /// `commands::build`.
+    pub fn new(wasm_path: &Path, project: &str) -> Self {
         Self {
-            dir: dir.to_path_buf(),
+            wasm_path: wasm_path.to_path_buf(),
             project: project.to_string(),
             verbose: false,
+            gas_report: false,
+            gas_cap: None,
+            limits: Limits::default(),
+            header: HeaderOverrides::default(),
         }
     }
 
@@ -24,99 +61,383 @@ impl TestRunner {
         self
     }
 
-    /// Run a single test case
-    pub fn run_test(&self, test_case: &str, function: Option<&str>) -> Result<TestResult> {
-        println!("{}", format!("Running test case: {test_case}").cyan());
+    /// Print each test's gas report when running all tests
+    pub fn gas_report(mut self, gas_report: bool) -> Self {
+        self.gas_report = gas_report;
+        self
+    }
 
-        // Ensure wasm-host-simulator is built
-        self.ensure_wasm_host_simulator_built()?;
+    /// Gas cap for each run: a number of gas units or `none`. When unset, each run is capped
+    /// at the fixture's `ComputationAllowance`.
+    pub fn gas_cap(mut self, gas_cap: Option<String>) -> Self {
+        self.gas_cap = gas_cap;
+        self
+    }
 
-        let wasm_host_simulator_path = self.get_wasm_host_simulator_path()?;
+    /// Limits the module must be within to be run
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
 
-        let mut args = vec![
-            "--dir",
-            self.dir.to_str().unwrap(),
-            "--test-case",
-            test_case,
-            "--project",
-            &self.project,
-        ];
+    /// Ledger header values to use instead of the fixture's
+    pub fn header(mut self, header: HeaderOverrides) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// A simulator running `function` (or `finish`) with the runner's settings
+    fn simulator(&self, function: Option<&str>) -> Result<Simulator> {
+        let gas_cap = self
+            .gas_cap
+            .as_deref()
+            .map(simulator::parse_gas_cap)
+            .transpose()
+            .map_err(|e| anyhow!("Invalid --gas-cap: {e}"))?;
+        let simulator = Simulator::new(&self.wasm_path)
+            .map_err(|e| anyhow!(e))?
+            .function(function.unwrap_or("finish"))
+            .gas_cap(gas_cap)
+            .echo_traces(self.verbose)
+            .limits(self.limits);
+        Ok(simulator)
+    }
+
+    /// The fixtures directory of the project, projects/<project>/fixtures/
+    pub fn fixtures_root(&self) -> Result<std::path::PathBuf> {
+        Ok(std::env::current_dir()?
+            .join("projects")
+            .join(&self.project)
+            .join("fixtures"))
+    }
 
-        if let Some(func) = function {
-            args.push("--function");
-            args.push(func);
+    /// Load a test case's fixture, with the runner's ledger header overrides applied
+    fn fixture(&self, test_case: &str) -> Result<Fixture, String> {
+        let dir = self
+            .fixtures_root()
+            .map_err(|e| e.to_string())?
+            .join(test_case);
+        let mut fixture = Fixture::load(&dir, test_case, None)?;
+        let mut header = self.header.clone();
+        if let Some(hash) = &header.parent_hash {
+            header.parent_hash = Some(
+                simulator::parse_hash(hash).map_err(|e| format!("Invalid --parent-hash: {e}"))?,
+            );
         }
+        header.apply(&mut fixture.data);
+        Ok(fixture)
+    }
 
-        if self.verbose {
-            args.push("--verbose");
+    /// Run a test case under rippled's host conditions and under varied ones, and compare
+    pub fn run_determinism_check(
+        &self,
+        test_case: &str,
+        function: Option<&str>,
+    ) -> Result<DeterminismResult> {
+        println!(
+            "{}",
+            format!("Checking determinism of test case: {test_case}").cyan()
+        );
+        let simulator = self.simulator(function)?;
+        let fixture = self.fixture(test_case).map_err(|e| anyhow!(e))?;
+        if fixture.scenario.is_some() {
+            anyhow::bail!("A scenario cannot be checked for determinism");
+        }
+        Ok(DeterminismResult {
+            function: simulator.function_name().to_string(),
+            test_case: test_case.to_string(),
+            report: simulator.check_determinism(&fixture.data)?,
+        })
+    }
+
+    /// Fuzz a test case's fixture with `runs` mutated copies, writing a minimal fixture for
+    /// each kind of failure found into the project's fixtures
+    pub fn run_fuzz(
+        &self,
+        test_case: &str,
+        function: Option<&str>,
+        runs: u32,
+        seed: Option<u64>,
+    ) -> Result<Vec<FuzzFinding>> {
+        let simulator = self.simulator(function)?;
+        let fixture = self.fixture(test_case).map_err(|e| anyhow!(e))?;
+        if fixture.scenario.is_some() {
+            anyhow::bail!("A scenario cannot be fuzzed");
+        }
+        let seed = seed.unwrap_or_else(wasm_host_simulator::fuzz::seed_from_time);
+        println!(
+            "{}",
+            format!("Fuzzing test case {test_case} with {runs} cases (seed {seed})").cyan()
+        );
+
+        let root = self.fixtures_root()?;
+        let mut findings = Vec::new();
+        for case in simulator.fuzz(&fixture.data, runs, seed)? {
+            let name = case.fixture_name(seed);
+            let dir = wasm_host_simulator::fuzz::write_fixture(
+                &root,
+                &fixture.dir,
+                &fixture.data,
+                &name,
+                &case,
+            )
+            .map_err(|e| anyhow!("Cannot write fixture {name}: {e}"))?;
+            findings.push(FuzzFinding {
+                kind: case.finding.kind.name(),
+                detail: case.finding.detail,
+                case: case.case,
+                count: case.count,
+                mutations: case.mutations.iter().map(|m| m.to_string()).collect(),
+                fixture: dir,
+            });
+        }
+        Ok(findings)
+    }
+
+    /// Run a single test case
+    pub fn run_test(&self, test_case: &str, function: Option<&str>) -> Result<TestResult> {
+        println!("{}", format!("Running test case: {test_case}").cyan());
+        self.run_case(test_case, function, self.verbose)
+    }
+
+    /// Run a single test case, printing nothing unless `echo_traces`, so that several can run at
+    /// once
+    fn run_case(
+        &self,
+        test_case: &str,
+        function: Option<&str>,
+        echo_traces: bool,
+    ) -> Result<TestResult> {
+        let started = Instant::now();
+        let simulator = self.simulator(function)?.echo_traces(echo_traces);
+        let report = match self.fixture(test_case) {
+            Ok(fixture) => simulator.run_fixture(&fixture),
+            Err(e) => TestReport::failed(simulator.function_name(), test_case, SETUP_ERROR, &e),
+        };
+        Ok(test_result(report, started))
+    }
+
+    /// Run a test case in the interactive debugger, stopping at every call to each of
+    /// `breakpoints`, or at the first host call if there are none
+    pub fn run_debug(
+        &self,
+        test_case: &str,
+        function: Option<&str>,
+        breakpoints: &[String],
+    ) -> Result<TestResult> {
+        println!("{}", format!("Debugging test case: {test_case}").cyan());
+        let started = Instant::now();
+        let simulator = self.simulator(function)?;
+        let fixture = self.fixture(test_case).map_err(|e| anyhow!(e))?;
+
+        let lines = LineMap::from_wasm(&std::fs::read(&self.wasm_path)?);
+        if lines.is_none() {
+            println!(
+                "{}",
+                "The module has no DWARF line tables, so no source lines will be shown".yellow()
+            );
+        }
+        println!("Type 'help' at the prompt for commands");
+        let mut session = Session::new(std::io::stdin().lock(), std::io::stdout()).lines(lines);
+        for function in breakpoints {
+            session = session.break_on(function);
         }
 
-        let output = Command::new(&wasm_host_simulator_path)
-            .args(&args)
-            .output()
-            .map_err(|e| anyhow::anyhow!("Failed to run wasm-host-simulator: {}", e))?;
+        let report = simulator.debug_fixture(&fixture, Box::new(session));
+        Ok(test_result(report, started))
+    }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
+    /// Run a test case, recording each host function call to the tape file `tape`
+    pub fn run_record(
+        &self,
+        test_case: &str,
+        function: Option<&str>,
+        tape: &Path,
+    ) -> Result<TestResult> {
+        println!("{}", format!("Recording test case: {test_case}").cyan());
+        let started = Instant::now();
+        let simulator = self.simulator(function)?;
+        let fixture = self.fixture(test_case).map_err(|e| anyhow!(e))?;
+        let (report, recorded) = simulator.record_fixture(&fixture);
+        if let Some(recorded) = recorded {
+            recorded
+                .save(tape)
+                .map_err(|e| anyhow!("Cannot write tape {}: {e}", tape.display()))?;
+            println!(
+                "{} host call(s) recorded to {}",
+                recorded.calls.len(),
+                tape.display()
+            );
+        }
+        Ok(test_result(report, started))
+    }
 
-        Ok(TestResult {
-            success: output.status.success(),
+    /// Run the function recorded on the tape file `tape`, answering its host function calls
+    /// from the tape, and compare the calls it makes with the tape's
+    pub fn run_replay(&self, tape: &Path) -> Result<ReplayResult> {
+        println!("{}", format!("Replaying tape: {}", tape.display()).cyan());
+        let recorded = Tape::load(tape).map_err(|e| anyhow!(e))?;
+        let simulator = self.simulator(Some(&recorded.function))?;
+        let report = simulator.replay(&recorded)?;
+        Ok(ReplayResult {
+            tape: tape.to_path_buf(),
+            recorded,
+            report,
+        })
+    }
+
+    /// Run a test case over a range of ledger sequences or close times
+    pub fn run_sweep(
+        &self,
+        test_case: &str,
+        function: Option<&str>,
+        sweep: &SweepSpec,
+    ) -> Result<SweepResult> {
+        println!("{}", format!("Sweeping test case: {test_case}").cyan());
+
+        let (from, to) = sweep::parse_range(&sweep.range).map_err(|e| anyhow!(e))?;
+        let simulator = self.simulator(function)?;
+        let fixture = self.fixture(test_case).map_err(|e| anyhow!(e))?;
+        if fixture.scenario.is_some() {
+            anyhow::bail!("A scenario cannot be swept");
+        }
+        let sweep = Sweep {
+            field: sweep.field,
+            from,
+            to,
+            step: sweep.step.max(1),
+        };
+        Ok(SweepResult {
+            function: simulator.function_name().to_string(),
             test_case: test_case.to_string(),
-            stdout: stdout.to_string(),
-            stderr: stderr.to_string(),
-            error_code: self.extract_error_code(&stderr),
+            report: simulator.sweep(&fixture.data, &sweep)?,
         })
     }
 
-    /// Run all test cases for a project
-    pub fn run_all_tests(&self, project: &str) -> Result<Vec<TestResult>> {
+    /// Run all test cases for a project on up to `jobs` threads. Each test's result is printed
+    /// once it and every test before it have finished, so the output is in discovery order
+    /// whatever order the tests finish in.
+    pub fn run_all_tests(&self, project: &str, jobs: usize) -> Result<Vec<TestResult>> {
         let test_cases = super::discover_test_cases(project)?;
-        let mut results = Vec::new();
+        let jobs = jobs.clamp(1, test_cases.len().max(1));
+        // Traces of tests running at once would interleave, so they are only echoed when the
+        // tests run one at a time
+        let echo_traces = self.verbose && jobs == 1;
 
         println!(
             "{}",
             format!(
-                "Running {} test cases for project '{}'",
+                "Running {} test cases for project '{}' on {} thread(s)",
                 test_cases.len(),
-                project
+                project,
+                jobs
             )
             .cyan()
         );
         println!();
 
-        for test_case in test_cases {
-            let result = self.run_test(&test_case, None)?;
-            let status_icon = if result.success { "✅" } else { "❌" };
-
-            println!(
-                "{} {} - {}",
-                status_icon,
-                result.test_case,
-                if result.success {
-                    "PASSED".green()
-                } else {
-                    "FAILED".red()
+        let started = Instant::now();
+        let next = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::channel();
+        let mut results = Vec::with_capacity(test_cases.len());
+        thread::scope(|scope| -> Result<()> {
+            for _ in 0..jobs {
+                let (sender, next, test_cases) = (sender.clone(), &next, &test_cases);
+                scope.spawn(move || {
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(test_case) = test_cases.get(i) else {
+                            break;
+                        };
+                        if sender
+                            .send((i, self.run_case(test_case, None, echo_traces)))
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            let mut finished = BTreeMap::new();
+            for (i, result) in receiver {
+                finished.insert(i, result);
+                while let Some(result) = finished.remove(&results.len()) {
+                    let result = match result {
+                        Ok(result) => result,
+                        Err(e) => {
+                            // Stop handing out tests; those already running finish first
+                            next.store(test_cases.len(), Ordering::Relaxed);
+                            return Err(e);
+                        }
+                    };
+                    self.print_result(&result);
+                    results.push(result);
                 }
-            );
+            }
+            Ok(())
+        })?;
 
-            if !result.success
-                && self.verbose
-                && let Some(code) = &result.error_code
-            {
-                println!("  Error code: {}", code.yellow());
+        self.print_summary(&results, started.elapsed());
+        Ok(results)
+    }
+
+    /// Print one test's line of `run_all_tests`, with its run record when verbose
+    fn print_result(&self, result: &TestResult) {
+        let status_icon = if result.success { "✅" } else { "❌" };
+        println!(
+            "{} {} - {}",
+            status_icon,
+            result.test_case,
+            if result.success {
+                "PASSED".green()
+            } else if result.report.outcome_kind() == "out_of_gas" {
+                "FAILED (out of gas)".red()
+            } else if result.report.outcome_kind() == SETUP_ERROR {
+                "FAILED (setup error)".red()
+            } else {
+                "FAILED".red()
             }
+        );
 
-            results.push(result);
+        if self.verbose {
+            result.print();
+        }
+        if self.gas_report {
+            result.print_gas_report();
         }
+    }
 
-        // Summary
+    fn print_summary(&self, results: &[TestResult], elapsed: Duration) {
         let passed = results.iter().filter(|r| r.success).count();
         let failed = results.len() - passed;
+        let width = results.iter().map(|r| r.test_case.len()).max().unwrap_or(0);
 
         println!();
         println!("{}", "Test Summary:".bold());
+        for result in results {
+            let status = if result.success {
+                "PASSED".green()
+            } else {
+                "FAILED".red()
+            };
+            let gas = result
+                .report
+                .gas_used()
+                .map_or_else(|| "-".to_string(), |gas| gas.to_string());
+            println!(
+                "  {:<width$}  {}  {:>10}  {:>8} gas",
+                result.test_case,
+                status,
+                format_duration(result.elapsed),
+                gas
+            );
+        }
+        println!();
         println!("  {} Passed: {}", "•".green(), passed);
         println!("  {} Failed: {}", "•".red(), failed);
+        println!("  {} Time: {}", "•".cyan(), format_duration(elapsed));
 
         if failed > 0 && !self.verbose {
             println!();
@@ -125,50 +446,296 @@ impl TestRunner {
                 "Run with --verbose to see detailed error output".yellow()
             );
         }
+    }
+}
 
-        Ok(results)
+/// Print a test case's run as trace output followed by a summary
+fn print_report(report: &TestReport) {
+    for run in report.runs() {
+        for trace in &run.log.traces {
+            println!(
+                "{} {}",
+                format!("[{}]", trace.origin).dimmed(),
+                trace.message
+            );
+        }
+        for error in &run.log.host_errors {
+            println!(
+                "{} {} returned {}",
+                "[host error]".yellow(),
+                error.function,
+                error_code_to_string(error.code as i64)
+            );
+        }
+    }
+    if let TestOutcome::Scenario(steps) = &report.outcome {
+        for step in steps {
+            let result = match &step.run.outcome {
+                Ok(rv) => format!("returned {rv}"),
+                Err(e) if e.kind() == "out_of_gas" => "ran out of gas".to_string(),
+                Err(e) if e.kind() == "trap" => "trapped".to_string(),
+                Err(_) => "did not run".to_string(),
+            };
+            let gas = step
+                .run
+                .gas_used
+                .map_or(String::new(), |gas| format!(", {gas} gas"));
+            let verdict = if step.failures.is_empty() {
+                "ok".green()
+            } else {
+                "FAILED".red()
+            };
+            println!(
+                "{} {result}{gas} {verdict}",
+                format!("[{}]", step.name).bold()
+            );
+            if let Err(error) = &step.run.outcome {
+                println!("  {}", error.to_string().red());
+            }
+        }
+    }
+    println!("Function:     {}", report.function);
+    println!("Test case:    {}", report.test_case);
+    match report.return_value() {
+        Some(rv) => println!("Return value: {rv}"),
+        None => println!("Return value: -"),
+    }
+    if let Some(gas) = report.gas_used() {
+        println!("Gas used:     {gas}");
+    }
+    if let Some(error) = report.error() {
+        let label = match report.outcome_kind() {
+            "out_of_gas" => "Out of gas:  ",
+            "trap" => "Trap:        ",
+            SETUP_ERROR => "Setup error: ",
+            _ => "Error:       ",
+        };
+        println!("{} {}", label, error.red());
+    }
+    for failure in report.failures() {
+        println!("{} {}", "Expectation failed:".red(), failure);
     }
+}
 
-    fn ensure_wasm_host_simulator_built(&self) -> Result<()> {
-        let wasm_host_simulator_path = self.get_wasm_host_simulator_path()?;
+/// Print a run's gas as a table, host functions ordered by the gas they consumed
+fn print_gas_report(report: &TestReport) {
+    let TestOutcome::Run { run, .. } = &report.outcome else {
+        println!("{}", "No gas profile recorded for this run".yellow());
+        return;
+    };
+    let fmt_gas = |gas: Option<u64>| gas.map_or("-".to_string(), |g| g.to_string());
+    let total = run.gas_used.map(u64::from);
+
+    let mut host_functions: Vec<&HostCallStats> = run.log.host_calls.iter().collect();
+    host_functions.sort_by(|a, b| b.gas.cmp(&a.gas).then(a.function.cmp(b.function)));
+
+    println!();
+    println!("{}", format!("Gas report: {}", report.test_case).bold());
+    println!("  {:<40} {:>7} {:>10}", "Source", "Calls", "Gas");
+    println!("  {}", "-".repeat(59));
+    println!(
+        "  {:<40} {:>7} {:>10}",
+        "Wasm instructions",
+        "",
+        fmt_gas(total.map(|total| total.saturating_sub(run.log.host_gas())))
+    );
+    for host in host_functions {
+        println!("  {:<40} {:>7} {:>10}", host.function, host.calls, host.gas);
+    }
+    println!("  {}", "-".repeat(59));
+    println!(
+        "  {:<40} {:>7} {:>10}",
+        "Total",
+        run.log.host_calls.iter().map(|h| h.calls).sum::<u32>(),
+        fmt_gas(total)
+    );
+}
+
+/// A sweep of a test case
+pub struct SweepResult {
+    pub function: String,
+    pub test_case: String,
+    pub report: SweepReport,
+}
 
-        if !wasm_host_simulator_path.exists() {
+impl SweepResult {
+    /// Print the ranges of the sweep and where the result flips
+    pub fn print(&self) {
+        let report = &self.report;
+        let field = report.sweep.field.header_field();
+        println!(
+            "Sweep of {} over {}..{}: {} runs of '{}' on {}",
+            field, report.sweep.from, report.sweep.to, report.runs, self.function, self.test_case
+        );
+        for (from, to, result) in report.ranges() {
+            println!("  {:>21}  {}", format!("{from}..{to}"), result);
+        }
+        if report.flips.is_empty() {
+            println!("{}", "The result does not change over the range".yellow());
+        }
+        for flip in &report.flips {
+            println!(
+                "{} {} {}: {} -> {}",
+                "Result flips at".bold(),
+                field,
+                flip.at.to_string().bold(),
+                flip.before,
+                flip.after
+            );
+        }
+    }
+}
+
+/// A determinism check of a test case
+pub struct DeterminismResult {
+    pub function: String,
+    pub test_case: String,
+    pub report: DeterminismReport,
+}
+
+impl DeterminismResult {
+    /// Whether every run agreed with the run under rippled's conditions
+    pub fn deterministic(&self) -> bool {
+        self.report.differences.is_empty()
+    }
+
+    /// Print whether the runs agreed and each way they did not
+    pub fn print(&self) {
+        let report = &self.report;
+        println!(
+            "Determinism check of '{}' on {}: {} runs, {}",
+            self.function, self.test_case, report.runs, report.result
+        );
+        if self.deterministic() {
             println!(
                 "{}",
-                "Building wasm-host-simulator testing environment...".yellow()
+                "Every run agreed on return value, gas, traces and Data".green()
+            );
+        }
+        for d in &report.differences {
+            println!(
+                "  {} {} with {}: {} -> {}",
+                d.aspect.bold(),
+                "differs".red(),
+                d.conditions,
+                d.expected,
+                d.actual
             );
+        }
+    }
+}
 
-            let status = Command::new("cargo")
-                .args(["build", "--release", "-p", "wasm-host-simulator"])
-                .status()
-                .map_err(|e| anyhow::anyhow!("Failed to run cargo build: {}", e))?;
+/// A replay of a tape
+pub struct ReplayResult {
+    pub tape: std::path::PathBuf,
+    pub recorded: Tape,
+    pub report: ReplayReport,
+}
 
-            if !status.success() {
-                return Err(anyhow::anyhow!(
-                    "Failed to build wasm-host-simulator. Make sure you're in the workspace root directory."
-                ));
-            }
+impl ReplayResult {
+    /// Whether every host call and the result matched the tape
+    pub fn matches(&self) -> bool {
+        self.report.differences.is_empty()
+    }
 
-            println!("{}", "wasm-host-simulator built successfully!".green());
+    /// Print whether the run matched the tape and each place it did not
+    pub fn print(&self) {
+        let report = &self.report;
+        println!(
+            "Replay of '{}' from {}: {} of {} host call(s), {}",
+            self.recorded.function,
+            self.tape.display(),
+            report.replayed,
+            self.recorded.calls.len(),
+            report.outcome
+        );
+        if self.matches() {
+            println!(
+                "{}",
+                "Every host call and the result matched the tape".green()
+            );
+        }
+        for d in &report.differences {
+            let at = match d.call {
+                Some(call) => format!("call {call}"),
+                None => "result".to_string(),
+            };
+            println!(
+                "  {} {}: expected {}, got {}",
+                at.bold(),
+                "differs".red(),
+                d.expected,
+                d.actual
+            );
         }
+    }
+}
 
-        Ok(())
+/// A kind of failure fuzzing found, with the smallest set of mutations that still produces it
+#[derive(Debug)]
+pub struct FuzzFinding {
+    /// `trap`, `out-of-gas`, `error`, `pointer-out-of-bound` or `nondeterminism`
+    pub kind: &'static str,
+    pub detail: String,
+    /// The first case that failed this way
+    pub case: u32,
+    /// How many cases failed this way
+    pub count: u32,
+    pub mutations: Vec<String>,
+    /// Where the minimal fixture was written
+    pub fixture: std::path::PathBuf,
+}
+
+impl FuzzFinding {
+    pub fn print(&self) {
+        println!(
+            "{} {} (first in case {}, {} case(s) in all)",
+            format!("{}:", self.kind).red().bold(),
+            self.detail,
+            self.case,
+            self.count
+        );
+        for mutation in &self.mutations {
+            println!("  {mutation}");
+        }
+        println!("  written to {}", self.fixture.display());
     }
+}
 
-    fn get_wasm_host_simulator_path(&self) -> Result<std::path::PathBuf> {
-        let path = std::env::current_dir()?
-            .join("target")
-            .join("release")
-            .join("wasm-host-simulator");
-        Ok(path)
+/// The result of a test case, from the simulator's report of it
+fn test_result(report: TestReport, started: Instant) -> TestResult {
+    TestResult {
+        success: report.passed(),
+        test_case: report.test_case.clone(),
+        error_code: error_code(&report),
+        report,
+        elapsed: started.elapsed(),
     }
+}
+
+/// The contract's own error code (a negative return value), or else the last error a host
+/// function returned to it
+fn error_code(report: &TestReport) -> Option<String> {
+    report
+        .return_value()
+        .filter(|rv| *rv < 0)
+        .or_else(|| {
+            report
+                .runs()
+                .iter()
+                .rev()
+                .find_map(|run| run.log.host_errors.last())
+                .map(|e| e.code)
+        })
+        .map(|code| code.to_string())
+}
 
-    fn extract_error_code(&self, stderr: &str) -> Option<String> {
-        stderr
-            .lines()
-            .find(|line| line.contains("error code:"))
-            .and_then(|line| line.split("error code:").nth(1))
-            .map(|s| s.trim().to_string())
+/// A duration as milliseconds, or seconds from ten seconds up
+fn format_duration(duration: Duration) -> String {
+    if duration.as_secs() >= 10 {
+        format!("{:.1} s", duration.as_secs_f64())
+    } else {
+        format!("{:.1} ms", duration.as_secs_f64() * 1000.0)
     }
 }
 
@@ -176,9 +743,23 @@ impl TestRunner {
 pub struct TestResult {
     pub success: bool,
     pub test_case: String,
-    pub stdout: String,
-    pub stderr: String,
+    /// The simulator's report of the run
+    pub report: TestReport,
     pub error_code: Option<String>,
+    /// Wall time of the run, including loading the fixture
+    pub elapsed: Duration,
+}
+
+impl TestResult {
+    /// Print the run as trace output followed by a summary
+    pub fn print(&self) {
+        print_report(&self.report);
+    }
+
+    /// Print the run's gas profile as a table
+    pub fn print_gas_report(&self) {
+        print_gas_report(&self.report);
+    }
 }
 
 #[allow(dead_code)]
@@ -224,11 +805,9 @@ pub async fn quick_test(project: &str, test_case: Option<&str>, verbose: bool) -
         let result = runner.run_test(case, None)?;
 
         // Print output
-        println!("{}", result.stdout);
+        result.print();
 
         if !result.success {
-            println!("{}", result.stderr.red());
-
             if let Some(desc) = result.error_description() {
                 println!();
                 println!("{}: {}", "Error".red().bold(), desc);
@@ -238,7 +817,7 @@ pub async fn quick_test(project: &str, test_case: Option<&str>, verbose: bool) -
         }
     } else {
         // Run all tests
-        let results = runner.run_all_tests(project)?;
+        let results = runner.run_all_tests(project, 1)?;
 
         // Check if any failed
         if results.iter().any(|r| !r.success) {
@@ -248,3 +827,55 @@ pub async fn quick_test(project: &str, test_case: Option<&str>, verbose: bool) -
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_host_simulator::run_log::{HostCallError, RunLog};
+    use wasm_host_simulator::vm_wamr::RunResult;
+
+    fn run(rv: i32, host_errors: Vec<HostCallError>) -> TestReport {
+        TestReport {
+            function: "finish".to_string(),
+            test_case: "case".to_string(),
+            outcome: TestOutcome::Run {
+                run: RunResult {
+                    outcome: Ok(rv),
+                    gas_used: Some(100),
+                    log: RunLog {
+                        host_errors,
+                        ..RunLog::default()
+                    },
+                    data: None,
+                    ledger_object: serde_json::Value::Null,
+                },
+                failures: vec![],
+            },
+        }
+    }
+
+    #[test]
+    fn test_error_code() {
+        let host_error = || HostCallError {
+            function: "get_tx_field",
+            code: -2,
+        };
+        assert_eq!(error_code(&run(1, vec![])), None);
+        assert_eq!(
+            error_code(&run(-7, vec![host_error()])).as_deref(),
+            Some("-7")
+        );
+        assert_eq!(
+            error_code(&run(0, vec![host_error()])).as_deref(),
+            Some("-2")
+        );
+
+        let result = test_result(
+            TestReport::failed("finish", "case", SETUP_ERROR, "no fixture"),
+            Instant::now(),
+        );
+        assert!(!result.success);
+        assert_eq!(result.report.outcome_kind(), SETUP_ERROR);
+        assert_eq!(result.report.gas_used(), None);
+    }
+}
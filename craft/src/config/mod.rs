@@ -1,6 +1,7 @@
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use wasm_host_simulator::limits::Limits;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
@@ -8,6 +9,9 @@ pub struct Config {
     pub build_mode: BuildMode,
     pub optimization_level: OptimizationLevel,
     pub project_path: PathBuf,
+    /// Limits the built module is checked against; chosen per command, so not saved
+    #[serde(skip)]
+    pub limits: Limits,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -64,6 +68,7 @@ impl Default for Config {
             build_mode: BuildMode::Release,
             optimization_level: OptimizationLevel::Small,
             project_path: std::env::current_dir().unwrap_or_default(),
+            limits: Limits::default(),
         }
     }
 }
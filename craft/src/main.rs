@@ -9,6 +9,7 @@ use colored::*;
 use inquire::Confirm;
 use inquire::Select;
 use std::io::IsTerminal;
+use wasm_host_simulator::limits::{self, Limits};
 
 /// Additional guides and how-tos
 #[cfg(doc)]
@@ -53,6 +54,9 @@ enum Commands {
         /// Run cargo fmt after building
         #[arg(long)]
         fmt: bool,
+        /// Limits profile the built module must be within: craft-devnet5 or unlimited
+        #[arg(long, value_parser = limits::profile, default_value = "craft-devnet5")]
+        limits: Limits,
         /// Set environment variable(s) for the build (repeatable): KEY=VALUE
         #[arg(long = "env", value_name = "KEY=VALUE")]
         envs: Vec<String>,
@@ -60,6 +64,17 @@ enum Commands {
         #[arg(last = true)]
         cargo_args: Vec<String>,
     },
+    /// Check a built WASM module's entry point, imports and features before deploying it
+    Check {
+        /// Path to the .wasm file
+        wasm: std::path::PathBuf,
+        /// Entry point the module must export as () -> i32
+        #[arg(short, long, default_value = "finish")]
+        function: String,
+        /// Limits profile the module must be within: craft-devnet5 or unlimited
+        #[arg(long, value_parser = limits::profile, default_value = "craft-devnet5")]
+        limits: Limits,
+    },
     /// Deploy a built WASM module to WASM Devnet
     Deploy {
         /// Project name under projects directory (or path to .wasm)
@@ -91,6 +106,18 @@ enum Commands {
         /// Run all test cases
         #[arg(long, conflicts_with = "case")]
         all: bool,
+        /// Number of test cases to run at once with --all
+        #[arg(
+            short,
+            long,
+            default_value_t = 1,
+            requires = "all",
+            conflicts_with = "check_determinism"
+        )]
+        jobs: usize,
+        /// Run a scenario: a test case whose scenario.json lists transactions run in turn
+        #[arg(long, conflicts_with_all = ["case", "all"])]
+        scenario: Option<String>,
         /// Function to test (defaults to 'finish')
         #[arg(short, long)]
         function: Option<String>,
@@ -103,6 +130,101 @@ enum Commands {
         /// List available test cases
         #[arg(long)]
         list: bool,
+        /// Print gas used per host function and by Wasm instructions
+        #[arg(long)]
+        gas_report: bool,
+        /// Gas cap for each run, or 'none' (defaults to the fixture's ComputationAllowance)
+        #[arg(long)]
+        gas_cap: Option<String>,
+        /// Limits profile the module must be within to be run: craft-devnet5 or unlimited
+        #[arg(long, value_parser = limits::profile, default_value = "craft-devnet5")]
+        limits: Limits,
+        /// Bytes of stack to run the module with, instead of the limits profile's 131072
+        #[arg(long, value_name = "BYTES")]
+        stack_size: Option<u32>,
+        /// Ledger sequence to run at, overriding the fixture's ledger_header.json
+        #[arg(long)]
+        ledger_seq: Option<u32>,
+        /// Parent close time to run at, in seconds since the Ripple epoch
+        #[arg(long)]
+        close_time: Option<u32>,
+        /// Parent ledger hash to run with, as 64 hex characters
+        #[arg(long)]
+        parent_hash: Option<String>,
+        /// Run over a range of ledger sequences, FROM..TO, and report where the result flips
+        #[arg(long, conflicts_with_all = ["all", "scenario", "ledger_seq", "sweep_close_time"])]
+        sweep_ledger_seq: Option<String>,
+        /// Run over a range of close times, FROM..TO, and report where the result flips
+        #[arg(long, conflicts_with_all = ["all", "scenario", "close_time"])]
+        sweep_close_time: Option<String>,
+        /// Run only every Nth value of a sweep, bisecting between runs whose results differ
+        #[arg(long, default_value_t = 1)]
+        sweep_step: u32,
+        /// Run each test case under varied host conditions and fail if the results differ
+        #[arg(long, conflicts_with_all = ["scenario", "sweep_ledger_seq", "sweep_close_time"])]
+        check_determinism: bool,
+        /// Record every host function call of the test case to this tape file
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["all", "scenario", "sweep_ledger_seq", "sweep_close_time", "check_determinism"])]
+        record_tape: Option<std::path::PathBuf>,
+        /// Answer each host function call from this tape instead of fixtures, and fail where
+        /// the calls differ from the tape's
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["case", "all", "scenario", "record_tape", "sweep_ledger_seq", "sweep_close_time", "check_determinism"])]
+        replay_tape: Option<std::path::PathBuf>,
+    },
+    /// Run a contract against thousands of randomly mutated copies of a fixture
+    Fuzz {
+        /// Project name to fuzz
+        project: String,
+        /// Test case whose fixture is mutated (defaults to 'success')
+        #[arg(short, long, default_value = "success")]
+        case: String,
+        /// Number of mutated fixtures to run
+        #[arg(long, default_value_t = 1000)]
+        runs: u32,
+        /// Seed to repeat an earlier fuzz run (defaults to the current time)
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Function to fuzz (defaults to 'finish')
+        #[arg(short, long)]
+        function: Option<String>,
+        /// Build before fuzzing
+        #[arg(long, default_value_t = true)]
+        build: bool,
+        /// Gas cap for each run, or 'none' (defaults to the fixture's ComputationAllowance)
+        #[arg(long)]
+        gas_cap: Option<String>,
+        /// Limits profile the module must be within to be run: craft-devnet5 or unlimited
+        #[arg(long, value_parser = limits::profile, default_value = "craft-devnet5")]
+        limits: Limits,
+        /// Bytes of stack to run the module with, instead of the limits profile's 131072
+        #[arg(long, value_name = "BYTES")]
+        stack_size: Option<u32>,
+    },
+    /// Step through a contract's host function calls in an interactive debugger
+    Debug {
+        /// Project name to debug
+        project: String,
+        /// Test case to run (defaults to 'success')
+        #[arg(short, long, default_value = "success")]
+        case: String,
+        /// Function to debug (defaults to 'finish')
+        #[arg(short, long)]
+        function: Option<String>,
+        /// Stop at every call to this host function (repeatable); without any, stop at the first
+        #[arg(long = "break", value_name = "HOST_FUNCTION")]
+        breakpoints: Vec<String>,
+        /// Build in debug mode before debugging, so source lines can be shown
+        #[arg(long, default_value_t = true)]
+        build: bool,
+        /// Gas cap for the run, or 'none' (defaults to the fixture's ComputationAllowance)
+        #[arg(long)]
+        gas_cap: Option<String>,
+        /// Limits profile the module must be within to be run: craft-devnet5 or unlimited
+        #[arg(long, value_parser = limits::profile, default_value = "craft-devnet5")]
+        limits: Limits,
+        /// Bytes of stack to run the module with, instead of the limits profile's 131072
+        #[arg(long, value_name = "BYTES")]
+        stack_size: Option<u32>,
     },
     /// Check if rippled is running and start it if not
     StartRippled {
@@ -127,6 +249,42 @@ enum Commands {
     // },
     /// Open the XRPL Explorer for a local rippled instance
     OpenExplorer,
+    /// Manage test fixtures
+    Fixture {
+        #[command(subcommand)]
+        action: FixtureAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum FixtureAction {
+    /// Capture a fixture from a running rippled over JSON-RPC
+    Capture {
+        /// Project name under projects directory
+        project: String,
+        /// Fixture name, written to projects/<project>/fixtures/<name>/
+        name: String,
+        /// Hash of a validated EscrowFinish transaction
+        #[arg(long, required_unless_present = "escrow", conflicts_with = "escrow")]
+        tx: Option<String>,
+        /// Ledger object ID (keylet) of an escrow to finish
+        #[arg(long)]
+        escrow: Option<String>,
+        /// Additional ledger object ID to include, e.g. one the contract reads (repeatable)
+        #[arg(long = "object", value_name = "ID")]
+        objects: Vec<String>,
+        /// rippled JSON-RPC URL
+        #[arg(long, default_value = commands::DEFAULT_RPC_URL)]
+        rpc_url: String,
+        /// Overwrite an existing fixture
+        #[arg(long)]
+        force: bool,
+    },
+    /// Validate fixture files: JSON syntax, ledger object IDs, NFTs and field names
+    Check {
+        /// Project name under projects directory (defaults to every project with fixtures)
+        project: Option<String>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -253,6 +411,13 @@ enum DockerAction {
 //     Ok(())
 // }
 
+/// Show the simulator's debug log on stderr when verbose, and keep it quiet otherwise
+fn init_simulator_logging(verbose: bool) {
+    let level = if verbose { "debug" } else { "off" };
+    let _ = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(level))
+        .try_init();
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Provide status of rippled in Docker
@@ -301,6 +466,7 @@ async fn main() -> Result<()> {
                 debug,
                 opt,
                 fmt,
+                limits,
                 envs,
                 cargo_args,
             } => {
@@ -354,8 +520,12 @@ async fn main() -> Result<()> {
                     }
                 } else if std::io::stdout().is_terminal() {
                     // Interactive selection if TTY available
-                    let config = commands::configure().await?;
-                    commands::build(&config).await?;
+                    let config = config::Config {
+                        limits,
+                        ..commands::configure().await?
+                    };
+                    let wasm_path = commands::build(&config).await?;
+                    commands::check_module(&wasm_path, None, &config.limits)?;
                     if fmt {
                         utils::run_cargo_fmt()?;
                     }
@@ -377,6 +547,7 @@ async fn main() -> Result<()> {
                     project_path,
                     build_mode,
                     optimization_level: opt.unwrap_or(config::OptimizationLevel::Small),
+                    limits,
                     ..Default::default()
                 };
 
@@ -388,9 +559,7 @@ async fn main() -> Result<()> {
                     utils::run_cargo_fmt()?;
                 }
 
-                if !matches!(config.optimization_level, config::OptimizationLevel::None) {
-                    commands::optimize(&wasm_path, &config.optimization_level).await?;
-                }
+                commands::finish_build(&wasm_path, &config).await?;
 
                 // TODO: non-interactive mode: do not prompt for next steps.
                 // Print a concise success message and exit. Users can run follow-up commands explicitly.
@@ -399,6 +568,13 @@ async fn main() -> Result<()> {
                     format!("Build complete. WASM at: {}", wasm_path.display()).green()
                 );
             }
+            Commands::Check {
+                wasm,
+                function,
+                limits,
+            } => {
+                commands::check_module(&wasm, Some(&function), &limits)?;
+            }
             Commands::Deploy {
                 target,
                 build,
@@ -427,6 +603,7 @@ async fn main() -> Result<()> {
                             ..Default::default()
                         };
                         wasm_path = commands::build(&config).await?;
+                        commands::check_module(&wasm_path, None, &config.limits)?;
                     } else {
                         wasm_path = utils::find_wasm_output(&project_path)?;
                     }
@@ -452,16 +629,32 @@ async fn main() -> Result<()> {
             Commands::ExportHex => {
                 let config = commands::configure().await?;
                 let wasm_path = commands::build(&config).await?;
+                commands::check_module(&wasm_path, None, &config.limits)?;
                 commands::copy_wasm_hex_to_clipboard(&wasm_path).await?;
             }
             Commands::Test {
                 project,
                 case,
                 all,
+                jobs,
+                scenario,
                 function,
                 build,
                 verbose,
                 list,
+                gas_report,
+                gas_cap,
+                limits,
+                stack_size,
+                ledger_seq,
+                close_time,
+                parent_hash,
+                sweep_ledger_seq,
+                sweep_close_time,
+                sweep_step,
+                check_determinism,
+                record_tape,
+                replay_tape,
             } => {
                 // Handle list mode
                 if list {
@@ -479,7 +672,8 @@ async fn main() -> Result<()> {
                     } else {
                         utils::find_wasm_output(&config.project_path)?
                     };
-                    commands::test(&wasm_path, function).await?;
+                    let project_name = commands::project_name(&config.project_path)?;
+                    commands::test(&wasm_path, &project_name, function, gas_cap).await?;
                     return Ok(());
                 } else {
                     // Non-interactive mode
@@ -508,6 +702,7 @@ async fn main() -> Result<()> {
                         project_path,
                         build_mode: config::BuildMode::Release,
                         optimization_level: config::OptimizationLevel::Small,
+                        limits,
                         ..Default::default()
                     };
                     commands::build(&config).await?
@@ -518,25 +713,144 @@ async fn main() -> Result<()> {
                     utils::find_wasm_output(&project_path)?
                 };
 
+                init_simulator_logging(verbose);
+                let runner = commands::TestRunner::new(&wasm_path, &project_name)
+                    .verbose(verbose)
+                    .gas_cap(gas_cap)
+                    .limits(stack_size.map_or(limits, |bytes| limits.with_stack_size(bytes)))
+                    .header(commands::HeaderOverrides {
+                        ledger_seq,
+                        close_time,
+                        parent_hash,
+                    });
+
+                let sweep = match (sweep_ledger_seq, sweep_close_time) {
+                    (Some(range), _) => Some((commands::SweepField::LedgerSeq, range)),
+                    (None, Some(range)) => Some((commands::SweepField::CloseTime, range)),
+                    (None, None) => None,
+                };
+                if let Some((field, range)) = sweep {
+                    let test_case = case.as_deref().unwrap_or("success");
+                    let sweep = commands::SweepSpec {
+                        field,
+                        range,
+                        step: sweep_step,
+                    };
+                    commands::run_sweep(&runner, test_case, function.as_deref(), &sweep)?;
+                    return Ok(());
+                }
+                if let Some(tape) = replay_tape {
+                    commands::replay(&runner, &tape)?;
+                    return Ok(());
+                }
+                if let Some(tape) = record_tape {
+                    let test_case = case.as_deref().unwrap_or("success");
+                    commands::record(&runner, test_case, function.as_deref(), &tape)?;
+                    return Ok(());
+                }
+
+                if all && !check_determinism {
+                    let results = runner
+                        .gas_report(gas_report)
+                        .run_all_tests(&project_name, jobs)?;
+                    let failed: Vec<_> = results
+                        .iter()
+                        .filter(|r| !r.success)
+                        .map(|r| r.test_case.as_str())
+                        .collect();
+                    if !failed.is_empty() {
+                        anyhow::bail!("Failed test cases: {}", failed.join(", "));
+                    }
+                    return Ok(());
+                }
+
                 // Determine test cases to run
                 let test_cases = if all {
                     commands::discover_test_cases(&project_name)?
+                } else if let Some(scenario) = scenario {
+                    commands::check_scenario(&project_name, &scenario)?;
+                    vec![scenario]
                 } else if let Some(case_name) = case {
                     vec![case_name]
                 } else {
                     vec!["success".to_string()] // default
                 };
 
-                // Run tests
+                // Run tests, carrying on past failures so one bad fixture doesn't hide the rest
+                let mut failed = Vec::new();
                 for test_case in test_cases {
-                    commands::run_test(
-                        &wasm_path,
-                        &test_case,
-                        function.as_deref(),
-                        verbose,
-                        false,
-                    )?;
+                    let result = if check_determinism {
+                        commands::check_determinism(&runner, &test_case, function.as_deref())
+                    } else {
+                        commands::run_test(&runner, &test_case, function.as_deref(), gas_report)
+                    };
+                    if let Err(e) = result {
+                        println!("{}", e.to_string().red());
+                        failed.push(test_case);
+                    }
+                }
+                if !failed.is_empty() {
+                    anyhow::bail!("Failed test cases: {}", failed.join(", "));
+                }
+            }
+            Commands::Fuzz {
+                project,
+                case,
+                runs,
+                seed,
+                function,
+                build,
+                gas_cap,
+                limits,
+                stack_size,
+            } => {
+                let project_path = std::env::current_dir()?.join("projects").join(&project);
+                let wasm_path = if build {
+                    let config = config::Config {
+                        project_path,
+                        build_mode: config::BuildMode::Release,
+                        optimization_level: config::OptimizationLevel::Small,
+                        limits,
+                        ..Default::default()
+                    };
+                    commands::build(&config).await?
+                } else {
+                    utils::find_wasm_output(&project_path)?
+                };
+                init_simulator_logging(false);
+                let runner = commands::TestRunner::new(&wasm_path, &project)
+                    .gas_cap(gas_cap)
+                    .limits(stack_size.map_or(limits, |bytes| limits.with_stack_size(bytes)));
+                commands::fuzz(&runner, &case, function.as_deref(), runs, seed)?;
+            }
+            Commands::Debug {
+                project,
+                case,
+                function,
+                breakpoints,
+                build,
+                gas_cap,
+                limits,
+                stack_size,
+            } => {
+                let project_path = std::env::current_dir()?.join("projects").join(&project);
+                if build {
+                    let config = config::Config {
+                        project_path: project_path.clone(),
+                        build_mode: config::BuildMode::Debug,
+                        optimization_level: config::OptimizationLevel::None,
+                        limits,
+                        ..Default::default()
+                    };
+                    commands::build(&config).await?;
                 }
+                // The debug build, for its DWARF line tables, even if a release build exists
+                let wasm_path = utils::find_build_output(&project_path, &config::BuildMode::Debug)?;
+                init_simulator_logging(false);
+                let runner = commands::TestRunner::new(&wasm_path, &project)
+                    .gas_cap(gas_cap)
+                    .limits(stack_size.map_or(limits, |bytes| limits.with_stack_size(bytes)));
+                commands::debug(&runner, &case, function.as_deref(), &breakpoints)?;
             }
             Commands::StartRippled { foreground } => {
                 let docker_manager = docker::DockerManager::new()?;
@@ -560,6 +874,28 @@ async fn main() -> Result<()> {
             Commands::OpenExplorer => {
                 commands::open_explorer().await?;
             }
+            Commands::Fixture { action } => match action {
+                FixtureAction::Capture {
+                    project,
+                    name,
+                    tx,
+                    escrow,
+                    objects,
+                    rpc_url,
+                    force,
+                } => {
+                    let source = match (tx, escrow) {
+                        (Some(hash), _) => commands::CaptureSource::Transaction(hash),
+                        (None, Some(index)) => commands::CaptureSource::Escrow(index),
+                        (None, None) => unreachable!("clap requires --tx or --escrow"),
+                    };
+                    commands::capture_fixture(&project, &name, source, &objects, &rpc_url, force)
+                        .await?;
+                }
+                FixtureAction::Check { project } => {
+                    commands::check_fixtures(project.as_deref())?;
+                }
+            },
         },
         None => {
             // Nothing from the CLI was provided, so we'll interactively ask the user what they want to do
@@ -577,9 +913,7 @@ async fn main() -> Result<()> {
                     let config = commands::configure().await?;
                     let wasm_path = commands::build(&config).await?;
 
-                    if !matches!(config.optimization_level, config::OptimizationLevel::None) {
-                        commands::optimize(&wasm_path, &config.optimization_level).await?;
-                    }
+                    commands::finish_build(&wasm_path, &config).await?;
 
                     // TODO: non-interactive mode: do not prompt for next steps.
                     println!(
@@ -590,7 +924,8 @@ async fn main() -> Result<()> {
                 "Test WASM library function" => {
                     let config = commands::configure().await?;
                     let wasm_path = commands::build(&config).await?;
-                    commands::test(&wasm_path, None).await?;
+                    let project_name = commands::project_name(&config.project_path)?;
+                    commands::test(&wasm_path, &project_name, None, None).await?;
                 }
                 "Start rippled" => {
                     let foreground = Confirm::new("Run rippled in foreground with console output? (Can be terminated with Ctrl+C)")
@@ -659,6 +994,225 @@ mod cli_tests {
         }
     }
 
+    #[test]
+    fn test_check_parsing() {
+        let cli = Cli::parse_from(["craft", "check", "a.wasm"]);
+        match cli.command {
+            Some(Commands::Check {
+                wasm,
+                function,
+                limits,
+            }) => {
+                assert_eq!(wasm, std::path::PathBuf::from("a.wasm"));
+                assert_eq!(function, "finish");
+                assert_eq!(limits.name, "craft-devnet5");
+            }
+            other => panic!("Expected Check command, got: {other:?}"),
+        }
+        assert!(Cli::try_parse_from(["craft", "check"]).is_err());
+    }
+
+    #[test]
+    fn test_limits_parsing() {
+        let cli = Cli::parse_from(["craft", "build", "myproj"]);
+        match cli.command {
+            Some(Commands::Build { limits, .. }) => assert_eq!(limits.name, "craft-devnet5"),
+            other => panic!("Expected Build command, got: {other:?}"),
+        }
+        let cli = Cli::parse_from(["craft", "test", "myproj", "--limits", "unlimited"]);
+        match cli.command {
+            Some(Commands::Test {
+                limits, stack_size, ..
+            }) => {
+                assert_eq!(limits.name, "unlimited");
+                assert_eq!(stack_size, None);
+            }
+            other => panic!("Expected Test command, got: {other:?}"),
+        }
+        let cli = Cli::parse_from(["craft", "debug", "myproj", "--stack-size", "262144"]);
+        match cli.command {
+            Some(Commands::Debug { stack_size, .. }) => assert_eq!(stack_size, Some(262144)),
+            other => panic!("Expected Debug command, got: {other:?}"),
+        }
+        assert!(Cli::try_parse_from(["craft", "build", "p", "--limits", "mainnet"]).is_err());
+        assert!(Cli::try_parse_from(["craft", "build", "p", "--stack-size", "1"]).is_err());
+    }
+
+    #[test]
+    fn test_test_scenario_parsing() {
+        let cli = Cli::parse_from(["craft", "test", "myproj", "--scenario", "counter"]);
+        match cli.command {
+            Some(Commands::Test {
+                project, scenario, ..
+            }) => {
+                assert_eq!(project.unwrap(), "myproj");
+                assert_eq!(scenario.as_deref(), Some("counter"));
+            }
+            other => panic!("Expected Test command, got: {other:?}"),
+        }
+        assert!(Cli::try_parse_from(["craft", "test", "p", "--scenario", "s", "--all"]).is_err());
+    }
+
+    #[test]
+    fn test_test_sweep_parsing() {
+        let cli = Cli::parse_from([
+            "craft",
+            "test",
+            "myproj",
+            "--sweep-ledger-seq",
+            "100..200",
+            "--close-time",
+            "5",
+        ]);
+        match cli.command {
+            Some(Commands::Test {
+                sweep_ledger_seq,
+                close_time,
+                sweep_step,
+                ..
+            }) => {
+                assert_eq!(sweep_ledger_seq.as_deref(), Some("100..200"));
+                assert_eq!(close_time, Some(5));
+                assert_eq!(sweep_step, 1);
+            }
+            other => panic!("Expected Test command, got: {other:?}"),
+        }
+        assert!(
+            Cli::try_parse_from([
+                "craft",
+                "test",
+                "p",
+                "--sweep-ledger-seq",
+                "1..2",
+                "--ledger-seq",
+                "1"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_test_check_determinism_parsing() {
+        let cli = Cli::parse_from(["craft", "test", "myproj", "--all", "--check-determinism"]);
+        match cli.command {
+            Some(Commands::Test {
+                all,
+                check_determinism,
+                ..
+            }) => {
+                assert!(all);
+                assert!(check_determinism);
+            }
+            other => panic!("Expected Test command, got: {other:?}"),
+        }
+        assert!(
+            Cli::try_parse_from([
+                "craft",
+                "test",
+                "p",
+                "--check-determinism",
+                "--scenario",
+                "s"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_test_jobs_parsing() {
+        let cli = Cli::parse_from(["craft", "test", "myproj", "--all", "-j", "4"]);
+        match cli.command {
+            Some(Commands::Test { all, jobs, .. }) => {
+                assert!(all);
+                assert_eq!(jobs, 4);
+            }
+            other => panic!("Expected Test command, got: {other:?}"),
+        }
+        assert!(Cli::try_parse_from(["craft", "test", "p", "--jobs", "4"]).is_err());
+    }
+
+    #[test]
+    fn test_debug_parsing() {
+        let cli = Cli::parse_from([
+            "craft",
+            "debug",
+            "myproj",
+            "--break",
+            "get_ledger_obj_field",
+            "--break",
+            "trace",
+        ]);
+        match cli.command {
+            Some(Commands::Debug {
+                project,
+                case,
+                breakpoints,
+                build,
+                ..
+            }) => {
+                assert_eq!(project, "myproj");
+                assert_eq!(case, "success");
+                assert_eq!(breakpoints, vec!["get_ledger_obj_field", "trace"]);
+                assert!(build);
+            }
+            other => panic!("Expected Debug command, got: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_test_tape_parsing() {
+        let cli = Cli::parse_from([
+            "craft",
+            "test",
+            "myproj",
+            "--case",
+            "failure",
+            "--record-tape",
+            "failure.tape.json",
+        ]);
+        match cli.command {
+            Some(Commands::Test {
+                case, record_tape, ..
+            }) => {
+                assert_eq!(case.as_deref(), Some("failure"));
+                assert_eq!(
+                    record_tape.as_deref(),
+                    Some(std::path::Path::new("failure.tape.json"))
+                );
+            }
+            other => panic!("Expected Test command, got: {other:?}"),
+        }
+        assert!(
+            Cli::try_parse_from(["craft", "test", "p", "--replay-tape", "t", "--all"]).is_err()
+        );
+        assert!(
+            Cli::try_parse_from(["craft", "test", "p", "--replay-tape", "t", "--case", "c"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_fuzz_parsing() {
+        let cli = Cli::parse_from(["craft", "fuzz", "myproj", "--runs", "50", "--seed", "7"]);
+        match cli.command {
+            Some(Commands::Fuzz {
+                project,
+                case,
+                runs,
+                seed,
+                build,
+                ..
+            }) => {
+                assert_eq!(project, "myproj");
+                assert_eq!(case, "success");
+                assert_eq!(runs, 50);
+                assert_eq!(seed, Some(7));
+                assert!(build);
+            }
+            other => panic!("Expected Fuzz command, got: {other:?}"),
+        }
+    }
+
     #[test]
     fn test_build_with_positional_project() {
         let cli = Cli::parse_from(["craft", "build", "myproj", "--debug"]);
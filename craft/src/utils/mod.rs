@@ -1,3 +1,4 @@
+use crate::config::BuildMode;
 use anyhow::{Context, Result};
 use sha2::{Digest, Sha256};
 use std::env;
@@ -567,6 +568,16 @@ pub fn run_cargo_fmt() -> Result<()> {
 
 /// Find WASM output file for a project
 pub fn find_wasm_output(project_path: &Path) -> Result<PathBuf> {
+    // Try release first, then debug
+    find_wasm_output_in(project_path, &["release", "debug"])
+}
+
+/// The WASM module built in `build_mode`, ignoring any left over from a build in the other mode
+pub fn find_build_output(project_path: &Path, build_mode: &BuildMode) -> Result<PathBuf> {
+    find_wasm_output_in(project_path, &[&build_mode.to_string()])
+}
+
+fn find_wasm_output_in(project_path: &Path, modes: &[&str]) -> Result<PathBuf> {
     let cargo_toml = find_cargo_toml(project_path).context("Could not find Cargo.toml")?;
     let project_dir = cargo_toml.parent().unwrap();
     let project_name = project_dir.file_name().unwrap().to_str().unwrap();
@@ -578,33 +589,14 @@ pub fn find_wasm_output(project_path: &Path) -> Result<PathBuf> {
         .parent()
         .unwrap();
 
-    // Try release first, then debug
-    let candidates = vec![
-        project_dir
-            .join("target/wasm32v1-none/release")
-            .join(format!("{project_name}.wasm")),
-        project_dir
-            .join("target/wasm32v1-none/release")
-            .join(format!("lib{project_name}.wasm")),
-        project_dir
-            .join("target/wasm32v1-none/debug")
-            .join(format!("{project_name}.wasm")),
-        project_dir
-            .join("target/wasm32v1-none/debug")
-            .join(format!("lib{project_name}.wasm")),
-        project_main_dir
-            .join("target/wasm32v1-none/release")
-            .join(format!("{project_name}.wasm")),
-        project_main_dir
-            .join("target/wasm32v1-none/release")
-            .join(format!("lib{project_name}.wasm")),
-        project_main_dir
-            .join("target/wasm32v1-none/debug")
-            .join(format!("{project_name}.wasm")),
-        project_main_dir
-            .join("target/wasm32v1-none/debug")
-            .join(format!("lib{project_name}.wasm")),
-    ];
+    let mut candidates = Vec::new();
+    for dir in [project_dir, project_main_dir] {
+        for mode in modes {
+            let target_dir = dir.join("target/wasm32v1-none").join(mode);
+            candidates.push(target_dir.join(format!("{project_name}.wasm")));
+            candidates.push(target_dir.join(format!("lib{project_name}.wasm")));
+        }
+    }
 
     for candidate in candidates {
         if candidate.exists() {
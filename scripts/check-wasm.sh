@@ -0,0 +1,34 @@
+#!/bin/bash
+# WASM contract checking script
+# Builds every WASM project and checks each module with `craft check`: that it exports
+# finish() -> i32, imports only host functions, and is within the craft-devnet5 limits
+
+set -euo pipefail
+
+# Change to the repository root directory (where this script's grandparent directory is located)
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+REPO_ROOT="$(cd "$SCRIPT_DIR/.." && pwd)"
+cd "$REPO_ROOT"
+
+echo "📦 Ensuring wasm32v1-none target is installed..."
+rustup target add wasm32v1-none
+
+echo "🏗️  Building WASM Projects Workspace..."
+(cd projects && cargo build --workspace --target wasm32v1-none --release)
+
+echo "🏗️  Building craft..."
+cargo build -p craft
+
+echo "🔍 Checking WASM contracts..."
+find projects -mindepth 2 -name "Cargo.toml" -not -path "*/target/*" | while read -r cargo_file; do
+    dir=$(dirname "$cargo_file")
+    crate_name=$(grep -m1 '^name' "$cargo_file" | cut -d'"' -f2 | tr '-' '_')
+    wasm="projects/target/wasm32v1-none/release/$crate_name.wasm"
+    echo "🔧 Checking $dir"
+    ./target/debug/craft check "$wasm" || {
+        echo "❌ $dir failed craft check"
+        exit 1
+    }
+done
+
+echo "✅ WASM contract check passed!"
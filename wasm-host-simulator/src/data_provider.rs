@@ -1,13 +1,18 @@
-use crate::decoding::{AccountId, Decodable, decode, decode_amount_json, decode_issue_json};
-use crate::hashing::Hash256;
+use crate::debugger::Debugger;
+use crate::decoding::{AccountId, Decodable, decode};
+use crate::hashing::{HASH256_LEN, Hash256, sha512_half};
 use crate::mock_data::{DataSource, Keylet, MockData};
+use crate::run_log::RunLog;
+use crate::serialization::field_bytes;
 use std::ffi::c_void;
 
 const LOCATOR_BUFFER_SIZE: usize = 64;
 const NUM_SLOTS: usize = 256;
 pub const XRPL_CONTRACT_DATA_SIZE: usize = 4096;
+const DEFAULT_BASE_FEE: i32 = 10;
 
 #[allow(dead_code)]
+#[derive(Debug)]
 pub enum HostError {
     InternalError = -1,
     FieldNotFound = -2,
@@ -21,8 +26,8 @@ pub enum HostError {
     LedgerObjNotFound = -10,
     InvalidDecoding = -11,
     DataFieldTooLarge = -12,
-    PointerOutOfBound = -13, // WAMR VM checks, so we don't need to
-    NoMemoryExported = -14,  // We don't explicitly call WAMR memory functions.
+    PointerOutOfBound = -13,
+    NoMemoryExported = -14, // Reported as PointerOutOfBound, as WAMR does not tell them apart
     InvalidParams = -15,
     InvalidAccount = -16,
     InvalidField = -17,
@@ -52,6 +57,8 @@ impl From<i64> for HostError {
             -16 => HostError::InvalidAccount,
             -17 => HostError::InvalidField,
             -18 => HostError::IndexOutOfBounds,
+            -19 => HostError::InvalidFloatInput,
+            -20 => HostError::InvalidFloatComputation,
             _ => HostError::InternalError, // Default to InternalError for unknown error codes
         }
     }
@@ -139,7 +146,7 @@ pub fn unpack_locator(buffer: Vec<u8>) -> Result<Vec<i32>, HostError> {
 }
 
 use num_derive::FromPrimitive;
-#[derive(FromPrimitive, Debug)]
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RippledRoundingMode {
     ToNearest = 0,
     TowardsZero = 1,
@@ -147,11 +154,46 @@ pub enum RippledRoundingMode {
     Upward = 3,
 }
 
+/// The order in which `cache_ledger_obj` hands out free slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotOrder {
+    /// From slot 1 upwards, as rippled does.
+    Ascending,
+    /// From the last slot downwards.
+    Descending,
+}
+
+/// Host behaviour a contract must not depend on. rippled runs with the defaults; the
+/// determinism check varies them to catch contracts whose results would.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostConditions {
+    pub slot_order: SlotOrder,
+    /// Byte left in an output buffer past the bytes a host function wrote.
+    pub buffer_fill: u8,
+    /// Float rounding mode in effect when the function starts, as if left over by an earlier
+    /// call.
+    pub rounding_mode: RippledRoundingMode,
+}
+
+impl Default for HostConditions {
+    fn default() -> Self {
+        Self {
+            slot_order: SlotOrder::Ascending,
+            buffer_fill: 0,
+            rounding_mode: RippledRoundingMode::ToNearest,
+        }
+    }
+}
+
 pub struct DataProvider {
     data_source: MockData,
     next_slot: usize,
     slots: [Keylet; NUM_SLOTS],
     pub _rounding_mode: RippledRoundingMode,
+    pub run_log: RunLog,
+    conditions: HostConditions,
+    /// Stops the run at host function calls, under `craft debug`.
+    pub debugger: Option<Box<dyn Debugger>>,
 }
 
 impl DataProvider {
@@ -162,6 +204,24 @@ impl DataProvider {
             next_slot: 1,
             slots,
             _rounding_mode: RippledRoundingMode::ToNearest,
+            run_log: RunLog::default(),
+            conditions: HostConditions::default(),
+            debugger: None,
+        }
+    }
+
+    pub fn with_conditions(data_source: MockData, conditions: HostConditions) -> Self {
+        Self {
+            conditions,
+            ..Self::new(data_source)
+        }
+    }
+
+    /// The slot number of the `n`th slot handed out, counting from 1.
+    fn nth_slot(&self, n: usize) -> usize {
+        match self.conditions.slot_order {
+            SlotOrder::Ascending => n,
+            SlotOrder::Descending => NUM_SLOTS - n,
         }
     }
 
@@ -170,7 +230,7 @@ impl DataProvider {
             if self.next_slot >= NUM_SLOTS {
                 return HostError::NoFreeSlots as i32;
             }
-            slot = self.next_slot;
+            slot = self.nth_slot(self.next_slot);
             self.next_slot += 1;
         } else if slot >= NUM_SLOTS {
             return HostError::SlotOutRange as i32;
@@ -185,7 +245,11 @@ impl DataProvider {
     }
 
     pub fn slot_get(&self, slot: usize) -> Option<&Keylet> {
-        if slot == 0 || slot >= self.next_slot {
+        let handed_out = match self.conditions.slot_order {
+            SlotOrder::Ascending => slot < self.next_slot,
+            SlotOrder::Descending => slot > self.nth_slot(self.next_slot),
+        };
+        if slot == 0 || slot >= NUM_SLOTS || !handed_out {
             None
         } else {
             Some(&self.slots[slot])
@@ -205,11 +269,10 @@ impl DataProvider {
             Err(e) => return (e as i32, vec![]),
         };
 
-        Self::fill_buf(
-            Some(field_result),
-            buf_cap,
-            Decodable::from_sfield(last_sfield),
-        )
+        match field_bytes(last_sfield, field_result) {
+            Ok(bytes) => self.copy_to_buf(bytes, buf_cap),
+            Err(_) => (HostError::InvalidDecoding as i32, vec![0u8; buf_cap]),
+        }
     }
 
     pub fn get_array_len(&self, source: DataSource, idx_fields: Vec<i32>) -> i32 {
@@ -252,7 +315,41 @@ impl DataProvider {
 
     pub fn get_parent_ledger_hash(&self, buf_cap: usize) -> (i32, Vec<u8>) {
         let field_result = self.data_source.get_parent_ledger_hash();
-        Self::fill_buf(field_result, buf_cap, Decodable::UINT256)
+        self.fill_buf(field_result, buf_cap, Decodable::UINT256)
+    }
+
+    pub fn get_base_fee(&self) -> i32 {
+        match self.data_source.get_base_fee() {
+            Some(value) => match value
+                .as_i64()
+                .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+            {
+                // A fee too large for the guest's i32 is an error rather than a wrapped value.
+                Some(num) => i32::try_from(num).unwrap_or(HostError::InternalError as i32),
+                None => HostError::InvalidDecoding as i32,
+            },
+            // The fixture header doesn't carry fees, use the network's reference fee.
+            None => DEFAULT_BASE_FEE,
+        }
+    }
+
+    /// `amendment_id` is the 256-bit amendment ID. The fixture header lists enabled amendments
+    /// either by ID (hex) or by name, a name being hashed the same way rippled derives IDs.
+    pub fn amendment_enabled(&self, amendment_id: &Hash256) -> i32 {
+        let Some(amendments) = self.data_source.get_amendments() else {
+            return 0;
+        };
+        let Some(amendments) = amendments.as_array() else {
+            return HostError::InvalidDecoding as i32;
+        };
+        let enabled = amendments.iter().filter_map(|a| a.as_str()).any(|a| {
+            let id = match hex::decode(a) {
+                Ok(bytes) if bytes.len() == HASH256_LEN => bytes,
+                _ => sha512_half(a.as_bytes()),
+            };
+            &id == amendment_id
+        });
+        enabled as i32
     }
 
     pub fn get_nft_uri(
@@ -262,123 +359,73 @@ impl DataProvider {
         buf_cap: usize,
     ) -> (i32, Vec<u8>) {
         let field_result = self.data_source.get_nft_uri(nft_id, account_id);
-        Self::fill_buf(field_result, buf_cap, Decodable::AS_IS)
+        self.fill_buf(field_result, buf_cap, Decodable::AS_IS)
     }
 
     pub fn set_current_ledger_obj_data(&mut self, data: Vec<u8>) {
         self.data_source.set_current_ledger_obj_data(data);
     }
 
+    pub fn get_current_ledger_obj_data(&self) -> Option<Vec<u8>> {
+        self.data_source.get_current_ledger_obj_data()
+    }
+
+    pub fn get_current_ledger_obj(&self) -> serde_json::Value {
+        self.data_source.get_current_ledger_obj()
+    }
+
+    /// Decodes a fixture string that is not an SField, such as a header hash or an NFT URI.
     fn fill_buf(
+        &self,
         field_result: Option<&serde_json::Value>,
         buf_cap: usize,
         decodable: Decodable,
     ) -> (i32, Vec<u8>) {
-        let mut buf = vec![0u8; buf_cap];
         match field_result {
-            Some(value) => {
-                if decodable == Decodable::AMOUNT {
-                    match decode_amount_json(value.clone()) {
-                        None => (HostError::InvalidDecoding as i32, buf),
-                        Some(bytes) => {
-                            if bytes.len() > buf_cap {
-                                return (HostError::BufferTooSmall as i32, buf);
-                            }
-                            buf[..bytes.len()].copy_from_slice(&bytes);
-                            (bytes.len() as i32, buf)
-                        }
-                    }
-                } else if decodable == Decodable::ISSUE {
-                    match decode_issue_json(value.clone()) {
-                        None => (HostError::InvalidDecoding as i32, buf),
-                        Some(bytes) => {
-                            if bytes.len() > buf_cap {
-                                return (HostError::BufferTooSmall as i32, buf);
-                            }
-                            buf[..bytes.len()].copy_from_slice(&bytes);
-                            (bytes.len() as i32, buf)
-                        }
-                    }
-                } else {
-                    match value {
-                        serde_json::Value::Number(n) => {
-                            if n.is_i64() {
-                                let num = n.as_i64().unwrap();
-                                if buf_cap == 4 {
-                                    // Safe cast to i32
-                                    if num > u32::MAX as i64 || num < u32::MIN as i64 {
-                                        return (HostError::BufferTooSmall as i32, buf);
-                                    }
-                                    let bytes = (num as u32).to_le_bytes();
-                                    buf[..4].copy_from_slice(&bytes);
-                                    (4, buf)
-                                } else {
-                                    let bytes = num.to_le_bytes();
-                                    if bytes.len() > buf_cap {
-                                        return (HostError::BufferTooSmall as i32, buf);
-                                    }
-                                    buf[..bytes.len()].copy_from_slice(&bytes);
-                                    (bytes.len() as i32, buf)
-                                }
-                            } else if n.is_u64() {
-                                let num = n.as_u64().unwrap();
-                                if buf_cap == 4 {
-                                    // Safe cast to u32
-                                    if num > u32::MAX as u64 {
-                                        return (HostError::BufferTooSmall as i32, buf);
-                                    }
-                                    let bytes = (num as u32).to_le_bytes();
-                                    buf[..4].copy_from_slice(&bytes);
-                                    (4, buf)
-                                } else {
-                                    let bytes = num.to_le_bytes();
-                                    if bytes.len() > buf_cap {
-                                        return (HostError::BufferTooSmall as i32, buf);
-                                    }
-                                    buf[..bytes.len()].copy_from_slice(&bytes);
-                                    (bytes.len() as i32, buf)
-                                }
-                            } else if n.is_f64() {
-                                let s = n.as_f64().unwrap().to_string();
-                                let bytes = s.as_bytes();
-                                if bytes.len() > buf_cap {
-                                    return (HostError::BufferTooSmall as i32, buf);
-                                }
-                                buf[..bytes.len()].copy_from_slice(bytes);
-                                (bytes.len() as i32, buf)
-                            } else {
-                                (HostError::InternalError as i32, buf)
-                            }
-                        }
-                        serde_json::Value::String(s) => match decode(s, decodable) {
-                            None => (HostError::InvalidDecoding as i32, buf),
-                            Some(bytes) => {
-                                if bytes.len() > buf_cap {
-                                    return (HostError::BufferTooSmall as i32, buf);
-                                }
-                                buf[..bytes.len()].copy_from_slice(&bytes);
-                                (bytes.len() as i32, buf)
-                            }
-                        },
-                        serde_json::Value::Bool(b) => {
-                            if buf_cap == 0 {
-                                return (HostError::BufferTooSmall as i32, buf);
-                            }
-                            buf[0] = if *b { 1 } else { 0 };
-                            (1, buf)
-                        }
-                        // be explicit about the cases we don't support
-                        serde_json::Value::Null => (HostError::NotLeafField as i32, buf),
-                        serde_json::Value::Array(_) => (HostError::NotLeafField as i32, buf),
-                        serde_json::Value::Object(_) => (HostError::NotLeafField as i32, buf),
-                    }
-                }
-            }
-            None => (HostError::FieldNotFound as i32, buf),
+            Some(serde_json::Value::String(s)) => match decode(s, decodable) {
+                Some(bytes) => self.copy_to_buf(bytes, buf_cap),
+                None => (HostError::InvalidDecoding as i32, vec![0u8; buf_cap]),
+            },
+            Some(_) => (HostError::InvalidDecoding as i32, vec![0u8; buf_cap]),
+            None => (HostError::FieldNotFound as i32, vec![0u8; buf_cap]),
+        }
+    }
+
+    fn copy_to_buf(&self, bytes: Vec<u8>, buf_cap: usize) -> (i32, Vec<u8>) {
+        let mut buf = vec![self.conditions.buffer_fill; buf_cap];
+        if bytes.len() > buf_cap {
+            return (HostError::BufferTooSmall as i32, buf);
         }
+        buf[..bytes.len()].copy_from_slice(&bytes);
+        (bytes.len() as i32, buf)
     }
+
     #[allow(unused)]
     pub fn as_ptr(&mut self) -> *mut c_void {
         self as *mut _ as *mut c_void
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_header(header: &str) -> DataProvider {
+        DataProvider::new(MockData::new("{}", "{}", header, "[]", "[]").unwrap())
+    }
+
+    #[test]
+    fn test_get_base_fee() {
+        assert_eq!(with_header("{}").get_base_fee(), DEFAULT_BASE_FEE);
+        assert_eq!(with_header(r#"{ "base_fee": 12 }"#).get_base_fee(), 12);
+        assert_eq!(with_header(r#"{ "base_fee": "15" }"#).get_base_fee(), 15);
+        assert_eq!(
+            with_header(r#"{ "base_fee": 4294967306 }"#).get_base_fee(),
+            HostError::InternalError as i32
+        );
+        assert_eq!(
+            with_header(r#"{ "base_fee": "ten" }"#).get_base_fee(),
+            HostError::InvalidDecoding as i32
+        );
+    }
+}
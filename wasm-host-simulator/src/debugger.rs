@@ -0,0 +1,768 @@
+use crate::host_functions_wamr::{GuestPtr, get_dp};
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use wamr_rust_sdk::sys::{
+    wasm_exec_env_t, wasm_runtime_addr_app_to_native, wasm_runtime_dump_call_stack_to_buf,
+    wasm_runtime_get_call_stack_buf_size, wasm_runtime_get_module_inst, wasm_runtime_set_exception,
+    wasm_runtime_validate_app_addr,
+};
+use wasmparser::{Parser, Payload};
+
+/// How many bytes `x` shows when no length is given.
+const DEFAULT_DUMP_LEN: u64 = 64;
+
+/// A value passed to a host function: a number, or an address in the guest's linear memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arg {
+    Int(i64),
+    Ptr(u64),
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arg::Int(value) => write!(f, "{}", value),
+            Arg::Ptr(addr) => write!(f, "{:#x}", addr),
+        }
+    }
+}
+
+/// Converts a host function parameter into an [`Arg`].
+pub(crate) trait ToArg {
+    fn to_arg(self) -> Arg;
+}
+
+macro_rules! int_to_arg {
+    ($($ty:ty),*) => {$(
+        impl ToArg for $ty {
+            fn to_arg(self) -> Arg {
+                Arg::Int(self as i64)
+            }
+        }
+    )*};
+}
+
+int_to_arg!(i32, u32, i64, usize);
+
+impl ToArg for GuestPtr {
+    fn to_arg(self) -> Arg {
+        Arg::Ptr(self.0 as u64)
+    }
+}
+
+/// A call the guest made to a host function.
+#[derive(Debug, Clone)]
+pub struct HostCall {
+    pub name: &'static str,
+    /// Each parameter, by the name the host function gives it.
+    pub args: Vec<(&'static str, Arg)>,
+}
+
+/// A buffer passed to a host function: a pointer and the length or capacity after it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Buffer {
+    pub name: &'static str,
+    pub addr: u64,
+    pub len: u64,
+    /// Whether the host writes to the buffer rather than reading it.
+    pub output: bool,
+}
+
+impl HostCall {
+    pub fn buffers(&self) -> Vec<Buffer> {
+        self.args
+            .windows(2)
+            .filter_map(|pair| match pair {
+                [(name, Arg::Ptr(addr)), (_, Arg::Int(len))] => Some(Buffer {
+                    name,
+                    addr: *addr,
+                    len: (*len).max(0) as u64,
+                    output: name.starts_with("out"),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for HostCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let args: Vec<_> = self
+            .args
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        write!(f, "{}({})", self.name, args.join(", "))
+    }
+}
+
+/// A frame of the guest's call stack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub function: String,
+    /// Offset of the frame's current instruction from the start of the module.
+    pub offset: u64,
+}
+
+/// What a debugger can see of the guest while it is stopped at a host call.
+pub trait Guest {
+    /// `len` bytes of linear memory from `addr`, or `None` if they are not all in bounds.
+    fn read(&self, addr: u64, len: u64) -> Option<Vec<u8>>;
+    /// Writes `bytes` to linear memory at `addr`, if they fit.
+    fn write(&self, addr: u64, bytes: &[u8]) -> bool;
+    /// Whether the `len` bytes from `addr` are all in linear memory.
+    fn contains(&self, addr: u64, len: u64) -> bool {
+        self.read(addr, len).is_some()
+    }
+    /// The guest's call stack, innermost frame first. Empty if WAMR was built without call stack
+    /// dumps.
+    fn call_stack(&self) -> Vec<Frame>;
+}
+
+/// Whether a run carries on after a debugger stops it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    Continue,
+    /// Return this to the guest instead of running the host function.
+    Respond(i32),
+    /// Stop the run, which then fails with a trap.
+    Abort,
+}
+
+/// Inspects a run at each host function call.
+pub trait Debugger {
+    /// Called before the host function runs.
+    fn before_call(&mut self, call: &HostCall, guest: &dyn Guest) -> Resume;
+    /// Called once the host function has returned `result`. Not called when `before_call`
+    /// responded in its place.
+    fn after_call(&mut self, call: &HostCall, result: i32, guest: &dyn Guest);
+}
+
+/// Lets the caller keep a handle on a debugger the run owns, to read what it gathered once the
+/// run is over.
+impl<D: Debugger> Debugger for Rc<RefCell<D>> {
+    fn before_call(&mut self, call: &HostCall, guest: &dyn Guest) -> Resume {
+        self.borrow_mut().before_call(call, guest)
+    }
+
+    fn after_call(&mut self, call: &HostCall, result: i32, guest: &dyn Guest) {
+        self.borrow_mut().after_call(call, result, guest)
+    }
+}
+
+/// The linear memory and call stack of the guest calling a host function.
+pub(crate) struct WamrGuest(pub(crate) wasm_exec_env_t);
+
+impl Guest for WamrGuest {
+    fn read(&self, addr: u64, len: u64) -> Option<Vec<u8>> {
+        if !self.contains(addr, len) {
+            return None;
+        }
+        unsafe {
+            let inst = wasm_runtime_get_module_inst(self.0);
+            let native = wasm_runtime_addr_app_to_native(inst, addr) as *const u8;
+            Some(std::slice::from_raw_parts(native, len as usize).to_vec())
+        }
+    }
+
+    fn write(&self, addr: u64, bytes: &[u8]) -> bool {
+        if !self.contains(addr, bytes.len() as u64) {
+            return false;
+        }
+        unsafe {
+            let inst = wasm_runtime_get_module_inst(self.0);
+            let native = wasm_runtime_addr_app_to_native(inst, addr) as *mut u8;
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), native, bytes.len());
+        }
+        true
+    }
+
+    fn contains(&self, addr: u64, len: u64) -> bool {
+        unsafe { wasm_runtime_validate_app_addr(wasm_runtime_get_module_inst(self.0), addr, len) }
+    }
+
+    fn call_stack(&self) -> Vec<Frame> {
+        unsafe {
+            let size = wasm_runtime_get_call_stack_buf_size(self.0);
+            if size == 0 {
+                return Vec::new();
+            }
+            let mut buf = vec![0u8; size as usize];
+            let written = wasm_runtime_dump_call_stack_to_buf(self.0, buf.as_mut_ptr() as _, size);
+            buf.truncate(written as usize);
+            parse_call_stack(&String::from_utf8_lossy(&buf))
+        }
+    }
+}
+
+/// Parses WAMR's call stack dump, lines like `#00: 0x0a8c - $f12`.
+fn parse_call_stack(dump: &str) -> Vec<Frame> {
+    dump.lines()
+        .filter_map(|line| {
+            let (_, rest) = line.trim().split_once(": ")?;
+            let (offset, function) = rest.split_once(" - ")?;
+            let offset = u64::from_str_radix(offset.trim_start_matches("0x"), 16).ok()?;
+            Some(Frame {
+                function: function.to_string(),
+                offset,
+            })
+        })
+        .collect()
+}
+
+/// Runs the host function `call`, letting the run's debugger, if any, stop before and after or
+/// respond in its place.
+pub(crate) fn intercept(
+    env: wasm_exec_env_t,
+    name: &'static str,
+    args: impl FnOnce() -> Vec<(&'static str, Arg)>,
+    call: impl FnOnce() -> i32,
+) -> i32 {
+    // Taken out for the duration of the call, as the host function borrows the data provider too
+    let Some(mut debugger) = get_dp(env).debugger.take() else {
+        return call();
+    };
+    let guest = WamrGuest(env);
+    let host_call = HostCall { name, args: args() };
+    let result = match debugger.before_call(&host_call, &guest) {
+        Resume::Continue => {
+            let result = call();
+            debugger.after_call(&host_call, result, &guest);
+            result
+        }
+        Resume::Respond(result) => result,
+        Resume::Abort => {
+            unsafe {
+                wasm_runtime_set_exception(
+                    wasm_runtime_get_module_inst(env),
+                    c"run aborted at a host function call".as_ptr(),
+                );
+            }
+            0
+        }
+    };
+    get_dp(env).debugger = Some(debugger);
+    result
+}
+
+/// Maps code in a WASM module back to source lines, from the DWARF line tables debug builds
+/// carry in custom sections.
+#[derive(Debug, Default)]
+pub struct LineMap {
+    /// Start of the code section's contents, which DWARF addresses are relative to.
+    code_start: u64,
+    files: Vec<PathBuf>,
+    /// Each address a run of lines starts at, with its file and line; `None` past the end of a
+    /// sequence. Sorted by address.
+    rows: Vec<(u64, Option<(usize, u32)>)>,
+}
+
+impl LineMap {
+    /// Reads the line tables of a module. `None` if it has none, as release builds don't.
+    pub fn from_wasm(bytes: &[u8]) -> Option<Self> {
+        let mut sections = Vec::new();
+        let mut code_start = None;
+        for payload in Parser::new(0).parse_all(bytes) {
+            match payload.ok()? {
+                Payload::CustomSection(reader) if reader.name().starts_with(".debug_") => {
+                    sections.push((reader.name(), reader.data()));
+                }
+                Payload::CodeSectionStart { range, .. } => code_start = Some(range.start as u64),
+                _ => {}
+            }
+        }
+        if sections.is_empty() {
+            return None;
+        }
+
+        let section = |id: gimli::SectionId| {
+            let data = sections
+                .iter()
+                .find(|(name, _)| *name == id.name())
+                .map_or(&[][..], |(_, data)| *data);
+            Ok::<_, gimli::Error>(gimli::EndianSlice::new(data, gimli::LittleEndian))
+        };
+        let dwarf = gimli::Dwarf::load(section).ok()?;
+
+        let mut map = LineMap {
+            code_start: code_start?,
+            ..Default::default()
+        };
+        let mut units = dwarf.units();
+        while let Ok(Some(header)) = units.next() {
+            let Ok(unit) = dwarf.unit(header) else {
+                continue;
+            };
+            let Some(program) = unit.line_program.clone() else {
+                continue;
+            };
+            let mut rows = program.rows();
+            while let Ok(Some((header, row))) = rows.next_row() {
+                if row.end_sequence() {
+                    map.rows.push((row.address(), None));
+                    continue;
+                }
+                let (Some(file), Some(line)) = (row.file(header), row.line()) else {
+                    continue;
+                };
+                let mut path = PathBuf::new();
+                if let Some(dir) = &unit.comp_dir {
+                    path.push(dir.to_string_lossy().as_ref());
+                }
+                if let Some(dir) = file.directory(header)
+                    && let Ok(dir) = dwarf.attr_string(&unit, dir)
+                {
+                    path.push(dir.to_string_lossy().as_ref());
+                }
+                if let Ok(name) = dwarf.attr_string(&unit, file.path_name()) {
+                    path.push(name.to_string_lossy().as_ref());
+                }
+                let file = match map.files.iter().position(|f| *f == path) {
+                    Some(index) => index,
+                    None => {
+                        map.files.push(path);
+                        map.files.len() - 1
+                    }
+                };
+                map.rows
+                    .push((row.address(), Some((file, line.get() as u32))));
+            }
+        }
+        map.rows.sort_by_key(|(address, _)| *address);
+        Some(map)
+    }
+
+    /// The source file and line of the instruction at `offset` from the start of the module.
+    pub fn location(&self, offset: u64) -> Option<(&Path, u32)> {
+        let address = offset.checked_sub(self.code_start)?;
+        let next = self.rows.partition_point(|(start, _)| *start <= address);
+        let (file, line) = self.rows.get(next.checked_sub(1)?)?.1?;
+        Some((&self.files[file], line))
+    }
+}
+
+/// A command typed at the debugger's prompt.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Run to the next breakpoint.
+    Continue,
+    /// Run this host call and stop at the next one.
+    Next,
+    /// Break on every call to a host function, or list breakpoints if none is given.
+    Break(Option<String>),
+    Delete(String),
+    /// Dump linear memory.
+    Examine {
+        addr: u64,
+        len: u64,
+    },
+    /// Show the current call's arguments and the buffers it passes.
+    Args,
+    Backtrace,
+    Quit,
+    Help,
+}
+
+const HELP: &str = "\
+  c, continue        run to the next breakpoint
+  n, next            run this host call and stop at the next one (also: empty line)
+  b, break [FN]      break on every call to host function FN, or list breakpoints
+  d, delete FN       remove the breakpoint on FN
+  x ADDR [LEN]       dump LEN bytes of linear memory from ADDR (decimal or 0x hex)
+  a, args            show this call's arguments and buffers
+  bt, backtrace      show the guest's call stack, with source lines in debug builds
+  q, quit            abort the run
+  h, help            show this help";
+
+fn parse_number(s: &str) -> Result<u64, String> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|_| format!("Invalid number: {}", s))
+}
+
+impl std::str::FromStr for Command {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("next");
+        let arg = words.next();
+        Ok(match (command, arg) {
+            ("c" | "continue", None) => Command::Continue,
+            ("n" | "next", None) => Command::Next,
+            ("b" | "break", function) => Command::Break(function.map(str::to_string)),
+            ("d" | "delete", Some(function)) => Command::Delete(function.to_string()),
+            ("x", Some(addr)) => Command::Examine {
+                addr: parse_number(addr)?,
+                len: words.next().map_or(Ok(DEFAULT_DUMP_LEN), parse_number)?,
+            },
+            ("a" | "args", None) => Command::Args,
+            ("bt" | "backtrace" | "where", None) => Command::Backtrace,
+            ("q" | "quit", None) => Command::Quit,
+            ("h" | "help", None) => Command::Help,
+            _ => return Err(format!("Unknown command: {} (try 'help')", line.trim())),
+        })
+    }
+}
+
+/// Formats bytes as a hex dump of 16 bytes a line, each line starting with its address.
+fn hex_dump(addr: u64, bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex: Vec<_> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            let text: String = chunk
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+                .collect();
+            format!(
+                "  {:08x}  {:<47}  {}",
+                addr + 16 * i as u64,
+                hex.join(" "),
+                text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// An interactive [`Debugger`], reading commands from `input` and writing to `output`. It stops
+/// at the first host call unless given breakpoints.
+pub struct Session<R, W> {
+    input: R,
+    output: W,
+    breakpoints: BTreeSet<String>,
+    /// Stop at the next host call, whatever it is.
+    stepping: bool,
+    lines: Option<LineMap>,
+    calls: u32,
+    /// Whether the run stopped before the current call, so its result is shown after.
+    stopped: bool,
+}
+
+impl<R: BufRead, W: Write> Session<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Session {
+            input,
+            output,
+            breakpoints: BTreeSet::new(),
+            stepping: true,
+            lines: None,
+            calls: 0,
+            stopped: false,
+        }
+    }
+
+    /// Stop at every call to `function`, rather than at the first host call.
+    pub fn break_on(mut self, function: &str) -> Self {
+        self.breakpoints.insert(function.to_string());
+        self.stepping = false;
+        self
+    }
+
+    /// Source lines to show where the guest is, from [`LineMap::from_wasm`].
+    pub fn lines(mut self, lines: Option<LineMap>) -> Self {
+        self.lines = lines;
+        self
+    }
+
+    /// `function at file:line`, or just the function where there is no line table.
+    fn describe(&self, frame: &Frame) -> String {
+        // A frame's offset is its return address, just past the call
+        let location = self
+            .lines
+            .as_ref()
+            .and_then(|lines| lines.location(frame.offset.saturating_sub(1)));
+        match location {
+            Some((file, line)) => format!("{} at {}:{}", frame.function, file.display(), line),
+            None => frame.function.clone(),
+        }
+    }
+
+    fn show_location(&mut self, guest: &dyn Guest) -> io::Result<()> {
+        let Some(frame) = guest.call_stack().into_iter().find(|f| f.offset > 0) else {
+            return Ok(());
+        };
+        writeln!(self.output, "   in {}", self.describe(&frame))?;
+        let location = self
+            .lines
+            .as_ref()
+            .and_then(|lines| lines.location(frame.offset.saturating_sub(1)));
+        if let Some((file, line)) = location
+            && let Ok(source) = std::fs::read_to_string(file)
+            && let Some(text) = source.lines().nth(line as usize - 1)
+        {
+            writeln!(self.output, "   {:>5} | {}", line, text)?;
+        }
+        Ok(())
+    }
+
+    fn show_buffers(&mut self, call: &HostCall, guest: &dyn Guest) -> io::Result<()> {
+        for buffer in call.buffers().into_iter().filter(|b| !b.output) {
+            let len = buffer.len.min(DEFAULT_DUMP_LEN * 4);
+            match guest.read(buffer.addr, len) {
+                Some(bytes) => {
+                    writeln!(self.output, "   {} ({} bytes):", buffer.name, buffer.len)?;
+                    writeln!(self.output, "{}", hex_dump(buffer.addr, &bytes))?;
+                }
+                None => writeln!(self.output, "   {}: out of bounds", buffer.name)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn prompt(&mut self, call: &HostCall, guest: &dyn Guest) -> io::Result<Resume> {
+        writeln!(self.output, "#{} {}", self.calls, call)?;
+        self.show_location(guest)?;
+        self.show_buffers(call, guest)?;
+        loop {
+            write!(self.output, "(debug) ")?;
+            self.output.flush()?;
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                // Input closed: let the run finish without stopping again
+                writeln!(self.output)?;
+                self.breakpoints.clear();
+                self.stepping = false;
+                return Ok(Resume::Continue);
+            }
+            let command = match line.parse::<Command>() {
+                Ok(command) => command,
+                Err(e) => {
+                    writeln!(self.output, "{}", e)?;
+                    continue;
+                }
+            };
+            match command {
+                Command::Continue => {
+                    self.stepping = false;
+                    return Ok(Resume::Continue);
+                }
+                Command::Next => {
+                    self.stepping = true;
+                    return Ok(Resume::Continue);
+                }
+                Command::Break(Some(function)) => {
+                    writeln!(self.output, "Breaking on {}", function)?;
+                    self.breakpoints.insert(function);
+                }
+                Command::Break(None) if self.breakpoints.is_empty() => {
+                    writeln!(self.output, "No breakpoints")?
+                }
+                Command::Break(None) => {
+                    let list: Vec<_> = self.breakpoints.iter().cloned().collect();
+                    writeln!(self.output, "Breaking on {}", list.join(", "))?;
+                }
+                Command::Delete(function) => {
+                    if !self.breakpoints.remove(&function) {
+                        writeln!(self.output, "No breakpoint on {}", function)?;
+                    }
+                }
+                Command::Examine { addr, len } => match guest.read(addr, len) {
+                    Some(bytes) => writeln!(self.output, "{}", hex_dump(addr, &bytes))?,
+                    None => writeln!(
+                        self.output,
+                        "{:#x}..{:#x} is out of bounds",
+                        addr,
+                        addr + len
+                    )?,
+                },
+                Command::Args => {
+                    writeln!(self.output, "#{} {}", self.calls, call)?;
+                    self.show_buffers(call, guest)?;
+                }
+                Command::Backtrace => {
+                    let frames = guest.call_stack();
+                    if frames.is_empty() {
+                        writeln!(self.output, "No call stack: WAMR was built without dumps")?;
+                    }
+                    for (i, frame) in frames.iter().enumerate() {
+                        writeln!(self.output, "  #{} {}", i, self.describe(frame))?;
+                    }
+                }
+                Command::Quit => return Ok(Resume::Abort),
+                Command::Help => writeln!(self.output, "{}", HELP)?,
+            }
+        }
+    }
+
+    fn show_result(&mut self, call: &HostCall, result: i32, guest: &dyn Guest) -> io::Result<()> {
+        writeln!(self.output, "   = {}", result)?;
+        if result <= 0 {
+            return Ok(());
+        }
+        for buffer in call.buffers().into_iter().filter(|b| b.output) {
+            let len = buffer.len.min(result as u64);
+            if let Some(bytes) = guest.read(buffer.addr, len) {
+                writeln!(self.output, "   {} ({} bytes written):", buffer.name, len)?;
+                writeln!(self.output, "{}", hex_dump(buffer.addr, &bytes))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead, W: Write> Debugger for Session<R, W> {
+    fn before_call(&mut self, call: &HostCall, guest: &dyn Guest) -> Resume {
+        self.calls += 1;
+        self.stopped = self.stepping || self.breakpoints.contains(call.name);
+        if !self.stopped {
+            return Resume::Continue;
+        }
+        // Output that cannot be written is no reason to stop the run
+        self.prompt(call, guest).unwrap_or(Resume::Continue)
+    }
+
+    fn after_call(&mut self, call: &HostCall, result: i32, guest: &dyn Guest) {
+        if self.stopped {
+            let _ = self.show_result(call, result, guest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Memory;
+
+    fn get_field(out_addr: u64) -> HostCall {
+        HostCall {
+            name: "get_tx_field",
+            args: vec![
+                ("field", Arg::Int(0x80001)),
+                ("out_buf_ptr", Arg::Ptr(out_addr)),
+                ("out_buf_cap", Arg::Int(20)),
+            ],
+        }
+    }
+
+    fn trace(addr: u64, len: i64) -> HostCall {
+        HostCall {
+            name: "trace",
+            args: vec![
+                ("msg_read_ptr", Arg::Ptr(addr)),
+                ("msg_read_len", Arg::Int(len)),
+                ("data_read_ptr", Arg::Ptr(0)),
+                ("data_read_len", Arg::Int(0)),
+                ("data_as_hex", Arg::Int(0)),
+            ],
+        }
+    }
+
+    /// Runs `calls` through a session fed `input`, returning what each call resumed with and the
+    /// session's output.
+    fn run(session: Session<&[u8], Vec<u8>>, calls: &[HostCall]) -> (Vec<Resume>, String) {
+        let mut session = session;
+        let guest =
+            Memory::with_bytes(b"hello, world....".repeat(4)).with_call_stack(vec![Frame {
+                function: "finish".to_string(),
+                offset: 0x25,
+            }]);
+        let resumes = calls
+            .iter()
+            .map(|call| {
+                let resume = session.before_call(call, &guest);
+                session.after_call(call, 4, &guest);
+                resume
+            })
+            .collect();
+        (resumes, String::from_utf8(session.output).unwrap())
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!("".parse(), Ok(Command::Next));
+        assert_eq!("c".parse(), Ok(Command::Continue));
+        assert_eq!(
+            "break get_ledger_obj_field".parse(),
+            Ok(Command::Break(Some("get_ledger_obj_field".to_string())))
+        );
+        assert_eq!(
+            "x 0x10 8".parse(),
+            Ok(Command::Examine { addr: 16, len: 8 })
+        );
+        assert_eq!(
+            "x 32".parse(),
+            Ok(Command::Examine {
+                addr: 32,
+                len: DEFAULT_DUMP_LEN
+            })
+        );
+        assert!("x nowhere".parse::<Command>().is_err());
+        assert!("jump".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn test_buffers_pair_pointers_with_lengths() {
+        assert_eq!(
+            get_field(8).buffers(),
+            vec![Buffer {
+                name: "out_buf_ptr",
+                addr: 8,
+                len: 20,
+                output: true
+            }]
+        );
+        assert_eq!(trace(0, 5).buffers().len(), 2);
+    }
+
+    #[test]
+    fn test_parse_call_stack() {
+        let frames = parse_call_stack("\n#00: 0x0a8c - $f12\n#01: 0x1234 - finish\n");
+        assert_eq!(
+            frames,
+            vec![
+                Frame {
+                    function: "$f12".to_string(),
+                    offset: 0xa8c
+                },
+                Frame {
+                    function: "finish".to_string(),
+                    offset: 0x1234
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_session_stops_at_breakpoints_only() {
+        let session = Session::new(&b"x 0 5\nc\n"[..], Vec::new()).break_on("get_tx_field");
+        let (resumes, output) = run(session, &[trace(0, 5), get_field(16), trace(0, 5)]);
+        assert_eq!(resumes, vec![Resume::Continue; 3]);
+        assert!(output.starts_with("#2 get_tx_field(field=524289, out_buf_ptr=0x10"));
+        assert!(output.contains("00000000  68 65 6c 6c 6f"));
+        // The output buffer is shown with as many bytes as the call returned
+        assert!(output.contains("out_buf_ptr (4 bytes written)"));
+        assert!(!output.contains("#3"));
+    }
+
+    #[test]
+    fn test_session_steps_and_quits() {
+        let session = Session::new(&b"n\nq\n"[..], Vec::new());
+        let (resumes, output) = run(session, &[trace(0, 5), get_field(16)]);
+        assert_eq!(resumes, vec![Resume::Continue, Resume::Abort]);
+        assert!(output.contains("#1 trace("));
+        assert!(output.contains("msg_read_ptr (5 bytes)"));
+        assert!(output.contains("#2 get_tx_field("));
+    }
+
+    #[test]
+    fn test_line_map_location() {
+        let map = LineMap {
+            code_start: 0x20,
+            files: vec![PathBuf::from("src/lib.rs")],
+            rows: vec![(0, Some((0, 10))), (4, Some((0, 12))), (8, None)],
+        };
+        assert_eq!(map.location(0x25), Some((Path::new("src/lib.rs"), 12)));
+        assert_eq!(map.location(0x21), Some((Path::new("src/lib.rs"), 10)));
+        assert_eq!(map.location(0x30), None);
+        assert_eq!(map.location(0x10), None);
+    }
+}
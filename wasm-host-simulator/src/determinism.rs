@@ -0,0 +1,225 @@
+use crate::data_provider::{HostConditions, RippledRoundingMode, SlotOrder};
+use crate::mock_data::MockData;
+use crate::run_log::TraceLine;
+use crate::vm_wamr::{RunResult, VmError};
+use serde_json::{Value, json};
+
+/// The host conditions each run of a determinism check uses, after a first run under rippled's.
+/// The order of the ledger's objects is not among them: host functions only look objects up by
+/// keylet, and [`MockData::ledger_keylets`] sorts them, so a contract cannot observe it.
+pub fn variations() -> Vec<(&'static str, HostConditions)> {
+    let default = HostConditions::default();
+    vec![
+        (
+            "slots handed out from the last",
+            HostConditions {
+                slot_order: SlotOrder::Descending,
+                ..default
+            },
+        ),
+        (
+            "output buffers pre-filled with 0xA5",
+            HostConditions {
+                buffer_fill: 0xA5,
+                ..default
+            },
+        ),
+        (
+            "rounding mode left upward",
+            HostConditions {
+                rounding_mode: RippledRoundingMode::Upward,
+                ..default
+            },
+        ),
+        (
+            "rounding mode left towards zero",
+            HostConditions {
+                rounding_mode: RippledRoundingMode::TowardsZero,
+                ..default
+            },
+        ),
+        (
+            "all of the above",
+            HostConditions {
+                slot_order: SlotOrder::Descending,
+                buffer_fill: 0xFF,
+                rounding_mode: RippledRoundingMode::Downward,
+            },
+        ),
+    ]
+}
+
+/// Something a run under varied conditions did differently from the first run.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Difference {
+    /// The varied conditions, as named by [`variations`].
+    pub conditions: &'static str,
+    /// What differed: `result`, `gas`, `trace` or `data`.
+    pub aspect: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// The outcome of running one fixture under each set of host conditions.
+#[derive(Debug)]
+pub struct DeterminismReport {
+    /// How the first run, under rippled's conditions, ended.
+    pub result: String,
+    pub runs: usize,
+    pub differences: Vec<Difference>,
+}
+
+pub(crate) fn result_label(run: &RunResult) -> String {
+    match &run.outcome {
+        Ok(rv) => format!("returned {}", rv),
+        Err(e) => e.kind().to_string(),
+    }
+}
+
+fn gas_label(run: &RunResult) -> String {
+    run.gas_used
+        .map_or_else(|| "unmetered".to_string(), |gas| gas.to_string())
+}
+
+fn data_label(run: &RunResult) -> String {
+    run.data
+        .as_ref()
+        .map_or_else(|| "none".to_string(), hex::encode_upper)
+}
+
+/// The first difference between two runs in each of the aspects validators must agree on.
+fn compare(conditions: &'static str, expected: &RunResult, actual: &RunResult) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    let mut differ = |aspect, expected: String, actual: String| {
+        if expected != actual {
+            differences.push(Difference {
+                conditions,
+                aspect,
+                expected,
+                actual,
+            });
+        }
+    };
+    differ("result", result_label(expected), result_label(actual));
+    differ("gas", gas_label(expected), gas_label(actual));
+
+    let (a, b) = (&expected.log.traces, &actual.log.traces);
+    let line = |traces: &[TraceLine], i: usize| {
+        traces
+            .get(i)
+            .map_or_else(|| "(no line)".to_string(), |t| t.message.clone())
+    };
+    if let Some(i) = (0..a.len().max(b.len())).find(|&i| line(a, i) != line(b, i)) {
+        differ(
+            "trace",
+            format!("line {}: {}", i + 1, line(a, i)),
+            format!("line {}: {}", i + 1, line(b, i)),
+        );
+    }
+
+    differ("data", data_label(expected), data_label(actual));
+    differences
+}
+
+/// Runs the fixture under rippled's host conditions, then under each of [`variations`], and
+/// reports every way a varied run differs from the first. Fails only if the function cannot be
+/// run at all.
+pub fn check(
+    state: &MockData,
+    mut run: impl FnMut(MockData, HostConditions) -> Result<RunResult, VmError>,
+) -> Result<DeterminismReport, VmError> {
+    let first = run(state.clone(), HostConditions::default())?;
+    let variations = variations();
+    let mut differences = Vec::new();
+    for (name, conditions) in &variations {
+        let varied = run(state.clone(), *conditions)?;
+        differences.extend(compare(name, &first, &varied));
+    }
+    Ok(DeterminismReport {
+        result: result_label(&first),
+        runs: variations.len() + 1,
+        differences,
+    })
+}
+
+impl DeterminismReport {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "result": self.result,
+            "runs": self.runs,
+            "deterministic": self.differences.is_empty(),
+            "differences": self.differences.iter().map(|d| {
+                json!({
+                    "conditions": d.conditions,
+                    "aspect": d.aspect,
+                    "expected": d.expected,
+                    "actual": d.actual,
+                })
+            }).collect::<Vec<_>>(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{mock_data, returned};
+
+    fn state() -> MockData {
+        mock_data(r#"{ "Data": "00" }"#, "{}")
+    }
+
+    fn traced(rv: i32, trace: &str) -> RunResult {
+        let mut run = returned(rv);
+        run.log.traces.push(TraceLine {
+            origin: "trace",
+            message: trace.to_string(),
+        });
+        run.data = Some(vec![0]);
+        run
+    }
+
+    #[test]
+    fn test_check_passes_contract_that_ignores_conditions() {
+        let report = check(&state(), |_, _| Ok(traced(1, "done"))).unwrap();
+        assert_eq!(report.result, "returned 1");
+        assert_eq!(report.runs, variations().len() + 1);
+        assert!(report.differences.is_empty());
+    }
+
+    #[test]
+    fn test_check_reports_each_difference() {
+        // Hardcodes slot 1 and reads past what the host wrote
+        let report = check(&state(), |_, conditions| {
+            let mut run = traced(1, "slot 1");
+            if conditions.slot_order == SlotOrder::Descending {
+                run.outcome = Ok(0);
+                run.log.traces[0].message = "slot 255".to_string();
+            }
+            if conditions.buffer_fill != 0 {
+                run.data = Some(vec![conditions.buffer_fill]);
+            }
+            Ok(run)
+        })
+        .unwrap();
+
+        let found: Vec<_> = report
+            .differences
+            .iter()
+            .map(|d| (d.conditions, d.aspect))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("slots handed out from the last", "result"),
+                ("slots handed out from the last", "trace"),
+                ("output buffers pre-filled with 0xA5", "data"),
+                ("all of the above", "result"),
+                ("all of the above", "trace"),
+                ("all of the above", "data"),
+            ]
+        );
+        assert_eq!(report.differences[1].expected, "line 1: slot 1");
+        assert_eq!(report.differences[2].actual, "A5");
+    }
+}
@@ -0,0 +1,266 @@
+use crate::vm_wamr::RunResult;
+use serde_json::Value;
+
+/// Name of the optional file, next to the other fixture files, holding the assertions for a test case.
+pub const EXPECTED_FILE: &str = "expected.json";
+
+/// Assertions a fixture makes about a run, loaded from its `expected.json`.
+///
+/// Every field is optional; a run passes when all of the present ones hold.
+#[derive(Debug, Default, PartialEq)]
+pub struct Expectations {
+    /// Exact value the entry point must return.
+    pub return_value: Option<i32>,
+    /// Upper bound on the gas the run may consume.
+    pub max_gas: Option<u32>,
+    /// Strings that must each appear in at least one trace line.
+    pub traces: Vec<String>,
+    /// The ledger object's `Data` after the run, as hex.
+    pub data: Option<Vec<u8>>,
+    /// How the run must stop instead of returning: `trap`, `out_of_gas` or `error`.
+    pub error: Option<String>,
+    /// Host error codes that must each be returned by at least one host function call.
+    pub host_errors: Vec<i32>,
+}
+
+impl Expectations {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let value: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        Self::from_value(&value)
+    }
+
+    /// Reads assertions already parsed as JSON, such as a scenario step's `expected`.
+    pub fn from_value(value: &Value) -> Result<Self, String> {
+        let obj = value
+            .as_object()
+            .ok_or_else(|| "expected a JSON object".to_string())?;
+
+        let mut expectations = Expectations::default();
+        for (key, value) in obj {
+            match key.as_str() {
+                "return_value" => {
+                    let rv = value
+                        .as_i64()
+                        .and_then(|v| i32::try_from(v).ok())
+                        .ok_or_else(|| format!("'return_value' must be an i32, got {value}"))?;
+                    expectations.return_value = Some(rv);
+                }
+                "max_gas" => {
+                    let gas = value
+                        .as_u64()
+                        .and_then(|v| u32::try_from(v).ok())
+                        .ok_or_else(|| format!("'max_gas' must be a u32, got {value}"))?;
+                    expectations.max_gas = Some(gas);
+                }
+                "traces" => {
+                    let lines = value
+                        .as_array()
+                        .ok_or_else(|| "'traces' must be an array of strings".to_string())?;
+                    for line in lines {
+                        let line = line
+                            .as_str()
+                            .ok_or_else(|| "'traces' must be an array of strings".to_string())?;
+                        expectations.traces.push(line.to_string());
+                    }
+                }
+                "data" => {
+                    let data = value
+                        .as_str()
+                        .and_then(|s| hex::decode(s).ok())
+                        .ok_or_else(|| format!("'data' must be a hex string, got {value}"))?;
+                    expectations.data = Some(data);
+                }
+                "error" => {
+                    let kind = value
+                        .as_str()
+                        .filter(|kind| ["trap", "out_of_gas", "error"].contains(kind))
+                        .ok_or_else(|| {
+                            format!("'error' must be trap, out_of_gas or error, got {value}")
+                        })?;
+                    expectations.error = Some(kind.to_string());
+                }
+                "host_errors" => {
+                    let codes = value
+                        .as_array()
+                        .ok_or_else(|| "'host_errors' must be an array of i32s".to_string())?;
+                    for code in codes {
+                        let code = code
+                            .as_i64()
+                            .and_then(|v| i32::try_from(v).ok())
+                            .ok_or_else(|| "'host_errors' must be an array of i32s".to_string())?;
+                        expectations.host_errors.push(code);
+                    }
+                }
+                other => return Err(format!("unknown field '{other}'")),
+            }
+        }
+        if expectations.error.is_some() && expectations.return_value.is_some() {
+            return Err("'error' and 'return_value' cannot both be given".to_string());
+        }
+        Ok(expectations)
+    }
+
+    /// Checks the run against every assertion, returning a description of each one that failed.
+    pub fn check(&self, run: &RunResult) -> Vec<String> {
+        let mut failures = Vec::new();
+
+        match (&run.outcome, &self.error) {
+            (Ok(rv), Some(expected)) => {
+                failures.push(format!("returned {rv}, expected to stop with {expected}"));
+            }
+            (Err(e), Some(expected)) if e.kind() != expected => {
+                failures.push(format!(
+                    "stopped with {}: {e}, expected {expected}",
+                    e.kind()
+                ));
+            }
+            (Err(_), Some(_)) => {}
+            (Ok(rv), None) => {
+                if let Some(expected) = self.return_value
+                    && *rv != expected
+                {
+                    failures.push(format!("returned {rv}, expected {expected}"));
+                }
+            }
+            (Err(e), None) => failures.push(format!("did not return: {e}")),
+        }
+
+        if let Some(max_gas) = self.max_gas {
+            match run.gas_used {
+                Some(gas) if gas > max_gas => {
+                    failures.push(format!("used {gas} gas, expected at most {max_gas}"));
+                }
+                Some(_) => {}
+                None => failures.push("gas usage was not measured".to_string()),
+            }
+        }
+
+        for expected in &self.traces {
+            if !run
+                .log
+                .traces
+                .iter()
+                .any(|t| t.message.contains(expected.as_str()))
+            {
+                failures.push(format!("no trace line contains \"{expected}\""));
+            }
+        }
+
+        for expected in &self.host_errors {
+            if !run.log.host_errors.iter().any(|e| e.code == *expected) {
+                failures.push(format!("no host function returned {expected}"));
+            }
+        }
+
+        if let Some(expected) = &self.data {
+            match &run.data {
+                Some(data) if data == expected => {}
+                Some(data) => failures.push(format!(
+                    "Data is {}, expected {}",
+                    hex::encode_upper(data),
+                    hex::encode_upper(expected)
+                )),
+                None => failures.push(format!(
+                    "Data is not set, expected {}",
+                    hex::encode_upper(expected)
+                )),
+            }
+        }
+
+        failures
+    }
+}
+
+/// The verdict for fixtures without an `expected.json`: `success` must return 1 and `failure`
+/// must return anything else.
+pub fn check_by_name(test_case: &str, run: &RunResult) -> Vec<String> {
+    let rv = match &run.outcome {
+        Ok(rv) => *rv,
+        Err(e) => return vec![format!("did not return: {e}")],
+    };
+    match test_case {
+        "success" if rv != 1 => vec![format!("returned {rv}, expected 1")],
+        "failure" if rv == 1 => vec!["returned 1, expected anything else".to_string()],
+        "success" | "failure" => vec![],
+        _ => vec![format!(
+            "test case '{test_case}' has no {EXPECTED_FILE}; only 'success' and 'failure' can omit it"
+        )],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::returned;
+    use crate::vm_wamr::VmError;
+
+    fn run(outcome: i32, gas_used: u32, data: Option<Vec<u8>>) -> RunResult {
+        let mut run = returned(outcome);
+        run.log.trace("trace_num", "count: 3".to_string());
+        run.gas_used = Some(gas_used);
+        run.data = data;
+        run
+    }
+
+    #[test]
+    fn test_expectations_check() {
+        let exp = Expectations::from_json(
+            r#"{ "return_value": 0, "max_gas": 500, "traces": ["count"], "data": "0a0B" }"#,
+        )
+        .unwrap();
+        assert!(exp.check(&run(0, 500, Some(vec![0x0A, 0x0B]))).is_empty());
+
+        let failures = exp.check(&run(1, 501, None));
+        assert_eq!(
+            failures,
+            vec![
+                "returned 1, expected 0",
+                "used 501 gas, expected at most 500",
+                "Data is not set, expected 0A0B",
+            ]
+        );
+
+        let exp = Expectations::from_json(r#"{ "traces": ["missing"] }"#).unwrap();
+        assert_eq!(
+            exp.check(&run(1, 0, None)),
+            vec!["no trace line contains \"missing\""]
+        );
+        assert!(Expectations::from_json(r#"{ "return": 1 }"#).is_err());
+    }
+
+    #[test]
+    fn test_expectations_check_errors() {
+        let exp = Expectations::from_json(r#"{ "error": "out_of_gas" }"#).unwrap();
+        let mut stopped = run(0, 100, None);
+        stopped.outcome = Err(VmError::OutOfGas { gas_cap: 100 });
+        assert!(exp.check(&stopped).is_empty());
+        assert_eq!(
+            exp.check(&run(1, 100, None)),
+            vec!["returned 1, expected to stop with out_of_gas"]
+        );
+        stopped.outcome = Err(VmError::InvalidModule("no memory".to_string()));
+        assert_eq!(
+            exp.check(&stopped),
+            vec!["stopped with error: Invalid WASM module: no memory, expected out_of_gas"]
+        );
+        assert!(Expectations::from_json(r#"{ "error": "crash" }"#).is_err());
+        assert!(Expectations::from_json(r#"{ "error": "trap", "return_value": 0 }"#).is_err());
+
+        let exp = Expectations::from_json(r#"{ "host_errors": [-13] }"#).unwrap();
+        assert_eq!(
+            exp.check(&run(1, 0, None)),
+            vec!["no host function returned -13"]
+        );
+        let mut failed = run(1, 0, None);
+        failed.log.record_host_call("update_data", 0, -13);
+        assert!(exp.check(&failed).is_empty());
+    }
+
+    #[test]
+    fn test_check_by_name() {
+        assert!(check_by_name("success", &run(1, 0, None)).is_empty());
+        assert!(check_by_name("failure", &run(0, 0, None)).is_empty());
+        assert_eq!(check_by_name("failure", &run(1, 0, None)).len(), 1);
+        assert_eq!(check_by_name("edge_case", &run(1, 0, None)).len(), 1);
+    }
+}
@@ -0,0 +1,442 @@
+use crate::decoding::{Decodable, SField_To_Name, decode};
+use crate::expectations::{EXPECTED_FILE, Expectations};
+use crate::keylets::ledger_entry_key;
+use crate::scenario::{SCENARIO_FILE, Scenario};
+use lazy_static::lazy_static;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The files every fixture directory must contain.
+pub const FIXTURE_FILES: [&str; 5] = [
+    "tx.json",
+    "ledger_object.json",
+    "ledger_header.json",
+    "ledger.json",
+    "nfts.json",
+];
+
+/// Files holding a single STObject, whose keys are SField names.
+const SFIELD_FILES: [&str; 2] = ["tx.json", "ledger_object.json"];
+
+lazy_static! {
+    static ref SFIELD_NAMES: HashSet<&'static str> =
+        SField_To_Name.values().map(String::as_str).collect();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in a fixture file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: PathBuf,
+    /// 1-based line the problem was found on, when it can be located.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match self.line {
+            Some(line) => write!(
+                f,
+                "{}:{}: {}: {}",
+                self.file.display(),
+                line,
+                severity,
+                self.message
+            ),
+            None => write!(f, "{}: {}: {}", self.file.display(), severity, self.message),
+        }
+    }
+}
+
+/// Collects diagnostics for one file, locating them in its source text.
+struct FileChecker<'a> {
+    path: PathBuf,
+    text: &'a str,
+    diagnostics: &'a mut Vec<Diagnostic>,
+}
+
+impl FileChecker<'_> {
+    /// Line of the first occurrence of `"needle"` in the file, if any.
+    fn line_of(&self, needle: &str) -> Option<usize> {
+        let quoted = format!("\"{}\"", needle);
+        let offset = self.text.find(&quoted)?;
+        Some(self.text[..offset].matches('\n').count() + 1)
+    }
+
+    fn report(&mut self, severity: Severity, needle: Option<&str>, message: String) {
+        let line = needle.and_then(|n| self.line_of(n));
+        self.diagnostics.push(Diagnostic {
+            severity,
+            file: self.path.clone(),
+            line,
+            message,
+        });
+    }
+
+    /// Every object key starting with an uppercase letter should be an SField name. Lowercase keys
+    /// (`index`, `hash`, amount `value`/`currency`/`issuer`, ...) are not fields.
+    fn check_field_names(&mut self, value: &Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, child) in map {
+                    if key.starts_with(|c: char| c.is_ascii_uppercase())
+                        && !SFIELD_NAMES.contains(key.as_str())
+                    {
+                        self.report(
+                            Severity::Warning,
+                            Some(key),
+                            format!("unknown field '{}' cannot be read by host functions", key),
+                        );
+                    }
+                    self.check_field_names(child);
+                }
+            }
+            Value::Array(items) => items.iter().for_each(|item| self.check_field_names(item)),
+            _ => {}
+        }
+    }
+
+    /// Line of the `n`th (0-based) occurrence of `"needle"` in the file, if any.
+    fn line_of_nth(&self, needle: &str, n: usize) -> Option<usize> {
+        let quoted = format!("\"{}\"", needle);
+        let (offset, _) = self.text.match_indices(&quoted).nth(n)?;
+        Some(self.text[..offset].matches('\n').count() + 1)
+    }
+
+    fn check_ledger(&mut self, ledger: &Value) {
+        const SHAPE: &str = "expected an array of objects or {\"<index>\": <object>} maps";
+        let Some(entries) = ledger.as_array() else {
+            self.report(Severity::Error, None, SHAPE.to_string());
+            return;
+        };
+        // `LedgerEntryType`s seen so far, to locate keyless objects by theirs
+        let mut entry_types = 0;
+        for entry in entries {
+            let Some(map) = entry.as_object() else {
+                self.report(Severity::Error, None, SHAPE.to_string());
+                continue;
+            };
+            if map.contains_key("LedgerEntryType") {
+                self.check_field_names(entry);
+                if let Err(e) = ledger_entry_key(None, entry) {
+                    self.diagnostics.push(Diagnostic {
+                        severity: Severity::Error,
+                        file: self.path.clone(),
+                        line: self.line_of_nth("LedgerEntryType", entry_types),
+                        message: e,
+                    });
+                }
+                entry_types += 1;
+                continue;
+            }
+            for (key, object) in map {
+                entry_types += object.get("LedgerEntryType").is_some() as usize;
+                if decode(key, Decodable::UINT256).is_none() {
+                    self.report(
+                        Severity::Error,
+                        Some(key),
+                        format!("key '{}' is not a 256-bit hex ledger object ID", key),
+                    );
+                    continue;
+                }
+                if let Err(e) = ledger_entry_key(Some(key), object) {
+                    self.report(Severity::Error, Some(key), format!("key '{}' {}", key, e));
+                }
+                self.check_field_names(object);
+                if let Some(index) = object.get("index").and_then(Value::as_str)
+                    && !index.eq_ignore_ascii_case(key)
+                {
+                    self.report(
+                        Severity::Warning,
+                        Some(key),
+                        format!("object stored under '{}' has index '{}'", key, index),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_nfts(&mut self, nfts: &Value) {
+        let Some(entries) = nfts.as_array() else {
+            self.report(
+                Severity::Error,
+                None,
+                "expected an array of NFTs".to_string(),
+            );
+            return;
+        };
+        for (i, nft) in entries.iter().enumerate() {
+            let id = nft.get("nft_id").and_then(Value::as_str);
+            for field in ["nft_id", "owner", "uri"] {
+                if nft.get(field).and_then(Value::as_str).is_none() {
+                    self.report(
+                        Severity::Error,
+                        id,
+                        format!("NFT #{} has no '{}' string", i, field),
+                    );
+                }
+            }
+            if let Some(id) = id
+                && decode(id, Decodable::UINT256).is_none()
+            {
+                self.report(
+                    Severity::Error,
+                    Some(id),
+                    format!("nft_id '{}' is not a 256-bit hex NFTokenID", id),
+                );
+            }
+            if let Some(owner) = nft.get("owner").and_then(Value::as_str)
+                && decode(owner, Decodable::ACCOUNT).is_none()
+            {
+                self.report(
+                    Severity::Error,
+                    Some(owner),
+                    format!("owner '{}' is not a valid account address", owner),
+                );
+            }
+        }
+    }
+}
+
+/// Validates one fixture directory, returning every problem found.
+pub fn check_fixture(dir: &Path) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    if !FIXTURE_FILES.iter().any(|name| dir.join(name).exists()) {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            file: dir.to_path_buf(),
+            line: None,
+            message: format!("not a fixture, expected {}", FIXTURE_FILES.join(", ")),
+        });
+        return diagnostics;
+    }
+
+    for name in FIXTURE_FILES {
+        let path = dir.join(name);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) => {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    file: path,
+                    line: None,
+                    message: format!("cannot read file: {}", e),
+                });
+                continue;
+            }
+        };
+        let value: Value = match serde_json::from_str(&text) {
+            Ok(value) => value,
+            Err(e) => {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    file: path,
+                    line: Some(e.line()),
+                    message: format!("invalid JSON: {}", e),
+                });
+                continue;
+            }
+        };
+
+        let first = diagnostics.len();
+        let mut checker = FileChecker {
+            path,
+            text: &text,
+            diagnostics: &mut diagnostics,
+        };
+        if SFIELD_FILES.contains(&name) {
+            checker.check_field_names(&value);
+        }
+        match name {
+            "ledger.json" => checker.check_ledger(&value),
+            "nfts.json" => checker.check_nfts(&value),
+            _ => {
+                if !value.is_object() {
+                    checker.report(Severity::Error, None, "expected a JSON object".to_string());
+                }
+            }
+        }
+        // Object keys are visited in sorted order; report in file order instead
+        diagnostics[first..].sort_by_key(|d| d.line);
+    }
+
+    let expected_path = dir.join(EXPECTED_FILE);
+    if let Ok(text) = fs::read_to_string(&expected_path)
+        && let Err(e) = Expectations::from_json(&text)
+    {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            file: expected_path,
+            line: None,
+            message: e,
+        });
+    }
+
+    let scenario_path = dir.join(SCENARIO_FILE);
+    if let Ok(text) = fs::read_to_string(&scenario_path)
+        && let Err(e) = Scenario::from_json(&text)
+    {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            file: scenario_path.clone(),
+            line: None,
+            message: e,
+        });
+    }
+    // A scenario checks each step against its own expectations, so this file would be ignored
+    if scenario_path.exists() && dir.join(EXPECTED_FILE).exists() {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            file: dir.join(EXPECTED_FILE),
+            line: None,
+            message: format!(
+                "a scenario's expectations go in each step's 'expected' in {}",
+                SCENARIO_FILE
+            ),
+        });
+    }
+
+    diagnostics
+}
+
+/// Validates every fixture directory under `fixtures_dir`, in name order.
+pub fn check_fixtures(fixtures_dir: &Path) -> std::io::Result<Vec<(String, Vec<Diagnostic>)>> {
+    let mut cases = Vec::new();
+    for entry in fs::read_dir(fixtures_dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            cases.push((name, check_fixture(&path)));
+        }
+    }
+    cases.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(cases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "2B6AC232AA4C4BE41BF49D2459FA4A0347E1B543A4C92FCEE0821C0201E2E9A8";
+
+    fn write_fixture(name: &str, files: &[(&str, String)]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("fixture-check-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for file in FIXTURE_FILES {
+            let contents = files
+                .iter()
+                .find(|(f, _)| *f == file)
+                .map(|(_, c)| c.clone())
+                .unwrap_or_else(|| {
+                    if file.ends_with("ledger.json") || file == "nfts.json" {
+                        "[]"
+                    } else {
+                        "{}"
+                    }
+                    .to_string()
+                });
+            fs::write(dir.join(file), contents).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_valid_fixture_has_no_diagnostics() {
+        let dir = write_fixture(
+            "valid",
+            &[
+                (
+                    "tx.json",
+                    r#"{ "TransactionType": "EscrowFinish", "hash": "AB" }"#.to_string(),
+                ),
+                (
+                    "ledger.json",
+                    format!(
+                        r#"[{{ "{ID}": {{ "LedgerEntryType": "AccountRoot", "index": "{ID}" }} }}]"#
+                    ),
+                ),
+            ],
+        );
+        assert_eq!(check_fixture(&dir), vec![]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_reports_file_and_line() {
+        let dir = write_fixture(
+            "invalid",
+            &[
+                (
+                    "tx.json",
+                    "{\n  \"Account\": \"r\",\n  \"Acount\": 1\n}".to_string(),
+                ),
+                ("ledger_object.json", "{\n  \"Flags\": 0,\n}".to_string()),
+                (
+                    "ledger.json",
+                    format!(
+                        "[{{\n  \"XYZ\": {{}},\n  \"{ID}\": {{ \"index\": \"00\" }}\n}},\n{{ \"LedgerEntryType\": \"DirectoryNode\" }}]"
+                    ),
+                ),
+                (
+                    "nfts.json",
+                    format!("[{{ \"nft_id\": \"{ID}\", \"owner\": \"r\" }}]"),
+                ),
+            ],
+        );
+        let diagnostics: Vec<String> = check_fixture(&dir)
+            .iter()
+            .map(|d| d.to_string().replace(&format!("{}/", dir.display()), ""))
+            .collect();
+        assert_eq!(
+            diagnostics,
+            vec![
+                "tx.json:3: warning: unknown field 'Acount' cannot be read by host functions"
+                    .to_string(),
+                "ledger_object.json:3: error: invalid JSON: trailing comma at line 3 column 1"
+                    .to_string(),
+                "ledger.json:2: error: key 'XYZ' is not a 256-bit hex ledger object ID".to_string(),
+                format!("ledger.json:3: warning: object stored under '{ID}' has index '00'"),
+                "ledger.json:5: error: the ID of a DirectoryNode cannot be derived from its fields; give its key or index".to_string(),
+                "nfts.json:1: error: NFT #0 has no 'uri' string".to_string(),
+                "nfts.json:1: error: owner 'r' is not a valid account address".to_string(),
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_expected_file_of_a_scenario() {
+        let dir = write_fixture("scenario", &[]);
+        fs::write(dir.join(SCENARIO_FILE), r#"{ "steps": [{}] }"#).unwrap();
+        assert_eq!(check_fixture(&dir), vec![]);
+
+        fs::write(dir.join(EXPECTED_FILE), r#"{ "return_value": 1 }"#).unwrap();
+        let diagnostics: Vec<String> = check_fixture(&dir)
+            .iter()
+            .map(|d| d.to_string().replace(&format!("{}/", dir.display()), ""))
+            .collect();
+        assert_eq!(
+            diagnostics,
+            vec![
+                "expected.json: error: a scenario's expectations go in each step's 'expected' in scenario.json"
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
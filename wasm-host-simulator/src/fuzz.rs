@@ -0,0 +1,678 @@
+use crate::data_provider::HostError;
+use crate::keylets::ledger_entry_key;
+use crate::mock_data::{Keylet, MockData};
+use crate::serialization::sfield_code;
+use crate::vm_wamr::{RunResult, VmError};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Most mutations applied to the base fixture in one case.
+const MAX_MUTATIONS: u64 = 4;
+
+/// Accounts to substitute into account fields, besides those already in the transaction.
+const ACCOUNTS: [&str; 3] = [
+    "rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh",
+    // The zero and one account IDs, which never have a key pair
+    "rrrrrrrrrrrrrrrrrrrrrhoLvTp",
+    "rrrrrrrrrrrrrrrrrrrrBZbvji",
+];
+
+/// A small, seedable PRNG (SplitMix64), so a fuzz run can be repeated from its seed.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`.
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n.max(1)
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len() as u64) as usize]
+    }
+
+    /// A number in `0..n`, or half of the time one of the edge values 0, 1 and `n - 1`.
+    fn edge_or_below(&mut self, n: u64) -> u64 {
+        match self.below(6) {
+            0 => 0,
+            1 => 1,
+            2 => n - 1,
+            _ => self.below(n),
+        }
+    }
+
+    fn hex(&mut self, len: usize) -> String {
+        let bytes: Vec<u8> = (0..len).map(|_| self.next_u64() as u8).collect();
+        hex::encode_upper(bytes)
+    }
+}
+
+/// The part of the fixture a mutation changes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    Tx,
+    LedgerObject,
+    /// An object of `ledger.json`, by ID.
+    Ledger(Keylet),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Tx => write!(f, "tx"),
+            Target::LedgerObject => write!(f, "ledger_object"),
+            Target::Ledger(keylet) => write!(f, "ledger[{}]", hex::encode_upper(keylet)),
+        }
+    }
+}
+
+/// One change to the base fixture.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mutation {
+    Remove {
+        target: Target,
+        field: String,
+    },
+    Set {
+        target: Target,
+        field: String,
+        value: Value,
+    },
+    DeleteObject(Keylet),
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mutation::Remove { target, field } => write!(f, "remove {}.{}", target, field),
+            Mutation::Set {
+                target,
+                field,
+                value,
+            } => write!(f, "set {}.{} = {}", target, field, value),
+            Mutation::DeleteObject(keylet) => {
+                write!(f, "delete ledger object {}", hex::encode_upper(keylet))
+            }
+        }
+    }
+}
+
+/// The current contents of `target`, or `None` for a ledger object that is not there.
+fn target_object(state: &MockData, target: &Target) -> Option<Value> {
+    match target {
+        Target::Tx => Some(state.get_tx().clone()),
+        Target::LedgerObject => Some(state.get_current_ledger_obj()),
+        Target::Ledger(keylet) => state.get_ledger_obj(keylet).cloned(),
+    }
+}
+
+impl Mutation {
+    pub fn apply(&self, state: &mut MockData) {
+        let target = match self {
+            Mutation::DeleteObject(keylet) => {
+                state.delete_ledger_obj(keylet);
+                return;
+            }
+            Mutation::Remove { target, .. } | Mutation::Set { target, .. } => target,
+        };
+        // An earlier mutation may have deleted the ledger object
+        let Some(mut object) = target_object(state, target) else {
+            return;
+        };
+        self.edit(&mut object);
+        match target {
+            Target::Tx => state.set_tx(object),
+            Target::LedgerObject => state.set_current_ledger_obj(object),
+            Target::Ledger(keylet) => state.set_ledger_obj(keylet.clone(), object),
+        }
+    }
+
+    /// Sets or removes the mutation's field of `object`.
+    fn edit(&self, object: &mut Value) {
+        let Some(fields) = object.as_object_mut() else {
+            return;
+        };
+        match self {
+            Mutation::Set { field, value, .. } => {
+                fields.insert(field.clone(), value.clone());
+            }
+            Mutation::Remove { field, .. } => {
+                fields.remove(field);
+            }
+            Mutation::DeleteObject(_) => {}
+        }
+    }
+}
+
+/// A new value for a field, chosen by the field's SField type, or `None` if the type has no
+/// generator and the field should be removed instead.
+fn generate_value(
+    rng: &mut Rng,
+    field: &str,
+    current: &Value,
+    accounts: &[String],
+) -> Option<Value> {
+    let type_code = sfield_code(field)? >> 16;
+    let value = match type_code {
+        // UInt16, UInt32, UInt8
+        1 => json!(rng.edge_or_below(1 << 16)),
+        2 => json!(rng.edge_or_below(1 << 32)),
+        16 => json!(rng.edge_or_below(1 << 8)),
+        // UInt64, in rippled's hex form
+        3 => json!(format!("{:X}", rng.edge_or_below(u64::MAX))),
+        // Hash128, Hash256, Hash160, Hash192
+        4 => json!(rng.hex(16)),
+        5 => json!(rng.hex(32)),
+        17 => json!(rng.hex(20)),
+        21 => json!(rng.hex(24)),
+        6 => generate_amount(rng, accounts),
+        // Blob
+        7 => {
+            let len = rng.edge_or_below(1024) as usize;
+            json!(rng.hex(len))
+        }
+        8 => json!(rng.pick(accounts)),
+        // Array: empty, truncated or repeated
+        15 => {
+            let elements = current.as_array().cloned().unwrap_or_default();
+            let len = match rng.below(3) {
+                0 => 0,
+                1 => rng.below(elements.len() as u64 + 1) as usize,
+                _ => elements.len() * 2 + 1,
+            };
+            let value: Vec<Value> = elements.iter().cycle().take(len).cloned().collect();
+            json!(value)
+        }
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// An amount rippled can still deserialize, at or near the edges of what it accepts.
+fn generate_amount(rng: &mut Rng, accounts: &[String]) -> Value {
+    // Up to the total XRP supply; drops cannot be negative
+    let drops = ["0", "1", "100000000000000000"];
+    // Including the largest and the smallest positive token amounts
+    let values = [
+        "0",
+        "1",
+        "-1.5",
+        "1e80",
+        "9999999999999999e80",
+        "1e-81",
+        "0.000001",
+    ];
+    match rng.below(3) {
+        0 => {
+            if rng.below(2) == 0 {
+                json!(rng.pick(&drops))
+            } else {
+                json!(rng.below(100_000_000_000).to_string())
+            }
+        }
+        // "XRP" is not a valid token currency code
+        1 => json!({
+            "currency": rng.pick(&["USD", "0158415500000000C1F76FF6ECB0BAC600000000"]),
+            "issuer": rng.pick(accounts),
+            "value": rng.pick(&values),
+        }),
+        // MPT amounts are unsigned, up to 2^63 - 1
+        _ => json!({
+            "mpt_issuance_id": rng.hex(24),
+            "value": rng.pick(&["0", "1", "9223372036854775807"]),
+        }),
+    }
+}
+
+/// Picks a random change to `state`.
+pub fn generate_mutation(rng: &mut Rng, state: &MockData) -> Option<Mutation> {
+    let keylets = state.ledger_keylets();
+    if !keylets.is_empty() && rng.below(8) == 0 {
+        return Some(Mutation::DeleteObject(rng.pick(&keylets).clone()));
+    }
+
+    let target = match rng.below(4) {
+        0 => Target::LedgerObject,
+        1 if !keylets.is_empty() => Target::Ledger(rng.pick(&keylets).clone()),
+        _ => Target::Tx,
+    };
+    let object = target_object(state, &target)?;
+    let fields = object.as_object()?;
+    let mut names: Vec<&String> = fields
+        .keys()
+        .filter(|name| !["TransactionType", "LedgerEntryType", "index"].contains(&name.as_str()))
+        .collect();
+    if names.is_empty() {
+        return None;
+    }
+    names.sort();
+    let field = rng.pick(&names).to_string();
+
+    let mut accounts: Vec<String> = ACCOUNTS.iter().map(|a| a.to_string()).collect();
+    for name in ["Account", "Destination", "Owner"] {
+        if let Some(account) = state.get_tx().get(name).and_then(Value::as_str) {
+            accounts.push(account.to_string());
+        }
+    }
+
+    // Leave optional fields out a quarter of the time
+    let mutation = match generate_value(rng, &field, &fields[&field], &accounts) {
+        Some(value) if rng.below(4) != 0 => Mutation::Set {
+            target: target.clone(),
+            field,
+            value,
+        },
+        _ => Mutation::Remove {
+            target: target.clone(),
+            field,
+        },
+    };
+    // A ledger object must still match the ID it is stored under
+    if let Target::Ledger(keylet) = &target {
+        let mut edited = object.clone();
+        mutation.edit(&mut edited);
+        ledger_entry_key(Some(&hex::encode_upper(keylet)), &edited).ok()?;
+    }
+    Some(mutation)
+}
+
+/// The kinds of misbehaviour the fuzzer looks for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FindingKind {
+    Trap,
+    OutOfGas,
+    /// The function could not complete for another reason, e.g. it returned no value.
+    Error,
+    /// The contract passed a host function a pointer outside its memory.
+    PointerOutOfBound,
+    /// Two runs of the same input did not behave the same.
+    Nondeterminism,
+}
+
+impl FindingKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            FindingKind::Trap => "trap",
+            FindingKind::OutOfGas => "out-of-gas",
+            FindingKind::Error => "error",
+            FindingKind::PointerOutOfBound => "pointer-out-of-bound",
+            FindingKind::Nondeterminism => "nondeterminism",
+        }
+    }
+}
+
+/// What went wrong in a run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+    pub kind: FindingKind,
+    pub detail: String,
+    /// `expected.json` assertions the failing run meets, so a test case written with them
+    /// reproduces the finding.
+    pub expected: Value,
+}
+
+/// A failing input, reduced to the mutations needed to reproduce it.
+pub struct FoundCase {
+    pub finding: Finding,
+    /// The case that first showed the failure.
+    pub case: u32,
+    /// How many cases failed this way.
+    pub count: u32,
+    pub mutations: Vec<Mutation>,
+}
+
+/// Everything observable about a run, to compare two runs of the same input.
+fn fingerprint(run: &RunResult) -> (String, Option<u32>, Vec<String>, Option<Vec<u8>>) {
+    let outcome = match &run.outcome {
+        Ok(rv) => format!("returned {}", rv),
+        Err(e) => e.to_string(),
+    };
+    let traces = run.log.traces.iter().map(|t| t.message.clone()).collect();
+    (outcome, run.gas_used, traces, run.data.clone())
+}
+
+/// Runs the case twice and reports the first problem, if any.
+fn check_case(
+    base: &MockData,
+    mutations: &[Mutation],
+    run: &mut impl FnMut(MockData) -> Result<RunResult, VmError>,
+) -> Result<Option<Finding>, VmError> {
+    let mut state = base.clone();
+    for mutation in mutations {
+        mutation.apply(&mut state);
+    }
+    let first = run(state.clone())?;
+    if let Err(e) = &first.outcome {
+        let kind = match e.kind() {
+            "trap" => FindingKind::Trap,
+            "out_of_gas" => FindingKind::OutOfGas,
+            _ => FindingKind::Error,
+        };
+        return Ok(Some(Finding {
+            kind,
+            detail: e.to_string(),
+            expected: json!({ "error": e.kind() }),
+        }));
+    }
+    if let Some(error) = first
+        .log
+        .host_errors
+        .iter()
+        .find(|e| e.code == HostError::PointerOutOfBound as i32)
+    {
+        return Ok(Some(Finding {
+            kind: FindingKind::PointerOutOfBound,
+            detail: format!(
+                "{} was given a pointer outside the module's memory",
+                error.function
+            ),
+            expected: json!({ "host_errors": [error.code] }),
+        }));
+    }
+    let second = run(state)?;
+    let (a, b) = (fingerprint(&first), fingerprint(&second));
+    if a != b {
+        let detail = if a.0 != b.0 {
+            format!("{} then {}", a.0, b.0)
+        } else if a.1 != b.1 {
+            format!("used {:?} then {:?} gas", a.1, b.1)
+        } else if a.2 != b.2 {
+            "traced different lines".to_string()
+        } else {
+            "left different Data".to_string()
+        };
+        // Pin what the first run did, so a run that behaves otherwise fails
+        let mut expected = json!({ "return_value": first.outcome.as_ref().ok() });
+        if let Some(gas) = first.gas_used {
+            expected["max_gas"] = json!(gas);
+        }
+        if let Some(data) = &first.data {
+            expected["data"] = json!(hex::encode_upper(data));
+        }
+        return Ok(Some(Finding {
+            kind: FindingKind::Nondeterminism,
+            detail,
+            expected,
+        }));
+    }
+    Ok(None)
+}
+
+/// A seed for a fuzz run that was not given one.
+pub fn seed_from_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+impl FoundCase {
+    /// Name of the test case written for the failure by a run with `seed`.
+    pub fn fixture_name(&self, seed: u64) -> String {
+        format!("fuzz-{}-{}", self.finding.kind.name(), seed)
+    }
+}
+
+/// Drops mutations one at a time for as long as the case still fails the same way, returning
+/// the remaining mutations and how they fail.
+fn shrink(
+    base: &MockData,
+    mut mutations: Vec<Mutation>,
+    mut finding: Finding,
+    run: &mut impl FnMut(MockData) -> Result<RunResult, VmError>,
+) -> Result<(Vec<Mutation>, Finding), VmError> {
+    let mut i = mutations.len();
+    while i > 0 {
+        i -= 1;
+        let mut smaller = mutations.clone();
+        smaller.remove(i);
+        if let Some(smaller_finding) = check_case(base, &smaller, run)?
+            && smaller_finding.kind == finding.kind
+        {
+            mutations = smaller;
+            finding = smaller_finding;
+        }
+    }
+    Ok((mutations, finding))
+}
+
+/// Runs `cases` randomly mutated copies of `base`, returning one shrunk case per kind of failure.
+/// A case that traps or runs out of gas is a finding; an error from `run` ends the fuzzing.
+pub fn fuzz(
+    base: &MockData,
+    cases: u32,
+    seed: u64,
+    mut run: impl FnMut(MockData) -> Result<RunResult, VmError>,
+) -> Result<Vec<FoundCase>, VmError> {
+    let mut rng = Rng::new(seed);
+    let mut found: BTreeMap<FindingKind, FoundCase> = BTreeMap::new();
+    for case in 0..cases {
+        let mut state = base.clone();
+        let mut mutations = Vec::new();
+        for _ in 0..=rng.below(MAX_MUTATIONS) {
+            if let Some(mutation) = generate_mutation(&mut rng, &state) {
+                mutation.apply(&mut state);
+                mutations.push(mutation);
+            }
+        }
+        let Some(finding) = check_case(base, &mutations, &mut run)? else {
+            continue;
+        };
+        match found.get_mut(&finding.kind) {
+            Some(existing) => existing.count += 1,
+            None => {
+                let kind = finding.kind;
+                let (mutations, finding) = shrink(base, mutations, finding, &mut run)?;
+                found.insert(
+                    kind,
+                    FoundCase {
+                        finding,
+                        case,
+                        count: 1,
+                        mutations,
+                    },
+                );
+            }
+        }
+    }
+    Ok(found.into_values().collect())
+}
+
+/// Writes the base fixture with the case's mutations applied as a new test case under
+/// `fixtures_root`, returning its directory. Its `expected.json` holds the finding's
+/// assertions, so the test case passes for as long as the failure reproduces.
+pub fn write_fixture(
+    fixtures_root: &Path,
+    base_dir: &Path,
+    base: &MockData,
+    name: &str,
+    case: &FoundCase,
+) -> std::io::Result<PathBuf> {
+    let mut state = base.clone();
+    for mutation in &case.mutations {
+        mutation.apply(&mut state);
+    }
+    let dir = fixtures_root.join(name);
+    fs::create_dir_all(&dir)?;
+    let pretty = |value: &Value| serde_json::to_string_pretty(value).unwrap() + "\n";
+    fs::write(dir.join("tx.json"), pretty(state.get_tx()))?;
+    fs::write(
+        dir.join("ledger_object.json"),
+        pretty(&state.get_current_ledger_obj()),
+    )?;
+    fs::write(dir.join("ledger_header.json"), pretty(state.get_header()))?;
+    fs::write(dir.join("ledger.json"), pretty(&state.get_ledger_json()))?;
+    fs::copy(base_dir.join("nfts.json"), dir.join("nfts.json"))?;
+    fs::write(dir.join("expected.json"), pretty(&case.finding.expected))?;
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::returned;
+
+    fn base() -> MockData {
+        MockData::new(
+            r#"{ "TransactionType": "EscrowFinish", "Account": "rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh", "Fee": "10", "OfferSequence": 5, "Memos": [{ "Memo": { "MemoData": "01" } }] }"#,
+            r#"{ "LedgerEntryType": "Escrow", "Data": "00" }"#,
+            r#"{ "ledger_index": 10 }"#,
+            r#"[{ "LedgerEntryType": "AccountRoot", "Account": "rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh" }]"#,
+            "[]",
+        )
+        .unwrap()
+    }
+
+    fn out_of_gas_without_fee(data: MockData) -> Result<RunResult, VmError> {
+        if data.get_tx().get("Fee").is_none() {
+            let mut run = returned(0);
+            run.outcome = Err(VmError::OutOfGas { gas_cap: 100 });
+            return Ok(run);
+        }
+        Ok(returned(1))
+    }
+
+    #[test]
+    fn test_mutations_apply_to_their_target() {
+        let mut state = base();
+        Mutation::Remove {
+            target: Target::Tx,
+            field: "Fee".to_string(),
+        }
+        .apply(&mut state);
+        Mutation::Set {
+            target: Target::LedgerObject,
+            field: "Data".to_string(),
+            value: json!("FF"),
+        }
+        .apply(&mut state);
+        assert!(state.get_tx().get("Fee").is_none());
+        assert_eq!(state.get_current_ledger_obj_data(), Some(vec![0xFF]));
+
+        let keylet = state.ledger_keylets()[0].clone();
+        Mutation::Set {
+            target: Target::Ledger(keylet.clone()),
+            field: "Balance".to_string(),
+            value: json!("0"),
+        }
+        .apply(&mut state);
+        assert_eq!(state.get_ledger_obj(&keylet).unwrap()["Balance"], "0");
+
+        Mutation::DeleteObject(keylet.clone()).apply(&mut state);
+        assert!(state.ledger_keylets().is_empty());
+        // Mutations of a deleted object do nothing
+        Mutation::Remove {
+            target: Target::Ledger(keylet),
+            field: "Balance".to_string(),
+        }
+        .apply(&mut state);
+        assert!(state.ledger_keylets().is_empty());
+    }
+
+    #[test]
+    fn test_generated_mutations_keep_the_fixture_loadable() {
+        let base = base();
+        let keylet = base.ledger_keylets()[0].clone();
+        let mut rng = Rng::new(3);
+        let mut ledger_mutations = 0;
+        for _ in 0..500 {
+            let Some(mutation) = generate_mutation(&mut rng, &base) else {
+                continue;
+            };
+            if let Mutation::Set {
+                target: Target::Ledger(_),
+                ..
+            }
+            | Mutation::Remove {
+                target: Target::Ledger(_),
+                ..
+            } = &mutation
+            {
+                ledger_mutations += 1;
+            }
+            let mut state = base.clone();
+            mutation.apply(&mut state);
+            if let Some(object) = state.get_ledger_obj(&keylet) {
+                assert!(ledger_entry_key(Some(&hex::encode_upper(&keylet)), object).is_ok());
+            }
+            if let Mutation::Set { value, .. } = &mutation {
+                // No negative drops
+                assert!(
+                    !value.as_str().is_some_and(|d| d.starts_with('-')),
+                    "{mutation}"
+                );
+                assert_ne!(value["currency"], "XRP", "{mutation}");
+                let mpt = value.get("mpt_issuance_id").map(|_| &value["value"]);
+                assert!(!mpt.is_some_and(|v| v.as_str().unwrap().starts_with('-')));
+            }
+        }
+        assert!(ledger_mutations > 0);
+    }
+
+    #[test]
+    fn test_fuzz_finds_and_shrinks_failures() {
+        let found = fuzz(&base(), 200, 7, out_of_gas_without_fee).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].finding.kind, FindingKind::OutOfGas);
+        assert_eq!(found[0].finding.expected, json!({ "error": "out_of_gas" }));
+        assert_eq!(
+            found[0].mutations,
+            vec![Mutation::Remove {
+                target: Target::Tx,
+                field: "Fee".to_string()
+            }]
+        );
+
+        // The same seed finds the same case
+        let again = fuzz(&base(), 200, 7, out_of_gas_without_fee).unwrap();
+        assert_eq!(again[0].case, found[0].case);
+        assert_eq!(again[0].count, found[0].count);
+    }
+
+    #[test]
+    fn test_fuzz_detects_nondeterminism() {
+        let mut calls = 0;
+        let found = fuzz(&base(), 1, 1, |_| {
+            calls += 1;
+            Ok(returned(calls % 2))
+        })
+        .unwrap();
+        assert_eq!(found[0].finding.kind, FindingKind::Nondeterminism);
+        assert_eq!(found[0].finding.detail, "returned 1 then returned 0");
+        assert_eq!(
+            found[0].finding.expected,
+            json!({ "return_value": 1, "max_gas": 100 })
+        );
+    }
+
+    #[test]
+    fn test_write_fixture_reproduces_the_finding() {
+        let found = fuzz(&base(), 200, 7, out_of_gas_without_fee).unwrap();
+        let root = std::env::temp_dir().join(format!("fuzz-fixture-{}", std::process::id()));
+        let base_dir = root.join("base");
+        fs::create_dir_all(&base_dir).unwrap();
+        fs::write(base_dir.join("nfts.json"), "[]").unwrap();
+
+        let dir = write_fixture(&root, &base_dir, &base(), "found", &found[0]).unwrap();
+        let expected = fs::read_to_string(dir.join("expected.json")).unwrap();
+        let expectations = crate::expectations::Expectations::from_json(&expected).unwrap();
+        assert_eq!(expectations.error.as_deref(), Some("out_of_gas"));
+        let tx: Value =
+            serde_json::from_str(&fs::read_to_string(dir.join("tx.json")).unwrap()).unwrap();
+        assert!(tx.get("Fee").is_none());
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -3,12 +3,13 @@ use crate::data_provider::{
     DataProvider, HostError, RippledRoundingMode, XRPL_CONTRACT_DATA_SIZE, error_code_to_string,
     unpack_locator,
 };
+use crate::debugger::{Guest, WamrGuest};
 use crate::decoding::{
     _deserialize_issued_currency_amount, _serialize_issued_currency_value, ACCOUNT_ID_LEN,
     CURRENCY_LEN, MPT_ID_LEN, decode_account_id,
 };
 use crate::hashing::{HASH256_LEN, LedgerNameSpace, index_hash, sha512_half};
-use crate::mock_data::{DataSource, Keylet};
+use crate::mock_data::DataSource;
 use bigdecimal::num_bigint::{BigInt, ToBigInt};
 use bigdecimal::num_traits::real::Real;
 use bigdecimal::{BigDecimal, ToPrimitive};
@@ -16,8 +17,7 @@ use hex::decode;
 use log::{debug, warn};
 use num_traits::FromPrimitive;
 use wamr_rust_sdk::sys::{
-    wasm_exec_env_t, wasm_runtime_get_function_attachment, wasm_runtime_get_module_inst,
-    wasm_runtime_validate_native_addr,
+    wasm_exec_env_t, wasm_runtime_get_custom_data, wasm_runtime_get_module_inst,
 };
 use xrpl::core::addresscodec::utils::encode_base58;
 use xrpl_wasm_std::core::types::amount::token_amount::TokenAmount;
@@ -75,100 +75,171 @@ fn set_rounding_mode_from_param(rounding_mode: i32) -> RoundingModeGuard {
 }
 
 const MAX_WASM_PARAM_LENGTH: usize = 1024;
+const MAX_AMENDMENT_NAME_LENGTH: usize = 64;
+const MAX_CREDENTIAL_TYPE_LENGTH: usize = 64;
 
 pub fn get_dp(env: wasm_exec_env_t) -> &'static mut DataProvider {
-    unsafe { &mut *(wasm_runtime_get_function_attachment(env) as *mut DataProvider) }
-}
-
-fn get_data(in_buf_ptr: *const u8, in_buf_len: usize) -> Vec<u8> {
-    let mut buffer = vec![0u8; in_buf_len];
     unsafe {
-        std::ptr::copy_nonoverlapping(in_buf_ptr, buffer.as_mut_ptr(), in_buf_len);
+        let instance = wasm_runtime_get_module_inst(env);
+        &mut *(wasm_runtime_get_custom_data(instance) as *mut DataProvider)
     }
-    buffer
 }
 
-fn get_keylet(in_buf_ptr: *const u8, in_buf_len: usize) -> Keylet {
-    get_data(in_buf_ptr, in_buf_len)
+/// A pointer argument as the guest passes it: an offset into its linear memory. Host functions
+/// are registered with plain `i` parameters rather than WAMR's checked `*~` pairs, so a buffer
+/// outside linear memory gets `POINTER_OUT_OF_BOUND` back, as on rippled, instead of a trap.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GuestPtr(pub u32);
+
+/// The `len` bytes of the guest buffer at `ptr`. An empty buffer is never out of bounds.
+fn read_buffer(guest: &dyn Guest, ptr: GuestPtr, len: usize) -> Result<Vec<u8>, HostError> {
+    if len == 0 {
+        return Ok(Vec::new());
+    }
+    guest
+        .read(ptr.0 as u64, len as u64)
+        .ok_or(HostError::PointerOutOfBound)
 }
 
-fn set_data(dp_res: i32, out_buf_ptr: *mut u8, data_to_write: Vec<u8>) {
-    if dp_res > 0 {
-        unsafe {
-            std::ptr::copy_nonoverlapping(data_to_write.as_ptr(), out_buf_ptr, data_to_write.len());
-        }
+/// Writes `bytes` to the guest's output buffer of `cap` bytes at `ptr`. As on rippled, the whole
+/// buffer must be in linear memory, not just the bytes written.
+fn write_buffer(
+    guest: &dyn Guest,
+    ptr: GuestPtr,
+    cap: usize,
+    bytes: &[u8],
+) -> Result<(), HostError> {
+    if bytes.is_empty() {
+        return Ok(());
     }
+    if !guest.contains(ptr.0 as u64, cap as u64) {
+        return Err(HostError::PointerOutOfBound);
+    }
+    if bytes.len() > cap {
+        return Err(HostError::BufferTooSmall);
+    }
+    if !guest.write(ptr.0 as u64, bytes) {
+        return Err(HostError::PointerOutOfBound);
+    }
+    Ok(())
 }
 
-pub fn get_ledger_sqn(env: wasm_exec_env_t) -> i32 {
-    let data_provider = get_dp(env);
-    data_provider.get_ledger_sqn()
+fn get_data(
+    guest: &dyn Guest,
+    in_buf_ptr: GuestPtr,
+    in_buf_len: usize,
+) -> Result<Vec<u8>, HostError> {
+    read_buffer(guest, in_buf_ptr, in_buf_len)
 }
 
-pub fn get_parent_ledger_time(
-    env: wasm_exec_env_t,
-    out_buf_ptr: *mut u8,
+/// Writes `data_to_write` to the output buffer if the host function succeeded, returning
+/// `dp_res`, or the error if the buffer is not in linear memory.
+fn set_data(
+    guest: &dyn Guest,
+    dp_res: i32,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
+    data_to_write: Vec<u8>,
 ) -> i32 {
-    let data_provider = get_dp(env);
+    if dp_res <= 0 {
+        return dp_res;
+    }
+    match write_buffer(guest, out_buf_ptr, out_buf_cap, &data_to_write) {
+        Ok(()) => dp_res,
+        Err(e) => e as i32,
+    }
+}
+
+pub fn get_ledger_sqn(data_provider: &mut DataProvider, guest: &dyn Guest) -> i32 {
+    data_provider.get_ledger_sqn()
+}
+
+pub fn get_parent_ledger_time(data_provider: &mut DataProvider, guest: &dyn Guest) -> i32 {
     data_provider.get_parent_ledger_time()
 }
 
 pub fn get_parent_ledger_hash(
-    env: wasm_exec_env_t,
-    out_buf_ptr: *mut u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
-    let data_provider = get_dp(env);
     let dp_res = data_provider.get_parent_ledger_hash(out_buf_cap);
-    set_data(dp_res.0, out_buf_ptr, dp_res.1);
-    dp_res.0
+    set_data(guest, dp_res.0, out_buf_ptr, out_buf_cap, dp_res.1)
+}
+
+pub fn get_base_fee(data_provider: &mut DataProvider, guest: &dyn Guest) -> i32 {
+    data_provider.get_base_fee()
+}
+
+pub fn amendment_enabled(
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    amendment_ptr: GuestPtr,
+    amendment_len: usize,
+) -> i32 {
+    if amendment_len > MAX_AMENDMENT_NAME_LENGTH {
+        return HostError::DataFieldTooLarge as i32;
+    }
+    let amendment = match get_data(guest, amendment_ptr, amendment_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
+    // A 32-byte argument is an amendment ID, anything else is an amendment name.
+    let amendment_id = if amendment.len() == HASH256_LEN {
+        amendment
+    } else {
+        sha512_half(&amendment)
+    };
+    data_provider.amendment_enabled(&amendment_id)
 }
 
 pub fn cache_ledger_obj(
-    env: wasm_exec_env_t,
-    in_buf_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    in_buf_ptr: GuestPtr,
     in_buf_cap: usize,
     cache_num: i32,
 ) -> i32 {
-    let data_provider = get_dp(env);
-    let keylet = get_keylet(in_buf_ptr, in_buf_cap);
+    let keylet = match get_data(guest, in_buf_ptr, in_buf_cap) {
+        Ok(keylet) => keylet,
+        Err(e) => return e as i32,
+    };
     data_provider.slot_set(keylet, cache_num as usize)
 }
 
 pub fn get_tx_field(
-    env: wasm_exec_env_t,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
     field: i32,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
-    let data_provider = get_dp(env);
     let dp_res = data_provider.get_field_value(DataSource::Tx, vec![field], out_buf_cap);
-    set_data(dp_res.0, out_buf_ptr, dp_res.1);
-    dp_res.0
+    set_data(guest, dp_res.0, out_buf_ptr, out_buf_cap, dp_res.1)
 }
 
 pub fn get_current_ledger_obj_field(
-    env: wasm_exec_env_t,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
     field: i32,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
-    let data_provider = get_dp(env);
     let dp_res =
         data_provider.get_field_value(DataSource::CurrentLedgerObj, vec![field], out_buf_cap);
-    set_data(dp_res.0, out_buf_ptr, dp_res.1);
-    dp_res.0
+    set_data(guest, dp_res.0, out_buf_ptr, out_buf_cap, dp_res.1)
 }
 
 pub fn get_ledger_obj_field(
-    env: wasm_exec_env_t,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
     slot: i32,
     field: i32,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
-    let data_provider = get_dp(env);
     let keylet = match data_provider.slot_get(slot as usize) {
         None => return HostError::EmptySlot as i32,
         Some(key) => key.clone(),
@@ -179,38 +250,42 @@ pub fn get_ledger_obj_field(
         out_buf_cap,
     );
 
-    set_data(dp_res.0, out_buf_ptr, dp_res.1);
-    dp_res.0
+    set_data(guest, dp_res.0, out_buf_ptr, out_buf_cap, dp_res.1)
 }
 
 pub fn get_tx_nested_field(
-    env: wasm_exec_env_t,
-    in_buf_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    in_buf_ptr: GuestPtr,
     in_buf_len: usize,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
-    let data_provider = get_dp(env);
-    let data = get_data(in_buf_ptr, in_buf_len);
+    let data = match get_data(guest, in_buf_ptr, in_buf_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     let idx_fields: Vec<i32> = match unpack_locator(data) {
         Ok(fields) => fields,
         Err(host_err) => return host_err as i32,
     };
 
     let dp_res = data_provider.get_field_value(DataSource::Tx, idx_fields, out_buf_cap);
-    set_data(dp_res.0, out_buf_ptr, dp_res.1);
-    dp_res.0
+    set_data(guest, dp_res.0, out_buf_ptr, out_buf_cap, dp_res.1)
 }
 
 pub fn get_current_ledger_obj_nested_field(
-    env: wasm_exec_env_t,
-    in_buf_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    in_buf_ptr: GuestPtr,
     in_buf_len: usize,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
-    let data_provider = get_dp(env);
-    let data = get_data(in_buf_ptr, in_buf_len);
+    let data = match get_data(guest, in_buf_ptr, in_buf_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     let idx_fields: Vec<i32> = match unpack_locator(data) {
         Ok(fields) => fields,
         Err(host_err) => return host_err as i32,
@@ -218,25 +293,27 @@ pub fn get_current_ledger_obj_nested_field(
 
     let dp_res =
         data_provider.get_field_value(DataSource::CurrentLedgerObj, idx_fields, out_buf_cap);
-    set_data(dp_res.0, out_buf_ptr, dp_res.1);
-    dp_res.0
+    set_data(guest, dp_res.0, out_buf_ptr, out_buf_cap, dp_res.1)
 }
 
 pub fn get_ledger_obj_nested_field(
-    env: wasm_exec_env_t,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
     slot: i32,
-    in_buf_ptr: *const u8,
+    in_buf_ptr: GuestPtr,
     in_buf_len: usize,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
-    let data_provider = get_dp(env);
     let keylet = match data_provider.slot_get(slot as usize) {
         None => return HostError::EmptySlot as i32,
         Some(key) => key.clone(),
     };
 
-    let data = get_data(in_buf_ptr, in_buf_len);
+    let data = match get_data(guest, in_buf_ptr, in_buf_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     let idx_fields: Vec<i32> = match unpack_locator(data) {
         Ok(fields) => fields,
         Err(host_err) => return host_err as i32,
@@ -244,20 +321,25 @@ pub fn get_ledger_obj_nested_field(
 
     let dp_res =
         data_provider.get_field_value(DataSource::KeyletLedgerObj(keylet), idx_fields, out_buf_cap);
-    set_data(dp_res.0, out_buf_ptr, dp_res.1);
-    dp_res.0
+    set_data(guest, dp_res.0, out_buf_ptr, out_buf_cap, dp_res.1)
 }
 
-pub fn get_tx_array_len(env: wasm_exec_env_t, field: i32) -> i32 {
-    let data_provider = get_dp(env);
+pub fn get_tx_array_len(data_provider: &mut DataProvider, guest: &dyn Guest, field: i32) -> i32 {
     data_provider.get_array_len(DataSource::Tx, vec![field])
 }
-pub fn get_current_ledger_obj_array_len(env: wasm_exec_env_t, field: i32) -> i32 {
-    let data_provider = get_dp(env);
+pub fn get_current_ledger_obj_array_len(
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    field: i32,
+) -> i32 {
     data_provider.get_array_len(DataSource::CurrentLedgerObj, vec![field])
 }
-pub fn get_ledger_obj_array_len(env: wasm_exec_env_t, slot: i32, field: i32) -> i32 {
-    let data_provider = get_dp(env);
+pub fn get_ledger_obj_array_len(
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    slot: i32,
+    field: i32,
+) -> i32 {
     let keylet = match data_provider.slot_get(slot as usize) {
         None => return HostError::EmptySlot as i32,
         Some(key) => key.clone(),
@@ -265,12 +347,15 @@ pub fn get_ledger_obj_array_len(env: wasm_exec_env_t, slot: i32, field: i32) ->
     data_provider.get_array_len(DataSource::KeyletLedgerObj(keylet), vec![field])
 }
 pub fn get_tx_nested_array_len(
-    env: wasm_exec_env_t,
-    in_buf_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    in_buf_ptr: GuestPtr,
     in_buf_len: usize,
 ) -> i32 {
-    let data_provider = get_dp(env);
-    let data = get_data(in_buf_ptr, in_buf_len);
+    let data = match get_data(guest, in_buf_ptr, in_buf_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     let idx_fields: Vec<i32> = match unpack_locator(data) {
         Ok(fields) => fields,
         Err(host_err) => return host_err as i32,
@@ -278,12 +363,15 @@ pub fn get_tx_nested_array_len(
     data_provider.get_array_len(DataSource::Tx, idx_fields)
 }
 pub fn get_current_ledger_obj_nested_array_len(
-    env: wasm_exec_env_t,
-    in_buf_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    in_buf_ptr: GuestPtr,
     in_buf_len: usize,
 ) -> i32 {
-    let data_provider = get_dp(env);
-    let data = get_data(in_buf_ptr, in_buf_len);
+    let data = match get_data(guest, in_buf_ptr, in_buf_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     let idx_fields: Vec<i32> = match unpack_locator(data) {
         Ok(fields) => fields,
         Err(host_err) => return host_err as i32,
@@ -291,18 +379,21 @@ pub fn get_current_ledger_obj_nested_array_len(
     data_provider.get_array_len(DataSource::CurrentLedgerObj, idx_fields)
 }
 pub fn get_ledger_obj_nested_array_len(
-    env: wasm_exec_env_t,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
     slot: i32,
-    in_buf_ptr: *const u8,
+    in_buf_ptr: GuestPtr,
     in_buf_len: usize,
 ) -> i32 {
-    let data_provider = get_dp(env);
     let keylet = match data_provider.slot_get(slot as usize) {
         None => return HostError::EmptySlot as i32,
         Some(key) => key.clone(),
     };
 
-    let data = get_data(in_buf_ptr, in_buf_len);
+    let data = match get_data(guest, in_buf_ptr, in_buf_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     let idx_fields: Vec<i32> = match unpack_locator(data) {
         Ok(fields) => fields,
         Err(host_err) => return host_err as i32,
@@ -310,20 +401,28 @@ pub fn get_ledger_obj_nested_array_len(
 
     data_provider.get_array_len(DataSource::KeyletLedgerObj(keylet), idx_fields)
 }
-pub fn update_data(env: wasm_exec_env_t, in_buf_ptr: *const u8, in_buf_len: usize) -> i32 {
-    let data_provider = get_dp(env);
+pub fn update_data(
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    in_buf_ptr: GuestPtr,
+    in_buf_len: usize,
+) -> i32 {
     if in_buf_len > XRPL_CONTRACT_DATA_SIZE {
         return HostError::DataFieldTooLarge as i32;
     }
-    let data = get_data(in_buf_ptr, in_buf_len);
+    let data = match get_data(guest, in_buf_ptr, in_buf_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     data_provider.set_current_ledger_obj_data(data);
     0
 }
 pub fn compute_sha512_half(
-    _env: wasm_exec_env_t,
-    in_buf_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    in_buf_ptr: GuestPtr,
     in_buf_len: usize,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
@@ -332,31 +431,111 @@ pub fn compute_sha512_half(
     if in_buf_len > MAX_WASM_PARAM_LENGTH {
         return HostError::DataFieldTooLarge as i32;
     }
-    let data = get_data(in_buf_ptr, in_buf_len);
+    let data = match get_data(guest, in_buf_ptr, in_buf_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     let hash_half = sha512_half(&data);
-    set_data(hash_half.len() as i32, out_buf_ptr, hash_half);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        hash_half,
+    )
+}
+
+/// Verifies `signature` over `message` the way rippled does: Ed25519 keys (0xED prefix) sign the
+/// raw message, secp256k1 keys sign its SHA-512Half and must use a canonical DER signature.
+fn verify_signature(message: &[u8], signature: &[u8], pubkey: &[u8]) -> Result<bool, HostError> {
+    match pubkey.first() {
+        Some(0xED) if pubkey.len() == 33 => {
+            let key_bytes: [u8; 32] = pubkey[1..].try_into().unwrap();
+            let Ok(key) = ed25519_dalek::VerifyingKey::from_bytes(&key_bytes) else {
+                return Err(HostError::InvalidParams);
+            };
+            let Ok(signature) = ed25519_dalek::Signature::from_slice(signature) else {
+                return Ok(false);
+            };
+            Ok(key.verify_strict(message, &signature).is_ok())
+        }
+        Some(0x02) | Some(0x03) if pubkey.len() == 33 => {
+            let Ok(key) = secp256k1::PublicKey::from_slice(pubkey) else {
+                return Err(HostError::InvalidParams);
+            };
+            let Ok(signature) = secp256k1::ecdsa::Signature::from_der(signature) else {
+                return Ok(false);
+            };
+            let digest: [u8; 32] = sha512_half(message).try_into().unwrap();
+            let message = secp256k1::Message::from_digest(digest);
+            let secp = secp256k1::Secp256k1::verification_only();
+            Ok(secp.verify_ecdsa(&message, &signature, &key).is_ok())
+        }
+        _ => Err(HostError::InvalidParams),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn check_sig(
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    message_ptr: GuestPtr,
+    message_len: usize,
+    signature_ptr: GuestPtr,
+    signature_len: usize,
+    pubkey_ptr: GuestPtr,
+    pubkey_len: usize,
+) -> i32 {
+    if message_len > MAX_WASM_PARAM_LENGTH
+        || signature_len > MAX_WASM_PARAM_LENGTH
+        || pubkey_len > MAX_WASM_PARAM_LENGTH
+    {
+        return HostError::DataFieldTooLarge as i32;
+    }
+    let message = match get_data(guest, message_ptr, message_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
+    let signature = match get_data(guest, signature_ptr, signature_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
+    let pubkey = match get_data(guest, pubkey_ptr, pubkey_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
+    match verify_signature(&message, &signature, &pubkey) {
+        Ok(valid) => valid as i32,
+        Err(e) => e as i32,
+    }
 }
 
 pub fn account_keylet(
-    _env: wasm_exec_env_t,
-    account_buf_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    account_buf_ptr: GuestPtr,
     account_buf_len: usize,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
         return HostError::BufferTooSmall as i32;
     }
-    let data = get_data(account_buf_ptr, account_buf_len);
+    let data = match get_data(guest, account_buf_ptr, account_buf_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if ACCOUNT_ID_LEN != data.len() {
         return HostError::InvalidAccount as i32;
     }
     let keylet_hash = index_hash(LedgerNameSpace::Account, &data);
-    // let hex_str = hex::encode(&keylet_hash);
-    // println!("Data (keylet_hash): {:?}", hex_str);
-    set_data(keylet_hash.len() as i32, out_buf_ptr, keylet_hash);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        keylet_hash,
+    )
 }
 
 struct Issue {
@@ -425,23 +604,25 @@ fn parse_asset(asset_data: &[u8]) -> Result<Issue, HostError> {
     Err(HostError::InvalidParams)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn amm_keylet(
-    _env: wasm_exec_env_t,
-    asset1_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    asset1_ptr: GuestPtr,
     asset1_len: usize,
-    asset2_ptr: *const u8,
+    asset2_ptr: GuestPtr,
     asset2_len: usize,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
         return HostError::BufferTooSmall as i32;
     }
-    let asset1 = match parse_asset(&get_data(asset1_ptr, asset1_len)) {
+    let asset1 = match get_data(guest, asset1_ptr, asset1_len).and_then(|d| parse_asset(&d)) {
         Ok(a) => a,
         Err(e) => return e as i32,
     };
-    let asset2 = match parse_asset(&get_data(asset2_ptr, asset2_len)) {
+    let asset2 = match get_data(guest, asset2_ptr, asset2_len).and_then(|d| parse_asset(&d)) {
         Ok(a) => a,
         Err(e) => return e as i32,
     };
@@ -456,166 +637,255 @@ pub fn amm_keylet(
     data.extend_from_slice(&max_asset.issuer);
     data.extend_from_slice(&max_asset.currency);
     let keylet_hash = index_hash(LedgerNameSpace::Amm, &data);
-    set_data(keylet_hash.len() as i32, out_buf_ptr, keylet_hash);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        keylet_hash,
+    )
 }
 
 pub fn check_keylet(
-    _env: wasm_exec_env_t,
-    account_buf_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    account_buf_ptr: GuestPtr,
     account_buf_len: usize,
     sequence: i32,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
         return HostError::BufferTooSmall as i32;
     }
-    let mut data = get_data(account_buf_ptr, account_buf_len);
+    let mut data = match get_data(guest, account_buf_ptr, account_buf_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if ACCOUNT_ID_LEN != data.len() {
         return HostError::InvalidAccount as i32;
     }
     let sqn_data = sequence.to_be_bytes();
     data.extend_from_slice(&sqn_data);
     let keylet_hash = index_hash(LedgerNameSpace::Check, &data);
-    set_data(keylet_hash.len() as i32, out_buf_ptr, keylet_hash);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        keylet_hash,
+    )
 }
 
 #[allow(clippy::too_many_arguments)]
 pub fn credential_keylet(
-    _env: wasm_exec_env_t,
-    subject_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    subject_ptr: GuestPtr,
     subject_len: usize,
-    issuer_ptr: *const u8,
+    issuer_ptr: GuestPtr,
     issuer_len: usize,
-    cred_type_ptr: *const u8,
+    cred_type_ptr: GuestPtr,
     cred_type_len: usize,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
         return HostError::BufferTooSmall as i32;
     }
-    let subject = get_data(subject_ptr, subject_len); // check length?
-    let mut issuer = get_data(issuer_ptr, issuer_len);
+    let subject = match get_data(guest, subject_ptr, subject_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
+    if ACCOUNT_ID_LEN != subject.len() {
+        return HostError::InvalidAccount as i32;
+    }
+    let mut issuer = match get_data(guest, issuer_ptr, issuer_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if ACCOUNT_ID_LEN != issuer.len() {
         return HostError::InvalidAccount as i32;
     }
-    let mut cred_type = get_data(cred_type_ptr, cred_type_len); // check length?
+    let mut cred_type = match get_data(guest, cred_type_ptr, cred_type_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
+    if cred_type.is_empty() || cred_type.len() > MAX_CREDENTIAL_TYPE_LENGTH {
+        return HostError::InvalidParams as i32;
+    }
     let mut data = subject;
     data.append(&mut issuer);
     data.append(&mut cred_type);
     let keylet_hash = index_hash(LedgerNameSpace::Credential, &data);
-    set_data(keylet_hash.len() as i32, out_buf_ptr, keylet_hash);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        keylet_hash,
+    )
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn delegate_keylet(
-    _env: wasm_exec_env_t,
-    account_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    account_ptr: GuestPtr,
     account_len: usize,
-    authorize_ptr: *const u8,
+    authorize_ptr: GuestPtr,
     authorize_len: usize,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
         return HostError::BufferTooSmall as i32;
     }
-    let mut data = get_data(account_ptr, account_len);
-    let mut authorized = get_data(authorize_ptr, authorize_len);
+    let mut data = match get_data(guest, account_ptr, account_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
+    let mut authorized = match get_data(guest, authorize_ptr, authorize_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if ACCOUNT_ID_LEN != data.len() || ACCOUNT_ID_LEN != authorized.len() {
         return HostError::InvalidAccount as i32;
     }
     data.append(&mut authorized);
     let keylet_hash = index_hash(LedgerNameSpace::Delegate, &data);
-    set_data(keylet_hash.len() as i32, out_buf_ptr, keylet_hash);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        keylet_hash,
+    )
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn deposit_preauth_keylet(
-    _env: wasm_exec_env_t,
-    account_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    account_ptr: GuestPtr,
     account_len: usize,
-    authorize_ptr: *const u8,
+    authorize_ptr: GuestPtr,
     authorize_len: usize,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
         return HostError::BufferTooSmall as i32;
     }
-    let mut data = get_data(account_ptr, account_len);
-    let mut authorized = get_data(authorize_ptr, authorize_len);
+    let mut data = match get_data(guest, account_ptr, account_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
+    let mut authorized = match get_data(guest, authorize_ptr, authorize_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if ACCOUNT_ID_LEN != data.len() || ACCOUNT_ID_LEN != authorized.len() {
         return HostError::InvalidAccount as i32;
     }
     data.append(&mut authorized);
     let keylet_hash = index_hash(LedgerNameSpace::DepositPreauth, &data);
-    set_data(keylet_hash.len() as i32, out_buf_ptr, keylet_hash);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        keylet_hash,
+    )
 }
 
 pub fn did_keylet(
-    _env: wasm_exec_env_t,
-    account_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    account_ptr: GuestPtr,
     account_len: usize,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
         return HostError::BufferTooSmall as i32;
     }
-    let mut data = get_data(account_ptr, account_len);
+    let mut data = match get_data(guest, account_ptr, account_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if ACCOUNT_ID_LEN != data.len() {
         return HostError::InvalidAccount as i32;
     }
     let keylet_hash = index_hash(LedgerNameSpace::Did, &data);
-    set_data(keylet_hash.len() as i32, out_buf_ptr, keylet_hash);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        keylet_hash,
+    )
 }
 
 pub fn escrow_keylet(
-    _env: wasm_exec_env_t,
-    account_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    account_ptr: GuestPtr,
     account_len: usize,
     sequence: u32,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
         return HostError::BufferTooSmall as i32;
     }
-    let mut data = get_data(account_ptr, account_len);
+    let mut data = match get_data(guest, account_ptr, account_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if ACCOUNT_ID_LEN != data.len() {
         return HostError::InvalidAccount as i32;
     }
     let sqn_data = sequence.to_be_bytes();
     data.extend_from_slice(&sqn_data);
     let keylet_hash = index_hash(LedgerNameSpace::Escrow, &data);
-    set_data(keylet_hash.len() as i32, out_buf_ptr, keylet_hash);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        keylet_hash,
+    )
 }
 
 #[allow(clippy::too_many_arguments)]
 pub fn line_keylet(
-    _env: wasm_exec_env_t,
-    account1_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    account1_ptr: GuestPtr,
     account1_len: usize,
-    account2_ptr: *const u8,
+    account2_ptr: GuestPtr,
     account2_len: usize,
-    currency_ptr: *const u8,
+    currency_ptr: GuestPtr,
     currency_len: usize,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
         return HostError::BufferTooSmall as i32;
     }
-    let mut account1 = get_data(account1_ptr, account1_len);
-    let mut account2 = get_data(account2_ptr, account2_len);
-    let mut currency = get_data(currency_ptr, currency_len);
+    let mut account1 = match get_data(guest, account1_ptr, account1_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
+    let mut account2 = match get_data(guest, account2_ptr, account2_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
+    let mut currency = match get_data(guest, currency_ptr, currency_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if ACCOUNT_ID_LEN != account1.len() || ACCOUNT_ID_LEN != account2.len() {
         return HostError::InvalidAccount as i32;
     }
@@ -626,22 +896,31 @@ pub fn line_keylet(
     data.append(&mut account2);
     data.append(&mut currency);
     let keylet_hash = index_hash(LedgerNameSpace::TrustLine, &data);
-    set_data(keylet_hash.len() as i32, out_buf_ptr, keylet_hash);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        keylet_hash,
+    )
 }
 
 pub fn mpt_issuance_keylet(
-    _env: wasm_exec_env_t,
-    issuer_buf_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    issuer_buf_ptr: GuestPtr,
     issuer_buf_len: usize,
     sequence: i32,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
         return HostError::BufferTooSmall as i32;
     }
-    let mut account = get_data(issuer_buf_ptr, issuer_buf_len);
+    let mut account = match get_data(guest, issuer_buf_ptr, issuer_buf_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if ACCOUNT_ID_LEN != account.len() {
         return HostError::InvalidAccount as i32;
     }
@@ -651,24 +930,37 @@ pub fn mpt_issuance_keylet(
     mpt_id.append(&mut account);
     let data = mpt_id;
     let keylet_hash = index_hash(LedgerNameSpace::MptokenIssuance, &data);
-    set_data(keylet_hash.len() as i32, out_buf_ptr, keylet_hash);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        keylet_hash,
+    )
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn mptoken_keylet(
-    _env: wasm_exec_env_t,
-    mpt_id_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    mpt_id_ptr: GuestPtr,
     mpt_id_len: usize,
-    holder_ptr: *const u8,
+    holder_ptr: GuestPtr,
     holder_len: usize,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
         return HostError::BufferTooSmall as i32;
     }
-    let mut mpt_id = get_data(mpt_id_ptr, mpt_id_len);
-    let mut holder = get_data(holder_ptr, holder_len);
+    let mut mpt_id = match get_data(guest, mpt_id_ptr, mpt_id_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
+    let mut holder = match get_data(guest, holder_ptr, holder_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if MPT_ID_LEN != mpt_id.len() {
         return HostError::InvalidParams as i32;
     }
@@ -679,92 +971,131 @@ pub fn mptoken_keylet(
     let mut data = mpt_id_hash;
     data.append(&mut holder);
     let keylet_hash = index_hash(LedgerNameSpace::Mptoken, &data);
-    set_data(keylet_hash.len() as i32, out_buf_ptr, keylet_hash);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        keylet_hash,
+    )
 }
 
 pub fn nft_offer_keylet(
-    _env: wasm_exec_env_t,
-    account_buf_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    account_buf_ptr: GuestPtr,
     account_buf_len: usize,
     sequence: i32,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
         return HostError::BufferTooSmall as i32;
     }
-    let mut data = get_data(account_buf_ptr, account_buf_len);
+    let mut data = match get_data(guest, account_buf_ptr, account_buf_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if ACCOUNT_ID_LEN != data.len() {
         return HostError::InvalidAccount as i32;
     }
     let sqn_data = sequence.to_be_bytes();
     data.extend_from_slice(&sqn_data);
     let keylet_hash = index_hash(LedgerNameSpace::NftokenOffer, &data);
-    set_data(keylet_hash.len() as i32, out_buf_ptr, keylet_hash);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        keylet_hash,
+    )
 }
 
 pub fn offer_keylet(
-    _env: wasm_exec_env_t,
-    account_buf_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    account_buf_ptr: GuestPtr,
     account_buf_len: usize,
     sequence: i32,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
         return HostError::BufferTooSmall as i32;
     }
-    let mut data = get_data(account_buf_ptr, account_buf_len);
+    let mut data = match get_data(guest, account_buf_ptr, account_buf_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if ACCOUNT_ID_LEN != data.len() {
         return HostError::InvalidAccount as i32;
     }
     let sqn_data = sequence.to_be_bytes();
     data.extend_from_slice(&sqn_data);
     let keylet_hash = index_hash(LedgerNameSpace::Offer, &data);
-    set_data(keylet_hash.len() as i32, out_buf_ptr, keylet_hash);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        keylet_hash,
+    )
 }
 
 pub fn oracle_keylet(
-    _env: wasm_exec_env_t,
-    account_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    account_ptr: GuestPtr,
     account_len: usize,
     document_id: u32,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
         return HostError::BufferTooSmall as i32;
     }
-    let mut data = get_data(account_ptr, account_len);
+    let mut data = match get_data(guest, account_ptr, account_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if ACCOUNT_ID_LEN != data.len() {
         return HostError::InvalidAccount as i32;
     }
     let sqn_data = document_id.to_be_bytes();
     data.extend_from_slice(&sqn_data);
     let keylet_hash = index_hash(LedgerNameSpace::Oracle, &data);
-    set_data(keylet_hash.len() as i32, out_buf_ptr, keylet_hash);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        keylet_hash,
+    )
 }
 
 #[allow(clippy::too_many_arguments)]
 pub fn paychan_keylet(
-    _env: wasm_exec_env_t,
-    account_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    account_ptr: GuestPtr,
     account_len: usize,
-    destination_ptr: *const u8,
+    destination_ptr: GuestPtr,
     destination_len: usize,
     sequence: i32,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
         return HostError::BufferTooSmall as i32;
     }
-    let mut data = get_data(account_ptr, account_len);
-    let mut destination = get_data(destination_ptr, destination_len);
+    let mut data = match get_data(guest, account_ptr, account_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
+    let mut destination = match get_data(guest, destination_ptr, destination_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if ACCOUNT_ID_LEN != data.len() || ACCOUNT_ID_LEN != destination.len() {
         return HostError::InvalidAccount as i32;
     }
@@ -772,43 +1103,61 @@ pub fn paychan_keylet(
     data.append(&mut destination);
     data.extend_from_slice(&sqn_data);
     let keylet_hash = index_hash(LedgerNameSpace::XrpPaymentChannel, &data);
-    set_data(keylet_hash.len() as i32, out_buf_ptr, keylet_hash);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        keylet_hash,
+    )
 }
 
 pub fn permissioned_domain_keylet(
-    _env: wasm_exec_env_t,
-    account_buf_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    account_buf_ptr: GuestPtr,
     account_buf_len: usize,
     sequence: i32,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
         return HostError::BufferTooSmall as i32;
     }
-    let mut data = get_data(account_buf_ptr, account_buf_len);
+    let mut data = match get_data(guest, account_buf_ptr, account_buf_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if ACCOUNT_ID_LEN != data.len() {
         return HostError::InvalidAccount as i32;
     }
     let sqn_data = sequence.to_be_bytes();
     data.extend_from_slice(&sqn_data);
     let keylet_hash = index_hash(LedgerNameSpace::PermissionedDomain, &data);
-    set_data(keylet_hash.len() as i32, out_buf_ptr, keylet_hash);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        keylet_hash,
+    )
 }
 
 pub fn signers_keylet(
-    _env: wasm_exec_env_t,
-    account_buf_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    account_buf_ptr: GuestPtr,
     account_buf_len: usize,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
         return HostError::BufferTooSmall as i32;
     }
-    let mut data = get_data(account_buf_ptr, account_buf_len);
+    let mut data = match get_data(guest, account_buf_ptr, account_buf_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if ACCOUNT_ID_LEN != data.len() {
         return HostError::InvalidAccount as i32;
     }
@@ -816,128 +1165,264 @@ pub fn signers_keylet(
     let sid_data = default_signer_list_id.to_be_bytes();
     data.extend_from_slice(&sid_data);
     let keylet_hash = index_hash(LedgerNameSpace::SignerList, &data);
-    set_data(keylet_hash.len() as i32, out_buf_ptr, keylet_hash);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        keylet_hash,
+    )
 }
 
 pub fn ticket_keylet(
-    _env: wasm_exec_env_t,
-    account_buf_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    account_buf_ptr: GuestPtr,
     account_buf_len: usize,
     sequence: i32,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
         return HostError::BufferTooSmall as i32;
     }
-    let mut data = get_data(account_buf_ptr, account_buf_len);
+    let mut data = match get_data(guest, account_buf_ptr, account_buf_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if ACCOUNT_ID_LEN != data.len() {
         return HostError::InvalidAccount as i32;
     }
     let sqn_data = sequence.to_be_bytes();
     data.extend_from_slice(&sqn_data);
     let keylet_hash = index_hash(LedgerNameSpace::Ticket, &data);
-    set_data(keylet_hash.len() as i32, out_buf_ptr, keylet_hash);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        keylet_hash,
+    )
 }
 
 pub fn vault_keylet(
-    _env: wasm_exec_env_t,
-    account_buf_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    account_buf_ptr: GuestPtr,
     account_buf_len: usize,
     sequence: i32,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
     if HASH256_LEN > out_buf_cap {
         return HostError::BufferTooSmall as i32;
     }
-    let mut data = get_data(account_buf_ptr, account_buf_len);
+    let mut data = match get_data(guest, account_buf_ptr, account_buf_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if ACCOUNT_ID_LEN != data.len() {
         return HostError::InvalidAccount as i32;
     }
     let sqn_data = sequence.to_be_bytes();
     data.extend_from_slice(&sqn_data);
     let keylet_hash = index_hash(LedgerNameSpace::Vault, &data);
-    set_data(keylet_hash.len() as i32, out_buf_ptr, keylet_hash);
-    HASH256_LEN as i32
+    set_data(
+        guest,
+        HASH256_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        keylet_hash,
+    )
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn get_nft(
-    env: wasm_exec_env_t,
-    owner_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    owner_ptr: GuestPtr,
     owner_len: usize,
-    nft_id_ptr: *const u8,
+    nft_id_ptr: GuestPtr,
     nft_id_len: usize,
-    out_buf_ptr: *mut u8,
+    out_buf_ptr: GuestPtr,
     out_buf_cap: usize,
 ) -> i32 {
-    let data_provider = get_dp(env);
-    let owner_id = get_data(owner_ptr, owner_len);
+    let owner_id = match get_data(guest, owner_ptr, owner_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if ACCOUNT_ID_LEN != owner_id.len() {
         return HostError::InvalidAccount as i32;
     }
-    let nft_id = get_data(nft_id_ptr, nft_id_len);
+    let nft_id = match get_data(guest, nft_id_ptr, nft_id_len) {
+        Ok(data) => data,
+        Err(e) => return e as i32,
+    };
     if HASH256_LEN != nft_id.len() {
         return HostError::InvalidParams as i32;
     }
     let dp_res = data_provider.get_nft_uri(&nft_id, &owner_id, out_buf_cap);
-    set_data(dp_res.0, out_buf_ptr, dp_res.1);
-    dp_res.0
+    set_data(guest, dp_res.0, out_buf_ptr, out_buf_cap, dp_res.1)
 }
 
-fn unpack_in_float(env: wasm_exec_env_t, in_buf: *const u8) -> Result<Number, HostError> {
-    let bytes: [u8; 8] = unsafe {
-        let inst = wasm_runtime_get_module_inst(env);
-        if !wasm_runtime_validate_native_addr(inst, in_buf as *mut ::core::ffi::c_void, 8) {
-            return Err(HostError::PointerOutOfBound);
-        }
-        match std::slice::from_raw_parts(in_buf, 8).try_into() {
-            Ok(bytes) => bytes,
-            Err(_) => return Err(HostError::InvalidFloatInput),
-        }
+/// Reads an NFTokenID, laid out as Flags (2) | TransferFee (2) | Issuer (20) | Taxon (4) |
+/// Serial (4), all big-endian, with the taxon scrambled by the serial.
+fn get_nft_id(
+    guest: &dyn Guest,
+    nft_id_ptr: GuestPtr,
+    nft_id_len: usize,
+) -> Result<[u8; 32], HostError> {
+    let nft_id = get_data(guest, nft_id_ptr, nft_id_len)?;
+    nft_id.try_into().map_err(|_| HostError::InvalidParams)
+}
+
+fn nft_serial(nft_id: &[u8; 32]) -> u32 {
+    u32::from_be_bytes(nft_id[28..32].try_into().unwrap())
+}
+
+fn nft_taxon(nft_id: &[u8; 32]) -> u32 {
+    let scrambled = u32::from_be_bytes(nft_id[24..28].try_into().unwrap());
+    // Same linear congruential generator rippled uses to cipher the taxon.
+    scrambled
+        ^ 384160001u32
+            .wrapping_mul(nft_serial(nft_id))
+            .wrapping_add(2459)
+}
+
+pub fn get_nft_issuer(
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    nft_id_ptr: GuestPtr,
+    nft_id_len: usize,
+    out_buf_ptr: GuestPtr,
+    out_buf_cap: usize,
+) -> i32 {
+    let nft_id = match get_nft_id(guest, nft_id_ptr, nft_id_len) {
+        Ok(nft_id) => nft_id,
+        Err(e) => return e as i32,
+    };
+    if ACCOUNT_ID_LEN > out_buf_cap {
+        return HostError::BufferTooSmall as i32;
+    }
+    set_data(
+        guest,
+        ACCOUNT_ID_LEN as i32,
+        out_buf_ptr,
+        out_buf_cap,
+        nft_id[4..24].to_vec(),
+    )
+}
+
+pub fn get_nft_taxon(
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    nft_id_ptr: GuestPtr,
+    nft_id_len: usize,
+    out_buf_ptr: GuestPtr,
+    out_buf_cap: usize,
+) -> i32 {
+    let nft_id = match get_nft_id(guest, nft_id_ptr, nft_id_len) {
+        Ok(nft_id) => nft_id,
+        Err(e) => return e as i32,
+    };
+    if 4 > out_buf_cap {
+        return HostError::BufferTooSmall as i32;
+    }
+    set_data(
+        guest,
+        4,
+        out_buf_ptr,
+        out_buf_cap,
+        nft_taxon(&nft_id).to_le_bytes().to_vec(),
+    )
+}
+
+pub fn get_nft_flags(
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    nft_id_ptr: GuestPtr,
+    nft_id_len: usize,
+) -> i32 {
+    match get_nft_id(guest, nft_id_ptr, nft_id_len) {
+        Ok(nft_id) => u16::from_be_bytes([nft_id[0], nft_id[1]]) as i32,
+        Err(e) => e as i32,
+    }
+}
+
+pub fn get_nft_transfer_fee(
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    nft_id_ptr: GuestPtr,
+    nft_id_len: usize,
+) -> i32 {
+    match get_nft_id(guest, nft_id_ptr, nft_id_len) {
+        Ok(nft_id) => u16::from_be_bytes([nft_id[2], nft_id[3]]) as i32,
+        Err(e) => e as i32,
+    }
+}
+
+pub fn get_nft_serial(
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    nft_id_ptr: GuestPtr,
+    nft_id_len: usize,
+    out_buf_ptr: GuestPtr,
+    out_buf_cap: usize,
+) -> i32 {
+    let nft_id = match get_nft_id(guest, nft_id_ptr, nft_id_len) {
+        Ok(nft_id) => nft_id,
+        Err(e) => return e as i32,
+    };
+    if 4 > out_buf_cap {
+        return HostError::BufferTooSmall as i32;
+    }
+    set_data(
+        guest,
+        4,
+        out_buf_ptr,
+        out_buf_cap,
+        nft_serial(&nft_id).to_le_bytes().to_vec(),
+    )
+}
+
+fn unpack_in_float(guest: &dyn Guest, in_buf: GuestPtr) -> Result<Number, HostError> {
+    let bytes: [u8; 8] = match get_data(guest, in_buf, 8)?.try_into() {
+        Ok(bytes) => bytes,
+        Err(_) => return Err(HostError::InvalidFloatInput),
     };
 
     Number::from_xrpl_iou_value(bytes).map_err(|_| HostError::InvalidFloatInput)
 }
 
-fn pack_out_float(number: Number, env: wasm_exec_env_t, out_buf: *mut u8) -> i32 {
+fn pack_out_float(number: Number, guest: &dyn Guest, out_buf: GuestPtr) -> i32 {
     // Convert Number directly to XRPL IOU format
     let bytes = match number.to_xrpl_iou_value() {
         Ok(bytes) => bytes,
         Err(_) => return HostError::InvalidFloatComputation as i32,
     };
 
-    unsafe {
-        let inst = wasm_runtime_get_module_inst(env);
-        if !wasm_runtime_validate_native_addr(inst, out_buf as *mut ::core::ffi::c_void, 8) {
-            return HostError::PointerOutOfBound as i32;
-        }
-        std::ptr::copy_nonoverlapping(bytes.as_ptr(), out_buf, 8);
-    }
-
-    8
+    set_data(guest, 8, out_buf, 8, bytes.to_vec())
 }
 
 #[allow(clippy::too_many_arguments)]
 pub fn float_add(
-    env: wasm_exec_env_t,
-    in_buff1: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    in_buff1: GuestPtr,
     in_buff1_len: usize,
-    in_buff2: *const u8,
+    in_buff2: GuestPtr,
     in_buff2_len: usize,
-    out_buff: *mut u8,
+    out_buff: GuestPtr,
     out_buff_len: usize,
     rounding_mode: i32,
 ) -> i32 {
     let _rounding_guard = set_rounding_mode_from_param(rounding_mode);
 
-    let n1 = match unpack_in_float(env, in_buff1) {
+    let n1 = match unpack_in_float(guest, in_buff1) {
         Ok(val) => val,
         Err(e) => return e as i32,
     };
-    let n2 = match unpack_in_float(env, in_buff2) {
+    let n2 = match unpack_in_float(guest, in_buff2) {
         Ok(val) => val,
         Err(e) => return e as i32,
     };
@@ -946,13 +1431,14 @@ pub fn float_add(
         Err(_) => return HostError::InvalidFloatComputation as i32,
     };
 
-    pack_out_float(result, env, out_buff)
+    pack_out_float(result, guest, out_buff)
 }
 
 pub fn float_from_int(
-    env: wasm_exec_env_t,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
     in_int: i64,
-    out_buf: *mut u8,
+    out_buf: GuestPtr,
     out_buff_len: usize,
     rounding_mode: i32,
 ) -> i32 {
@@ -963,29 +1449,24 @@ pub fn float_from_int(
         Err(_) => return HostError::InvalidFloatComputation as i32,
     };
 
-    pack_out_float(number, env, out_buf)
+    pack_out_float(number, guest, out_buf)
 }
 
 pub fn float_from_uint(
-    env: wasm_exec_env_t,
-    in_uint_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    in_uint_ptr: GuestPtr,
     in_uint_len: usize,
-    out_buff: *mut u8,
+    out_buff: GuestPtr,
     out_buff_len: usize,
     rounding_mode: i32,
 ) -> i32 {
     let _rounding_guard = set_rounding_mode_from_param(rounding_mode);
 
-    let v: u64 = unsafe {
-        let inst = wasm_runtime_get_module_inst(env);
-        if !wasm_runtime_validate_native_addr(inst, in_uint_ptr as *mut ::core::ffi::c_void, 8) {
-            return HostError::PointerOutOfBound as i32;
-        }
-        let bytes: [u8; 8] = match std::slice::from_raw_parts(in_uint_ptr, 8).try_into() {
-            Ok(bytes) => bytes,
-            Err(_) => return HostError::InvalidFloatInput as i32,
-        };
-        u64::from_le_bytes(bytes)
+    let v: u64 = match get_data(guest, in_uint_ptr, 8).map(<[u8; 8]>::try_from) {
+        Ok(Ok(bytes)) => u64::from_le_bytes(bytes),
+        Ok(Err(_)) => return HostError::InvalidFloatInput as i32,
+        Err(e) => return e as i32,
     };
 
     // Convert u64 to i64 safely, checking for overflow
@@ -1000,14 +1481,15 @@ pub fn float_from_uint(
         Err(_) => return HostError::InvalidFloatComputation as i32,
     };
 
-    pack_out_float(number, env, out_buff)
+    pack_out_float(number, guest, out_buff)
 }
 
 pub fn float_set(
-    env: wasm_exec_env_t,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
     exponent: i32,
     mantissa: i64,
-    out_buff: *mut u8,
+    out_buff: GuestPtr,
     out_buff_len: usize,
     rounding_mode: i32,
 ) -> i32 {
@@ -1018,21 +1500,22 @@ pub fn float_set(
         Err(_) => return HostError::InvalidFloatComputation as i32,
     };
 
-    pack_out_float(number, env, out_buff)
+    pack_out_float(number, guest, out_buff)
 }
 
 pub fn float_compare(
-    env: wasm_exec_env_t,
-    in_buff1: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    in_buff1: GuestPtr,
     in_buff1_len: usize,
-    in_buff2: *const u8,
+    in_buff2: GuestPtr,
     in_buff2_len: usize,
 ) -> i32 {
-    let n1 = match unpack_in_float(env, in_buff1) {
+    let n1 = match unpack_in_float(guest, in_buff1) {
         Ok(val) => val,
         Err(e) => return e as i32,
     };
-    let n2 = match unpack_in_float(env, in_buff2) {
+    let n2 = match unpack_in_float(guest, in_buff2) {
         Ok(val) => val,
         Err(e) => return e as i32,
     };
@@ -1046,22 +1529,23 @@ pub fn float_compare(
 
 #[allow(clippy::too_many_arguments)]
 pub fn float_subtract(
-    env: wasm_exec_env_t,
-    in_buff1: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    in_buff1: GuestPtr,
     in_buff1_len: usize,
-    in_buff2: *const u8,
+    in_buff2: GuestPtr,
     in_buff2_len: usize,
-    out_buff: *mut u8,
+    out_buff: GuestPtr,
     out_buff_len: usize,
     rounding_mode: i32,
 ) -> i32 {
     let _rounding_guard = set_rounding_mode_from_param(rounding_mode);
 
-    let n1 = match unpack_in_float(env, in_buff1) {
+    let n1 = match unpack_in_float(guest, in_buff1) {
         Ok(val) => val,
         Err(e) => return e as i32,
     };
-    let n2 = match unpack_in_float(env, in_buff2) {
+    let n2 = match unpack_in_float(guest, in_buff2) {
         Ok(val) => val,
         Err(e) => return e as i32,
     };
@@ -1071,27 +1555,28 @@ pub fn float_subtract(
         Err(_) => return HostError::InvalidFloatComputation as i32,
     };
 
-    pack_out_float(result, env, out_buff)
+    pack_out_float(result, guest, out_buff)
 }
 
 #[allow(clippy::too_many_arguments)]
 pub fn float_multiply(
-    env: wasm_exec_env_t,
-    in_buff1: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    in_buff1: GuestPtr,
     in_buff1_len: usize,
-    in_buff2: *const u8,
+    in_buff2: GuestPtr,
     in_buff2_len: usize,
-    out_buff: *mut u8,
+    out_buff: GuestPtr,
     out_buff_len: usize,
     rounding_mode: i32,
 ) -> i32 {
     let _rounding_guard = set_rounding_mode_from_param(rounding_mode);
 
-    let n1 = match unpack_in_float(env, in_buff1) {
+    let n1 = match unpack_in_float(guest, in_buff1) {
         Ok(val) => val,
         Err(e) => return e as i32,
     };
-    let n2 = match unpack_in_float(env, in_buff2) {
+    let n2 = match unpack_in_float(guest, in_buff2) {
         Ok(val) => val,
         Err(e) => return e as i32,
     };
@@ -1101,27 +1586,28 @@ pub fn float_multiply(
         Err(_) => return HostError::InvalidFloatComputation as i32,
     };
 
-    pack_out_float(result, env, out_buff)
+    pack_out_float(result, guest, out_buff)
 }
 
 #[allow(clippy::too_many_arguments)]
 pub fn float_divide(
-    env: wasm_exec_env_t,
-    in_buff1: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    in_buff1: GuestPtr,
     in_buff1_len: usize,
-    in_buff2: *const u8,
+    in_buff2: GuestPtr,
     in_buff2_len: usize,
-    out_buff: *mut u8,
+    out_buff: GuestPtr,
     out_buff_len: usize,
     rounding_mode: i32,
 ) -> i32 {
     let _rounding_guard = set_rounding_mode_from_param(rounding_mode);
 
-    let n1 = match unpack_in_float(env, in_buff1) {
+    let n1 = match unpack_in_float(guest, in_buff1) {
         Ok(val) => val,
         Err(e) => return e as i32,
     };
-    let n2 = match unpack_in_float(env, in_buff2) {
+    let n2 = match unpack_in_float(guest, in_buff2) {
         Ok(val) => val,
         Err(e) => return e as i32,
     };
@@ -1131,21 +1617,23 @@ pub fn float_divide(
         Err(_) => return HostError::InvalidFloatComputation as i32,
     };
 
-    pack_out_float(result, env, out_buff)
+    pack_out_float(result, guest, out_buff)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn float_pow(
-    env: wasm_exec_env_t,
-    in_buff: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    in_buff: GuestPtr,
     in_buff_len: usize,
     in_int: i32,
-    out_buff: *mut u8,
+    out_buff: GuestPtr,
     out_buff_len: usize,
     rounding_mode: i32,
 ) -> i32 {
     let _rounding_guard = set_rounding_mode_from_param(rounding_mode);
 
-    let n = match unpack_in_float(env, in_buff) {
+    let n = match unpack_in_float(guest, in_buff) {
         Ok(val) => val,
         Err(e) => return e as i32,
     };
@@ -1164,21 +1652,23 @@ pub fn float_pow(
         Err(_) => return HostError::InvalidFloatComputation as i32,
     };
 
-    pack_out_float(result, env, out_buff)
+    pack_out_float(result, guest, out_buff)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn float_root(
-    env: wasm_exec_env_t,
-    in_buff: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    in_buff: GuestPtr,
     in_buff_len: usize,
     in_int: i32,
-    out_buff: *mut u8,
+    out_buff: GuestPtr,
     out_buff_len: usize,
     rounding_mode: i32,
 ) -> i32 {
     let _rounding_guard = set_rounding_mode_from_param(rounding_mode);
 
-    let n = match unpack_in_float(env, in_buff) {
+    let n = match unpack_in_float(guest, in_buff) {
         Ok(val) => val,
         Err(e) => return e as i32,
     };
@@ -1192,20 +1682,21 @@ pub fn float_root(
         Err(_) => return HostError::InvalidFloatComputation as i32,
     };
 
-    pack_out_float(result, env, out_buff)
+    pack_out_float(result, guest, out_buff)
 }
 
 pub fn float_log(
-    env: wasm_exec_env_t,
-    in_buff: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    in_buff: GuestPtr,
     in_buff_len: usize,
-    out_buff: *mut u8,
+    out_buff: GuestPtr,
     out_buff_len: usize,
     rounding_mode: i32,
 ) -> i32 {
     let _rounding_guard = set_rounding_mode_from_param(rounding_mode);
 
-    let n = match unpack_in_float(env, in_buff) {
+    let n = match unpack_in_float(guest, in_buff) {
         Ok(val) => val,
         Err(e) => return e as i32,
     };
@@ -1215,36 +1706,43 @@ pub fn float_log(
         Err(_) => return HostError::InvalidFloatComputation as i32,
     };
 
-    pack_out_float(result, env, out_buff)
+    pack_out_float(result, guest, out_buff)
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
-fn read_utf8_from_wasm(msg_read_ptr: *const u8, msg_read_len: usize) -> Option<String> {
-    String::from_utf8(get_data(msg_read_ptr, msg_read_len)).ok()
+fn read_utf8_from_wasm(
+    guest: &dyn Guest,
+    msg_read_ptr: GuestPtr,
+    msg_read_len: usize,
+) -> Result<String, HostError> {
+    String::from_utf8(get_data(guest, msg_read_ptr, msg_read_len)?)
+        .map_err(|_| HostError::InvalidDecoding)
 }
 fn read_hex_from_wasm(
-    data_read_ptr: *const u8,
+    guest: &dyn Guest,
+    data_read_ptr: GuestPtr,
     data_read_len: usize,
     data_as_hex: bool,
-) -> Option<String> {
+) -> Result<String, HostError> {
     if data_as_hex {
         // Read the data from memory.
-        let bytes_vec: Vec<u8> = get_data(data_read_ptr, data_read_len);
+        let bytes_vec = get_data(guest, data_read_ptr, data_read_len)?;
         let mut final_hex_string = "0x".to_owned();
         let hex_data = hex::encode_upper(&bytes_vec);
         final_hex_string.push_str(hex_data.as_str());
-        Some(final_hex_string)
+        Ok(final_hex_string)
     } else {
-        read_utf8_from_wasm(data_read_ptr, data_read_len)
+        read_utf8_from_wasm(guest, data_read_ptr, data_read_len)
     }
 }
 
 pub fn trace(
-    _env: wasm_exec_env_t,
-    msg_read_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    msg_read_ptr: GuestPtr,
     msg_read_len: usize,
-    data_read_ptr: *const u8,
+    data_read_ptr: GuestPtr,
     data_read_len: usize,
     data_as_hex: i32,
 ) -> i32 {
@@ -1269,29 +1767,33 @@ pub fn trace(
         msg_read_ptr, msg_read_len, data_read_ptr, data_read_len
     );
 
-    let Some(message) = read_utf8_from_wasm(msg_read_ptr, msg_read_len) else {
-        return HostError::InvalidDecoding as i32;
+    let message = match read_utf8_from_wasm(guest, msg_read_ptr, msg_read_len) {
+        Ok(message) => message,
+        Err(e) => return e as i32,
     };
 
-    let Some(data_string) = read_hex_from_wasm(data_read_ptr, data_read_len, data_as_hex) else {
-        return HostError::InvalidDecoding as i32;
+    let data_string = match read_hex_from_wasm(guest, data_read_ptr, data_read_len, data_as_hex) {
+        Ok(data_string) => data_string,
+        Err(e) => return e as i32,
     };
 
+    let run_log = &mut data_provider.run_log;
     if data_read_len > 0 {
-        println!(
-            "WASM TRACE: {message} ({data_string} | {} data bytes)",
-            data_read_len
+        run_log.trace(
+            "trace",
+            format!("{message} ({data_string} | {data_read_len} data bytes)"),
         );
     } else {
-        println!("WASM TRACE: {message}");
+        run_log.trace("trace", message);
     }
 
     (data_read_len + msg_read_len + 1) as i32
 }
 
 pub fn trace_num(
-    _env: wasm_exec_env_t,
-    msg_read_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    msg_read_ptr: GuestPtr,
     msg_read_len: usize,
     number: i64,
 ) -> i32 {
@@ -1306,34 +1808,36 @@ pub fn trace_num(
         "trace() params: msg_read_ptr={:?} msg_read_len={} number={} ",
         msg_read_ptr, msg_read_len, number
     );
-    let Some(message) = read_utf8_from_wasm(msg_read_ptr, msg_read_len) else {
-        return HostError::InvalidDecoding as i32;
+    let message = match read_utf8_from_wasm(guest, msg_read_ptr, msg_read_len) {
+        Ok(message) => message,
+        Err(e) => return e as i32,
     };
 
+    let run_log = &mut data_provider.run_log;
     if (number < 0) {
         let error_code_str = error_code_to_string(number);
-        println!("WASM TRACE[ERROR]: {message} {error_code_str}");
+        run_log.trace_error("trace_num", format!("{message} {error_code_str}"));
     } else {
-        println!("WASM TRACE: {message} {number}");
+        run_log.trace("trace_num", format!("{message} {number}"));
     }
     0
 }
 
 pub fn trace_opaque_float(
-    _env: wasm_exec_env_t,
-    msg_read_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    msg_read_ptr: GuestPtr,
     msg_read_len: usize,
-    op_float: *const u8,
+    op_float: GuestPtr,
     float_len: usize,
 ) -> i32 {
     if msg_read_len > MAX_WASM_PARAM_LENGTH || float_len > MAX_WASM_PARAM_LENGTH {
         return HostError::DataFieldTooLarge as i32;
     }
-    let bytes: [u8; 8] = unsafe {
-        match std::slice::from_raw_parts(op_float, 8).try_into() {
-            Ok(bytes) => bytes,
-            Err(_) => return HostError::InvalidFloatInput as i32,
-        }
+    let bytes: [u8; 8] = match get_data(guest, op_float, 8).map(<[u8; 8]>::try_from) {
+        Ok(Ok(bytes)) => bytes,
+        Ok(Err(_)) => return HostError::InvalidFloatInput as i32,
+        Err(e) => return e as i32,
     };
 
     let f = match _deserialize_issued_currency_amount(bytes) {
@@ -1345,19 +1849,23 @@ pub fn trace_opaque_float(
         "trace() params: msg_read_ptr={:?} msg_read_len={} float={} ",
         msg_read_ptr, msg_read_len, f
     );
-    let Some(message) = read_utf8_from_wasm(msg_read_ptr, msg_read_len) else {
-        return HostError::InvalidDecoding as i32;
+    let message = match read_utf8_from_wasm(guest, msg_read_ptr, msg_read_len) {
+        Ok(message) => message,
+        Err(e) => return e as i32,
     };
 
-    println!("WASM TRACE: {message} {f}");
+    data_provider
+        .run_log
+        .trace("trace_opaque_float", format!("{message} {f}"));
     0
 }
 
 pub fn trace_account(
-    _env: wasm_exec_env_t,
-    msg_read_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    msg_read_ptr: GuestPtr,
     msg_read_len: usize,
-    account_ptr: *const u8,
+    account_ptr: GuestPtr,
     account_len: usize,
 ) -> i32 {
     // Don't need to check number of inputs or types since these will manifest at runtime and
@@ -1375,38 +1883,41 @@ pub fn trace_account(
         msg_read_ptr, msg_read_len, account_ptr, account_len
     );
 
-    let Some(message) = read_utf8_from_wasm(msg_read_ptr, msg_read_len) else {
-        return HostError::InvalidDecoding as i32;
+    let message = match read_utf8_from_wasm(guest, msg_read_ptr, msg_read_len) {
+        Ok(message) => message,
+        Err(e) => return e as i32,
     };
 
-    let bytes: [u8; ACCOUNT_ID_LEN] = unsafe {
-        match std::slice::from_raw_parts(account_ptr, account_len).try_into() {
-            Ok(arr) => arr,
-            Err(_) => return HostError::InvalidAccount as i32,
-        }
-    };
+    let bytes: [u8; ACCOUNT_ID_LEN] =
+        match get_data(guest, account_ptr, account_len).map(<[u8; ACCOUNT_ID_LEN]>::try_from) {
+            Ok(Ok(arr)) => arr,
+            Ok(Err(_)) => return HostError::InvalidAccount as i32,
+            Err(e) => return e as i32,
+        };
     let account_id = match encode_base58(&bytes, &[0x0], Some(20)) {
         Ok(val) => val,
         Err(_) => return HostError::InvalidAccount as i32,
     };
 
+    let run_log = &mut data_provider.run_log;
     if account_len > 0 {
-        println!(
-            "WASM TRACE: {message} ({account_id} | {} data bytes)",
-            account_len
+        run_log.trace(
+            "trace_account",
+            format!("{message} ({account_id} | {account_len} data bytes)"),
         );
     } else {
-        println!("WASM TRACE: {message}");
+        run_log.trace("trace_account", message);
     }
 
     (account_id.len() + msg_read_len + 1) as i32
 }
 
 pub fn trace_amount(
-    _env: wasm_exec_env_t,
-    msg_read_ptr: *const u8,
+    data_provider: &mut DataProvider,
+    guest: &dyn Guest,
+    msg_read_ptr: GuestPtr,
     msg_read_len: usize,
-    amount_ptr: *const u8,
+    amount_ptr: GuestPtr,
     amount_len: usize,
 ) -> i32 {
     // Don't need to check number of inputs or types since these will manifest at runtime and
@@ -1427,23 +1938,24 @@ pub fn trace_amount(
         msg_read_ptr, msg_read_len, amount_ptr, amount_len
     );
 
-    let Some(message) = read_utf8_from_wasm(msg_read_ptr, msg_read_len) else {
-        return HostError::InvalidDecoding as i32;
+    let message = match read_utf8_from_wasm(guest, msg_read_ptr, msg_read_len) {
+        Ok(message) => message,
+        Err(e) => return e as i32,
     };
 
-    let amount_bytes: [u8; TOKEN_AMOUNT_SIZE] = unsafe {
-        match std::slice::from_raw_parts(amount_ptr, amount_len).try_into() {
-            Ok(arr) => arr,
-            Err(_) => return HostError::InvalidParams as i32,
-        }
-    };
+    let amount_bytes: [u8; TOKEN_AMOUNT_SIZE] =
+        match get_data(guest, amount_ptr, amount_len).map(<[u8; TOKEN_AMOUNT_SIZE]>::try_from) {
+            Ok(Ok(arr)) => arr,
+            Ok(Err(_)) => return HostError::InvalidParams as i32,
+            Err(e) => return e as i32,
+        };
 
     // Parse the STAmount format to determine token type and display appropriate info
     let amount_info = parse_stamount_for_display(&amount_bytes);
 
-    println!(
-        "WASM TRACE: {message} ({amount_info} | {} amount bytes)",
-        amount_len
+    data_provider.run_log.trace(
+        "trace_amount",
+        format!("{message} ({amount_info} | {amount_len} amount bytes)"),
     );
 
     (amount_info.len() + msg_read_len + 1) as i32
@@ -1536,6 +2048,8 @@ fn format_currency_code(currency_bytes: &[u8; 20]) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock_data::MockData;
+    use crate::test_support::Memory;
     use xrpl_wasm_std::core::types::{
         account_id::AccountID,
         amount::{
@@ -1544,6 +2058,193 @@ mod tests {
         },
     };
 
+    #[test]
+    fn test_verify_signature_ed25519() {
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]);
+        let mut pubkey = vec![0xED];
+        pubkey.extend_from_slice(signing_key.verifying_key().as_bytes());
+        let signature = ed25519_dalek::Signer::sign(&signing_key, b"hello").to_bytes();
+
+        assert!(verify_signature(b"hello", &signature, &pubkey).unwrap());
+        assert!(!verify_signature(b"goodbye", &signature, &pubkey).unwrap());
+    }
+
+    #[test]
+    fn test_verify_signature_secp256k1() {
+        let secp = secp256k1::Secp256k1::new();
+        let secret_key = secp256k1::SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let pubkey = secp256k1::PublicKey::from_secret_key(&secp, &secret_key).serialize();
+        let digest: [u8; 32] = sha512_half(b"hello").try_into().unwrap();
+        let signature = secp
+            .sign_ecdsa(&secp256k1::Message::from_digest(digest), &secret_key)
+            .serialize_der();
+
+        assert!(verify_signature(b"hello", &signature, &pubkey).unwrap());
+        assert!(!verify_signature(b"goodbye", &signature, &pubkey).unwrap());
+        assert!(verify_signature(b"hello", &signature, b"test pubkey").is_err());
+    }
+
+    fn read(memory: &Memory, ptr: u32, len: usize) -> Result<Vec<u8>, i32> {
+        read_buffer(memory, GuestPtr(ptr), len).map_err(|e| e as i32)
+    }
+
+    fn write(memory: &Memory, ptr: u32, cap: usize, bytes: &[u8]) -> Result<(), i32> {
+        write_buffer(memory, GuestPtr(ptr), cap, bytes).map_err(|e| e as i32)
+    }
+
+    #[test]
+    fn test_read_buffer_rejects_bad_pointers() {
+        let memory = Memory::new(64);
+        assert_eq!(read(&memory, 60, 4), Ok(vec![60, 61, 62, 63]));
+
+        // Past the end, across it, and wrapping around the address space
+        assert_eq!(read(&memory, 64, 1), Err(-13));
+        assert_eq!(read(&memory, 60, 5), Err(-13));
+        assert_eq!(read(&memory, u32::MAX, 2), Err(-13));
+        assert_eq!(read(&memory, 8, usize::MAX), Err(-13));
+
+        // An empty buffer is never out of bounds
+        assert_eq!(read(&memory, u32::MAX, 0), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_write_buffer_rejects_bad_pointers() {
+        let memory = Memory::new(64);
+        assert_eq!(write(&memory, 60, 4, &[9; 4]), Ok(()));
+        assert_eq!(memory.read(60, 4), Some(vec![9; 4]));
+
+        // The whole buffer must be in memory, even where what is written would fit
+        assert_eq!(write(&memory, 56, 32, &[7; 4]), Err(-13));
+        assert_eq!(memory.read(56, 4), Some(vec![56, 57, 58, 59]));
+        assert_eq!(write(&memory, u32::MAX, 8, &[7; 4]), Err(-13));
+
+        assert_eq!(write(&memory, 0, 2, &[7; 4]), Err(-3));
+        assert_eq!(write(&memory, u32::MAX, 8, &[]), Ok(()));
+    }
+
+    const SEQUENCE: i32 = 131076;
+
+    fn data_provider(tx: &str, ledger_object: &str) -> DataProvider {
+        DataProvider::new(MockData::new(tx, ledger_object, "{}", "[]", "[]").unwrap())
+    }
+
+    #[test]
+    fn test_update_data_rejects_bad_pointers() {
+        let memory = Memory::new(64);
+        let mut dp = data_provider("{}", r#"{ "Data": "01" }"#);
+
+        assert_eq!(update_data(&mut dp, &memory, GuestPtr(60), 8), -13);
+        assert_eq!(update_data(&mut dp, &memory, GuestPtr(u32::MAX), 2), -13);
+        assert_eq!(dp.get_current_ledger_obj_data(), Some(vec![1]));
+
+        assert_eq!(update_data(&mut dp, &memory, GuestPtr(60), 4), 0);
+        assert_eq!(dp.get_current_ledger_obj_data(), Some(vec![60, 61, 62, 63]));
+    }
+
+    #[test]
+    fn test_get_tx_field_rejects_bad_pointers() {
+        let memory = Memory::new(64);
+        let mut dp = data_provider(r#"{ "Sequence": 7 }"#, "{}");
+
+        assert_eq!(
+            get_tx_field(&mut dp, &memory, SEQUENCE, GuestPtr(62), 4),
+            -13
+        );
+        assert_eq!(memory.read(62, 2), Some(vec![62, 63]));
+        assert_eq!(
+            get_tx_field(&mut dp, &memory, SEQUENCE, GuestPtr(u32::MAX), 4),
+            -13
+        );
+
+        assert_eq!(get_tx_field(&mut dp, &memory, SEQUENCE, GuestPtr(60), 4), 4);
+        assert_eq!(memory.read(60, 4), Some(vec![7, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_keylets_reject_bad_pointers() {
+        let memory = Memory::new(64);
+        let mut dp = data_provider("{}", "{}");
+
+        // The account read from past the end, or the keylet written across it
+        assert_eq!(
+            account_keylet(&mut dp, &memory, GuestPtr(50), 20, GuestPtr(0), 32),
+            -13
+        );
+        assert_eq!(
+            account_keylet(&mut dp, &memory, GuestPtr(0), 20, GuestPtr(40), 32),
+            -13
+        );
+        assert_eq!(
+            escrow_keylet(&mut dp, &memory, GuestPtr(50), 20, 1, GuestPtr(0), 32),
+            -13
+        );
+        assert_eq!(
+            escrow_keylet(&mut dp, &memory, GuestPtr(0), 20, 1, GuestPtr(u32::MAX), 32),
+            -13
+        );
+        assert_eq!(memory.read(20, 4), Some(vec![20, 21, 22, 23]));
+
+        assert_eq!(
+            account_keylet(&mut dp, &memory, GuestPtr(0), 20, GuestPtr(20), 32),
+            32
+        );
+        assert_eq!(
+            memory.read(20, 32),
+            Some(index_hash(
+                LedgerNameSpace::Account,
+                &(0..20).collect::<Vec<u8>>()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_credential_keylet_checks_lengths() {
+        let memory = Memory::new(200);
+        let mut dp = data_provider("{}", "{}");
+        let keylet = |dp: &mut DataProvider, subject_len, issuer_len, type_len| {
+            credential_keylet(
+                dp,
+                &memory,
+                GuestPtr(0),
+                subject_len,
+                GuestPtr(20),
+                issuer_len,
+                GuestPtr(40),
+                type_len,
+                GuestPtr(128),
+                32,
+            )
+        };
+
+        assert_eq!(keylet(&mut dp, 19, 20, 8), HostError::InvalidAccount as i32);
+        assert_eq!(keylet(&mut dp, 21, 20, 8), HostError::InvalidAccount as i32);
+        assert_eq!(keylet(&mut dp, 20, 19, 8), HostError::InvalidAccount as i32);
+        assert_eq!(keylet(&mut dp, 20, 20, 0), HostError::InvalidParams as i32);
+        assert_eq!(keylet(&mut dp, 20, 20, 65), HostError::InvalidParams as i32);
+        assert_eq!(memory.read(128, 4), Some(vec![128, 129, 130, 131]));
+
+        assert_eq!(keylet(&mut dp, 20, 20, 64), 32);
+        assert_eq!(
+            memory.read(128, 32),
+            Some(index_hash(
+                LedgerNameSpace::Credential,
+                &(0..104).collect::<Vec<u8>>()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_nft_id_taxon_and_serial() {
+        // NFTokenID from the XRPL docs: flags 11, transfer fee 1337, taxon 1337, serial 12
+        let nft_id: [u8; 32] =
+            hex::decode("000B0539C35B55AA096BA6D87A6E6C965A6534150DC56E5E12C5D09E0000000C")
+                .unwrap()
+                .try_into()
+                .unwrap();
+        assert_eq!(nft_serial(&nft_id), 12);
+        assert_eq!(nft_taxon(&nft_id), 1337);
+    }
+
     #[test]
     fn test_parse_stamount_for_display_xrp() {
         // Test XRP amount using TokenAmount's to_stamount_bytes
@@ -0,0 +1,334 @@
+use crate::decoding::{Decodable, decode};
+use crate::hashing::{Hash256, LedgerNameSpace, index_hash};
+use serde_json::Value;
+
+const CURRENCY_LEN: usize = 20;
+
+/// Reads the identifying fields of one ledger object.
+struct Fields<'a> {
+    object: &'a Value,
+}
+
+impl Fields<'_> {
+    fn get(&self, name: &str) -> Result<&Value, String> {
+        self.object
+            .get(name)
+            .ok_or_else(|| format!("missing field '{}'", name))
+    }
+
+    fn account(&self, name: &str) -> Result<Vec<u8>, String> {
+        self.get(name)?
+            .as_str()
+            .and_then(|s| decode(s, Decodable::ACCOUNT))
+            .ok_or_else(|| format!("'{}' is not a valid account address", name))
+    }
+
+    fn u32(&self, name: &str) -> Result<[u8; 4], String> {
+        let value = self.get(name)?;
+        value
+            .as_u64()
+            .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+            .and_then(|n| u32::try_from(n).ok())
+            .map(u32::to_be_bytes)
+            .ok_or_else(|| format!("'{}' is not a UInt32", name))
+    }
+
+    fn hex(&self, name: &str, len: Option<usize>) -> Result<Vec<u8>, String> {
+        self.get(name)?
+            .as_str()
+            .and_then(|s| hex::decode(s).ok())
+            .filter(|bytes| len.is_none_or(|len| bytes.len() == len))
+            .ok_or_else(|| format!("'{}' is not valid hex", name))
+    }
+
+    /// The currency of an amount field, in its 160-bit form.
+    fn currency(&self, name: &str) -> Result<Vec<u8>, String> {
+        let invalid = || format!("'{}' does not have a valid issued currency", name);
+        let code = self.get(name)?["currency"].as_str().ok_or_else(invalid)?;
+        if code.len() == 3 && code != "XRP" && code.is_ascii() {
+            let mut currency = vec![0u8; CURRENCY_LEN];
+            currency[12..15].copy_from_slice(code.as_bytes());
+            return Ok(currency);
+        }
+        hex::decode(code)
+            .ok()
+            .filter(|bytes| bytes.len() == CURRENCY_LEN)
+            .ok_or_else(invalid)
+    }
+
+    fn limit_issuer(&self, name: &str) -> Result<Vec<u8>, String> {
+        self.get(name)?["issuer"]
+            .as_str()
+            .and_then(|s| decode(s, Decodable::ACCOUNT))
+            .ok_or_else(|| format!("'{}' does not have a valid issuer", name))
+    }
+}
+
+fn concat(parts: &[&[u8]]) -> Vec<u8> {
+    parts.concat()
+}
+
+/// Derives a ledger object's ID from its `LedgerEntryType` and identifying fields, the same way
+/// rippled and the `*_keylet` host functions compute it.
+///
+/// Returns `Ok(None)` for objects whose ID cannot be derived from their own contents, such as
+/// directory nodes, NFT pages and AMMs, escrows, offers and payment channels without the
+/// `Sequence` of the transaction that created them, or signer lists without an `Owner`.
+pub fn derive_keylet(object: &Value) -> Result<Option<Hash256>, String> {
+    let Some(entry_type) = object.get("LedgerEntryType").and_then(Value::as_str) else {
+        return Err("missing field 'LedgerEntryType'".to_string());
+    };
+    let f = Fields { object };
+    let has = |name: &str| object.get(name).is_some();
+
+    let keylet = match entry_type {
+        "AccountRoot" => index_hash(LedgerNameSpace::Account, &f.account("Account")?),
+        "Amendments" => index_hash(LedgerNameSpace::Amendments, &[]),
+        "FeeSettings" => index_hash(LedgerNameSpace::FeeSettings, &[]),
+        "NegativeUNL" => index_hash(LedgerNameSpace::NegativeUnl, &[]),
+        "Check" => index_hash(
+            LedgerNameSpace::Check,
+            &concat(&[&f.account("Account")?, &f.u32("Sequence")?]),
+        ),
+        "Credential" => index_hash(
+            LedgerNameSpace::Credential,
+            &concat(&[
+                &f.account("Subject")?,
+                &f.account("Issuer")?,
+                &f.hex("CredentialType", None)?,
+            ]),
+        ),
+        "Delegate" => index_hash(
+            LedgerNameSpace::Delegate,
+            &concat(&[&f.account("Account")?, &f.account("Authorize")?]),
+        ),
+        // Credential-based preauthorizations are keyed by a sorted credential set
+        "DepositPreauth" if has("Authorize") => index_hash(
+            LedgerNameSpace::DepositPreauth,
+            &concat(&[&f.account("Account")?, &f.account("Authorize")?]),
+        ),
+        "DID" => index_hash(LedgerNameSpace::Did, &f.account("Account")?),
+        "Escrow" if has("Sequence") => index_hash(
+            LedgerNameSpace::Escrow,
+            &concat(&[&f.account("Account")?, &f.u32("Sequence")?]),
+        ),
+        "MPTokenIssuance" => index_hash(
+            LedgerNameSpace::MptokenIssuance,
+            &concat(&[&f.u32("Sequence")?, &f.account("Issuer")?]),
+        ),
+        "MPToken" => {
+            let issuance = index_hash(
+                LedgerNameSpace::MptokenIssuance,
+                &f.hex("MPTokenIssuanceID", Some(24))?,
+            );
+            index_hash(
+                LedgerNameSpace::Mptoken,
+                &concat(&[&issuance, &f.account("Account")?]),
+            )
+        }
+        "NFTokenOffer" if has("Sequence") => index_hash(
+            LedgerNameSpace::NftokenOffer,
+            &concat(&[&f.account("Owner")?, &f.u32("Sequence")?]),
+        ),
+        "Offer" => index_hash(
+            LedgerNameSpace::Offer,
+            &concat(&[&f.account("Account")?, &f.u32("Sequence")?]),
+        ),
+        "Oracle" => index_hash(
+            LedgerNameSpace::Oracle,
+            &concat(&[&f.account("Owner")?, &f.u32("OracleDocumentID")?]),
+        ),
+        "PayChannel" if has("Sequence") => index_hash(
+            LedgerNameSpace::XrpPaymentChannel,
+            &concat(&[
+                &f.account("Account")?,
+                &f.account("Destination")?,
+                &f.u32("Sequence")?,
+            ]),
+        ),
+        "PermissionedDomain" => index_hash(
+            LedgerNameSpace::PermissionedDomain,
+            &concat(&[&f.account("Owner")?, &f.u32("Sequence")?]),
+        ),
+        "RippleState" => index_hash(
+            LedgerNameSpace::TrustLine,
+            &concat(&[
+                &f.limit_issuer("LowLimit")?,
+                &f.limit_issuer("HighLimit")?,
+                &f.currency("Balance")?,
+            ]),
+        ),
+        // Only the default signer list (ID 0) exists. Its owner is recorded in `Owner` only by
+        // newer rippled versions.
+        "SignerList" if has("Owner") => index_hash(
+            LedgerNameSpace::SignerList,
+            &concat(&[&f.account("Owner")?, &0u32.to_be_bytes()]),
+        ),
+        "Ticket" => index_hash(
+            LedgerNameSpace::Ticket,
+            &concat(&[&f.account("Account")?, &f.u32("TicketSequence")?]),
+        ),
+        "Vault" => index_hash(
+            LedgerNameSpace::Vault,
+            &concat(&[&f.account("Owner")?, &f.u32("Sequence")?]),
+        ),
+        _ => return Ok(None),
+    };
+    Ok(Some(keylet))
+}
+
+/// The ID a `ledger.json` object is stored under.
+///
+/// Objects given under an explicit `key` must agree with the ID derived from their fields, when
+/// one can be derived. Objects without a key use the derived ID, falling back to their `index`
+/// when it cannot be derived.
+pub fn ledger_entry_key(key: Option<&str>, object: &Value) -> Result<Hash256, String> {
+    let entry_type = object["LedgerEntryType"].as_str().unwrap_or("object");
+    let Some(key) = key else {
+        let derived = derive_keylet(object);
+        if let Ok(Some(keylet)) = derived {
+            return Ok(keylet);
+        }
+        if let Some(index) = object["index"]
+            .as_str()
+            .and_then(|index| decode(index, Decodable::UINT256))
+        {
+            return Ok(index);
+        }
+        return Err(derived.err().unwrap_or_else(|| {
+            format!(
+                "the ID of a {} cannot be derived from its fields; give its key or index",
+                entry_type
+            )
+        }));
+    };
+
+    let keylet = decode(key, Decodable::UINT256)
+        .ok_or_else(|| "not a 256-bit hex ledger object ID".to_string())?;
+    // Objects too incomplete to derive an ID from can still be looked up by key
+    if let Ok(Some(derived)) = derive_keylet(object)
+        && derived != keylet
+    {
+        return Err(format!(
+            "does not match the ID {} derived from the {}'s fields",
+            hex::encode_upper(derived),
+            entry_type
+        ));
+    }
+    Ok(keylet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn derived(object: Value) -> String {
+        hex::encode_upper(derive_keylet(&object).unwrap().unwrap())
+    }
+
+    #[test]
+    fn test_derive_keylet_matches_ledger_ids() {
+        // IDs as stored on the ledger for these objects
+        assert_eq!(
+            derived(json!({
+                "LedgerEntryType": "AccountRoot",
+                "Account": "rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh",
+            })),
+            "2B6AC232AA4C4BE41BF49D2459FA4A0347E1B543A4C92FCEE0821C0201E2E9A8"
+        );
+        assert_eq!(
+            derived(json!({ "LedgerEntryType": "Amendments" })),
+            "7DB0788C020F02780A673DC74757F23823FA3014C1866E72CC4CD8B226CD6EF4"
+        );
+        assert_eq!(
+            derived(json!({ "LedgerEntryType": "FeeSettings" })),
+            "4BC50C9B0D8515D3EAAE1E74B29A95804346C491EE1A95BF25E4AAB854A6A651"
+        );
+    }
+
+    #[test]
+    fn test_derive_keylet_needs_identifying_fields() {
+        let escrow =
+            json!({ "LedgerEntryType": "Escrow", "Account": "rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh" });
+        assert_eq!(derive_keylet(&escrow), Ok(None));
+        assert_eq!(
+            derive_keylet(
+                &json!({ "LedgerEntryType": "Oracle", "Owner": "rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh" })
+            ),
+            Err("missing field 'OracleDocumentID'".to_string())
+        );
+        assert_eq!(
+            derive_keylet(&json!({ "LedgerEntryType": "DirectoryNode" })),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_ledger_entry_key() {
+        const ID: &str = "2B6AC232AA4C4BE41BF49D2459FA4A0347E1B543A4C92FCEE0821C0201E2E9A8";
+        let account = json!({
+            "LedgerEntryType": "AccountRoot",
+            "Account": "rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh",
+        });
+        let id = hex::decode(ID).unwrap();
+        assert_eq!(ledger_entry_key(None, &account), Ok(id.clone()));
+        assert_eq!(ledger_entry_key(Some(ID), &account), Ok(id.clone()));
+
+        let other = "0000000000000000000000000000000000000000000000000000000000000001";
+        assert_eq!(
+            ledger_entry_key(Some(other), &account),
+            Err(format!(
+                "does not match the ID {} derived from the AccountRoot's fields",
+                ID
+            ))
+        );
+
+        // Fields missing for derivation fall back to the index
+        let oracle = json!({ "LedgerEntryType": "Oracle", "index": ID });
+        assert_eq!(ledger_entry_key(None, &oracle), Ok(id.clone()));
+        assert_eq!(
+            ledger_entry_key(None, &json!({ "LedgerEntryType": "Oracle" })),
+            Err("missing field 'Owner'".to_string())
+        );
+
+        let directory = json!({ "LedgerEntryType": "DirectoryNode" });
+        assert!(ledger_entry_key(None, &directory).is_err());
+        assert_eq!(
+            ledger_entry_key(
+                None,
+                &json!({ "LedgerEntryType": "DirectoryNode", "index": ID })
+            ),
+            Ok(id)
+        );
+    }
+
+    #[test]
+    fn test_signer_list_key() {
+        const ID: &str = "A9C28A28B85CD533217F5C0A0C7767666B093FA58A0F2D80026FCC4CD932DDC7";
+        // A signer list as returned by rippled's ledger_entry, without an Owner field
+        let mut signer_list = json!({
+            "Flags": 0,
+            "LedgerEntryType": "SignerList",
+            "OwnerNode": "0000000000000000",
+            "PreviousTxnID": "5904C0DC72C58A83AEFED2FFC5386356AA83FCA6A88C89D00646E51E687CDBE4",
+            "PreviousTxnLgrSeq": 16061435,
+            "SignerEntries": [
+                { "SignerEntry": { "Account": "rsA2LpzuawewSBQXkiju3YQTMzW13pAAdW", "SignerWeight": 2 } },
+                { "SignerEntry": { "Account": "raKEEVSGnKSD9Zyvxu4z6Pqpm4ABH8FS6n", "SignerWeight": 1 } },
+                { "SignerEntry": { "Account": "rUpy3eEg8rqjqfUoLeBnZkscbKbFsKXC3v", "SignerWeight": 1 } }
+            ],
+            "SignerListID": 0,
+            "SignerQuorum": 3,
+            "index": ID
+        });
+        let id = hex::decode(ID).unwrap();
+        assert_eq!(derive_keylet(&signer_list), Ok(None));
+        assert_eq!(ledger_entry_key(None, &signer_list), Ok(id.clone()));
+        assert_eq!(ledger_entry_key(Some(ID), &signer_list), Ok(id.clone()));
+
+        signer_list["Owner"] = json!("rf1BiGeXwwQoi8Z2ueFYTEXSwuJYfV2Jpn");
+        assert_eq!(derive_keylet(&signer_list), Ok(Some(id.clone())));
+        assert_eq!(ledger_entry_key(Some(ID), &signer_list), Ok(id));
+    }
+}
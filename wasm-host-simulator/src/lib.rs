@@ -0,0 +1,26 @@
+//! Simulates the host environment rippled provides to WASM extensions, so their functions can be
+//! run against fixture ledgers. [`Simulator`] is the entry point.
+
+pub mod data_provider;
+pub mod debugger;
+pub mod decoding;
+pub mod determinism;
+pub mod expectations;
+pub mod fixture_check;
+pub mod fuzz;
+mod hashing;
+mod host_functions_wamr;
+mod keylets;
+pub mod limits;
+pub mod mock_data;
+pub mod run_log;
+pub mod scenario;
+mod serialization;
+pub mod simulator;
+pub mod sweep;
+pub mod tape;
+#[cfg(test)]
+mod test_support;
+pub mod vm_wamr;
+
+pub use simulator::{Fixture, Simulator, TestOutcome, TestReport};
@@ -0,0 +1,606 @@
+//! Limits a network's VM puts on the WASM modules it runs, checked before a module is built into
+//! a project or run, so that a module the network would reject is rejected here first.
+
+use crate::vm_wamr::{self, HOST_FUNCTIONS};
+use std::fmt;
+use wasmparser::{FuncType, FunctionBody, Operator, Parser, Payload, TypeRef, ValType, Validator};
+
+/// The module host functions are imported from.
+pub const HOST_MODULE: &str = "host_lib";
+
+/// What a VM allows of a module, and the stack it runs it with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The name the profile is selected by.
+    pub name: &'static str,
+    /// Pages a module's memory may start with or declare as its maximum.
+    pub max_memory_pages: u64,
+    /// Bytes of stack each instance is created with.
+    pub stack_size: u32,
+    /// Elements a module's tables may start with or declare as their maximum.
+    pub max_table_elements: u64,
+    /// Functions a module may define, not counting imports.
+    pub max_functions: u32,
+    /// Exports of any kind a module may have.
+    pub max_exports: u32,
+    /// Whether a module may import anything, rather than only the host functions.
+    pub any_imports: bool,
+    /// Whether a module may use floating-point types and instructions.
+    pub floats: bool,
+    /// Whether a module may use SIMD types and instructions, whose relaxed forms give results
+    /// that differ between machines.
+    pub simd: bool,
+    /// Whether a module may use shared memory and atomics.
+    pub threads: bool,
+}
+
+/// The VM of rippled's devnet5 release, commit 58741d2 of the `ripple/smart-escrow` branch (see
+/// `DEVNET.md`), with craft's own guard rails on a module's size. Named for craft rather than
+/// rippled, since only the import and feature rules are rippled's. Where each limit comes from:
+///
+/// - Imports: rippled links modules against the host functions it registers under `host_lib`,
+///   with the signatures in [`HOST_FUNCTIONS`], and nothing else.
+/// - Floats: the release does float arithmetic through host functions on rippled's `Number`,
+///   and native floating-point and SIMD results (NaN bits, relaxed instructions) can differ
+///   between validators. Each call runs on one thread, so shared memory and atomics are not
+///   allowed either.
+/// - Stack: the 128 KiB the simulator has always created instances with. Use
+///   [`Limits::with_stack_size`] to run with another.
+/// - Memory, tables, functions and exports: craft's guard rails, not rippled's limits, and
+///   reported as such. They are set well above what the example projects use, so that a module
+///   going beyond them is almost certainly a mistake, but rippled may accept a module that does.
+pub const CRAFT_DEVNET5: Limits = Limits {
+    name: "craft-devnet5",
+    max_memory_pages: 128,
+    stack_size: 128 * 1024,
+    max_table_elements: 1024,
+    max_functions: 10_000,
+    max_exports: 128,
+    any_imports: false,
+    floats: false,
+    simd: false,
+    threads: false,
+};
+
+/// No limits beyond the stack of [`CRAFT_DEVNET5`], for modules not headed for a network.
+pub const UNLIMITED: Limits = Limits {
+    name: "unlimited",
+    max_memory_pages: u64::MAX,
+    stack_size: CRAFT_DEVNET5.stack_size,
+    max_table_elements: u64::MAX,
+    max_functions: u32::MAX,
+    max_exports: u32::MAX,
+    any_imports: true,
+    floats: true,
+    simd: true,
+    threads: true,
+};
+
+/// The profiles `--limits` selects from. The first is the default.
+pub const PROFILES: &[Limits] = &[CRAFT_DEVNET5, UNLIMITED];
+
+impl Default for Limits {
+    fn default() -> Self {
+        PROFILES[0]
+    }
+}
+
+impl Limits {
+    /// These limits, with instances created with `stack_size` bytes of stack.
+    pub fn with_stack_size(self, stack_size: u32) -> Self {
+        Limits { stack_size, ..self }
+    }
+}
+
+/// Looks up the profile named `name`.
+pub fn profile(name: &str) -> Result<Limits, String> {
+    PROFILES
+        .iter()
+        .find(|limits| limits.name == name)
+        .copied()
+        .ok_or_else(|| {
+            let names: Vec<&str> = PROFILES.iter().map(|limits| limits.name).collect();
+            format!(
+                "unknown limits profile '{}', expected one of: {}",
+                name,
+                names.join(", ")
+            )
+        })
+}
+
+/// A way a module goes beyond its limits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// The limit broken, such as `memory` or `imports`.
+    pub limit: &'static str,
+    pub detail: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.limit, self.detail)
+    }
+}
+
+/// `ty` in the form host functions are registered with WAMR, such as `(iI)i`.
+fn wamr_signature(ty: &FuncType) -> String {
+    let code = |ty: &ValType| match ty {
+        ValType::I32 => 'i',
+        ValType::I64 => 'I',
+        ValType::F32 => 'f',
+        ValType::F64 => 'F',
+        _ => '?',
+    };
+    let params: String = ty.params().iter().map(code).collect();
+    let results: String = ty.results().iter().map(code).collect();
+    format!("({}){}", params, results)
+}
+
+fn violation(limit: &'static str, detail: String) -> Violation {
+    Violation { limit, detail }
+}
+
+fn check_size(
+    violations: &mut Vec<Violation>,
+    limit: &'static str,
+    unit: &str,
+    initial: u64,
+    maximum: Option<u64>,
+    allowed: u64,
+) {
+    if initial > allowed {
+        violations.push(violation(
+            limit,
+            format!(
+                "{} starts at {} {}, over craft's guard rail of {}",
+                limit, initial, unit, allowed
+            ),
+        ));
+    } else if let Some(maximum) = maximum.filter(|maximum| *maximum > allowed) {
+        violations.push(violation(
+            limit,
+            format!(
+                "{} declares a maximum of {} {}, over craft's guard rail of {}",
+                limit, maximum, unit, allowed
+            ),
+        ));
+    }
+}
+
+/// The feature limit operator `op`, of the proposal `proposal`, falls under, if any.
+fn proposal_limit(proposal: &str, op: &str) -> Option<&'static str> {
+    match proposal {
+        "simd" | "relaxed_simd" => Some("simd"),
+        "threads" | "shared_everything_threads" => Some("threads"),
+        _ if op.contains("F32") || op.contains("F64") => Some("floats"),
+        _ => None,
+    }
+}
+
+macro_rules! define_operator_limit {
+    ($( @$proposal:ident $op:ident $({ $($arg:ident: $argty:ty),* })? => $visit:ident ($($ann:tt)*) )*) => {
+        /// The feature limit `op` falls under, if any.
+        fn operator_limit(op: &Operator) -> Option<&'static str> {
+            match op {
+                $( Operator::$op { .. } => proposal_limit(stringify!($proposal), stringify!($op)), )*
+                #[allow(unreachable_patterns)]
+                _ => None,
+            }
+        }
+    };
+}
+wasmparser::for_each_operator!(define_operator_limit);
+
+/// The feature limit values of type `ty` fall under, if any.
+fn val_type_limit(ty: &ValType) -> Option<&'static str> {
+    match ty {
+        ValType::F32 | ValType::F64 => Some("floats"),
+        ValType::V128 => Some("simd"),
+        _ => None,
+    }
+}
+
+/// Whether `limits` allows the feature named `limit`.
+fn allows(limits: &Limits, limit: &str) -> bool {
+    match limit {
+        "floats" => limits.floats,
+        "simd" => limits.simd,
+        "threads" => limits.threads,
+        _ => true,
+    }
+}
+
+fn feature_description(limit: &str) -> &'static str {
+    match limit {
+        "floats" => "floating-point types or instructions",
+        "simd" => "SIMD types or instructions",
+        _ => "shared memory or atomics",
+    }
+}
+
+/// The feature limits each function body goes beyond, with the offset of the first local or
+/// instruction that does, in the order they are first found.
+fn check_body(
+    body: &FunctionBody,
+    limits: &Limits,
+) -> wasmparser::Result<Vec<(&'static str, usize)>> {
+    let mut found: Vec<(&'static str, usize)> = Vec::new();
+    let mut note = |limit: &'static str, offset: usize| {
+        if !allows(limits, limit) && !found.iter().any(|(seen, _)| *seen == limit) {
+            found.push((limit, offset));
+        }
+    };
+    let mut locals = body.get_locals_reader()?;
+    for _ in 0..locals.get_count() {
+        let offset = locals.original_position();
+        let (_, ty) = locals.read()?;
+        if let Some(limit) = val_type_limit(&ty) {
+            note(limit, offset);
+        }
+    }
+    for operator in body.get_operators_reader()?.into_iter_with_offsets() {
+        let (op, offset) = operator?;
+        if let Some(limit) = operator_limit(&op) {
+            note(limit, offset);
+        }
+    }
+    Ok(found)
+}
+
+/// Checks `wasm` against `limits`, returning every violation found. A module that is not valid
+/// WebAssembly is reported as a single `module` violation.
+///
+/// Features are found by scanning the module's types, globals, memories and every function's
+/// locals and instructions, so each type, global, memory and function that uses a feature the
+/// limits do not allow is reported, not only the first.
+pub fn check(wasm: &[u8], limits: &Limits) -> Vec<Violation> {
+    if let Err(e) = Validator::new().validate_all(wasm) {
+        return vec![violation("module", e.to_string())];
+    }
+
+    let mut violations = Vec::new();
+    let feature = |violations: &mut Vec<Violation>, limit: &'static str, what: String| {
+        if !allows(limits, limit) {
+            violations.push(violation(
+                limit,
+                format!("{} uses {}", what, feature_description(limit)),
+            ));
+        }
+    };
+    let mut types = Vec::new();
+    let mut imported_functions = 0u32;
+    let mut imported_globals = 0u32;
+    let mut imported_memories = 0u32;
+    let mut functions = 0u32;
+    let mut exports = 0u32;
+    let mut bodies = 0u32;
+    for payload in Parser::new(0).parse_all(wasm) {
+        let Ok(payload) = payload else {
+            break;
+        };
+        match payload {
+            Payload::TypeSection(reader) => {
+                for ty in reader.into_iter_err_on_gc_types().flatten() {
+                    let index = types.len();
+                    let mut limits_used: Vec<&'static str> = Vec::new();
+                    for limit in ty
+                        .params()
+                        .iter()
+                        .chain(ty.results())
+                        .filter_map(val_type_limit)
+                    {
+                        if !limits_used.contains(&limit) {
+                            limits_used.push(limit);
+                        }
+                    }
+                    for limit in limits_used {
+                        feature(&mut violations, limit, format!("type {}", index));
+                    }
+                    types.push(ty);
+                }
+            }
+            Payload::ImportSection(reader) => {
+                for import in reader.into_iter().flatten() {
+                    match import.ty {
+                        TypeRef::Func(_) => imported_functions += 1,
+                        TypeRef::Global(global) => {
+                            if let Some(limit) = val_type_limit(&global.content_type) {
+                                feature(
+                                    &mut violations,
+                                    limit,
+                                    format!("global {}", imported_globals),
+                                );
+                            }
+                            imported_globals += 1;
+                        }
+                        TypeRef::Memory(memory) => {
+                            if memory.shared {
+                                feature(
+                                    &mut violations,
+                                    "threads",
+                                    format!("memory {}", imported_memories),
+                                );
+                            }
+                            imported_memories += 1;
+                        }
+                        _ => {}
+                    }
+                    if limits.any_imports {
+                        continue;
+                    }
+                    let name = format!("{}.{}", import.module, import.name);
+                    let host_function = HOST_FUNCTIONS
+                        .iter()
+                        .find(|(host_name, _)| *host_name == import.name)
+                        .filter(|_| import.module == HOST_MODULE);
+                    let detail = match (import.ty, host_function) {
+                        _ if import.module.starts_with("wasi") => {
+                            format!(
+                                "'{}' is a WASI import, which rippled does not provide",
+                                name
+                            )
+                        }
+                        (TypeRef::Func(idx), Some((_, expected))) => {
+                            match types.get(idx as usize).map(wamr_signature) {
+                                Some(signature) if signature == *expected => continue,
+                                Some(signature) => format!(
+                                    "'{}' is imported as {}, but the host function is {}",
+                                    name, signature, expected
+                                ),
+                                None => format!("'{}' has no type", name),
+                            }
+                        }
+                        (TypeRef::Func(_), None) => format!("'{}' is not a host function", name),
+                        _ => format!("'{}' is imported, but only host functions may be", name),
+                    };
+                    violations.push(violation("imports", detail));
+                }
+            }
+            Payload::FunctionSection(reader) => functions += reader.count(),
+            Payload::ExportSection(reader) => exports += reader.count(),
+            Payload::GlobalSection(reader) => {
+                for (index, global) in reader.into_iter().enumerate() {
+                    let Ok(global) = global else {
+                        break;
+                    };
+                    if let Some(limit) = val_type_limit(&global.ty.content_type) {
+                        feature(
+                            &mut violations,
+                            limit,
+                            format!("global {}", imported_globals as usize + index),
+                        );
+                    }
+                }
+            }
+            Payload::MemorySection(reader) => {
+                for (index, memory) in reader.into_iter().enumerate() {
+                    let Ok(memory) = memory else {
+                        break;
+                    };
+                    if memory.shared {
+                        feature(
+                            &mut violations,
+                            "threads",
+                            format!("memory {}", imported_memories as usize + index),
+                        );
+                    }
+                    check_size(
+                        &mut violations,
+                        "memory",
+                        "pages",
+                        memory.initial,
+                        memory.maximum,
+                        limits.max_memory_pages,
+                    );
+                }
+            }
+            Payload::TableSection(reader) => {
+                for table in reader.into_iter().flatten() {
+                    check_size(
+                        &mut violations,
+                        "table",
+                        "elements",
+                        table.ty.initial,
+                        table.ty.maximum,
+                        limits.max_table_elements,
+                    );
+                }
+            }
+            Payload::CodeSectionEntry(body) => {
+                let index = imported_functions + bodies;
+                bodies += 1;
+                for (limit, offset) in check_body(&body, limits).unwrap_or_default() {
+                    violations.push(violation(
+                        limit,
+                        format!(
+                            "function {} uses {} (first at offset {:#x})",
+                            index,
+                            feature_description(limit),
+                            offset
+                        ),
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+    if functions > limits.max_functions {
+        violations.push(violation(
+            "functions",
+            format!(
+                "{} functions defined, over craft's guard rail of {}",
+                functions, limits.max_functions
+            ),
+        ));
+    }
+    if exports > limits.max_exports {
+        violations.push(violation(
+            "exports",
+            format!(
+                "{} exports, over craft's guard rail of {}",
+                exports, limits.max_exports
+            ),
+        ));
+    }
+    violations
+}
+
+/// Checks `wasm` like [`check`], and also that it exports `function` as an entry point.
+pub fn check_module(wasm: &[u8], function: &str, limits: &Limits) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    if let Err(e) = vm_wamr::resolve_entry_point(wasm, function) {
+        violations.push(violation("entry point", e.to_string()));
+    }
+    violations.extend(check(wasm, limits));
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A module whose `finish` returns `1.0f32` truncated to an `i32`.
+    const FLOAT_WASM: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // types
+        0x03, 0x02, 0x01, 0x00, // functions
+        0x07, 0x0a, 0x01, 0x06, b'f', b'i', b'n', b'i', b's', b'h', 0x00, 0x00, // exports
+        0x0a, 0x0a, 0x01, 0x08, 0x00, 0x43, 0x00, 0x00, 0x80, 0x3f, 0xa8, 0x0b, // code
+    ];
+
+    /// A module importing `env.abort` and `host_lib.get_ledger_sqn`, with 200 pages of memory.
+    const IMPORTS_WASM: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // types
+        0x02, 0x27, 0x02, // imports
+        0x03, b'e', b'n', b'v', 0x05, b'a', b'b', b'o', b'r', b't', 0x00, 0x00, //
+        0x08, b'h', b'o', b's', b't', b'_', b'l', b'i', b'b', //
+        0x0e, b'g', b'e', b't', b'_', b'l', b'e', b'd', b'g', b'e', b'r', b'_', b's', b'q', b'n',
+        0x00, 0x00, //
+        0x05, 0x04, 0x01, 0x00, 0xc8, 0x01, // memory
+    ];
+
+    /// A module importing `host_lib.get_base_fee` as `(i32) -> i32` and
+    /// `wasi_snapshot_preview1.fd_write`, without an entry point.
+    const WRONG_IMPORTS_WASM: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // types
+        0x02, 0x3b, 0x02, // imports
+        0x08, b'h', b'o', b's', b't', b'_', b'l', b'i', b'b', //
+        0x0c, b'g', b'e', b't', b'_', b'b', b'a', b's', b'e', b'_', b'f', b'e', b'e', 0x00, 0x00,
+        0x16, b'w', b'a', b's', b'i', b'_', b's', b'n', b'a', b'p', b's', b'h', b'o', b't', b'_',
+        b'p', b'r', b'e', b'v', b'i', b'e', b'w', b'1', //
+        0x08, b'f', b'd', b'_', b'w', b'r', b'i', b't', b'e', 0x00, 0x00, //
+    ];
+
+    /// A module whose `finish` drops a `v128` constant and returns 0.
+    const SIMD_WASM: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // types
+        0x03, 0x02, 0x01, 0x00, // functions
+        0x07, 0x0a, 0x01, 0x06, b'f', b'i', b'n', b'i', b's', b'h', 0x00, 0x00, // exports
+        0x0a, 0x19, 0x01, 0x17, 0x00, 0xfd, 0x0c, // code
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //
+        0x1a, 0x41, 0x00, 0x0b,
+    ];
+
+    /// A module with a shared memory, an `f64` global, a function truncating `1.0f32` and one
+    /// dropping a `v128` constant.
+    const FEATURES_WASM: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // types
+        0x03, 0x03, 0x02, 0x00, 0x00, // functions
+        0x05, 0x04, 0x01, 0x03, 0x01, 0x01, // memory
+        0x06, 0x0d, 0x01, 0x7c, 0x00, 0x44, 0, 0, 0, 0, 0, 0, 0, 0, 0x0b, // globals
+        0x0a, 0x22, 0x02, // code
+        0x08, 0x00, 0x43, 0x00, 0x00, 0x80, 0x3f, 0xa8, 0x0b, //
+        0x17, 0x00, 0xfd, 0x0c, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //
+        0x1a, 0x41, 0x00, 0x0b,
+    ];
+
+    #[test]
+    fn test_profile_lookup() {
+        assert_eq!(profile("craft-devnet5"), Ok(CRAFT_DEVNET5));
+        assert_eq!(profile("unlimited"), Ok(UNLIMITED));
+        assert_eq!(Limits::default(), CRAFT_DEVNET5);
+        let limits = CRAFT_DEVNET5.with_stack_size(256 * 1024);
+        assert_eq!(limits.stack_size, 256 * 1024);
+        assert_eq!(limits.max_memory_pages, CRAFT_DEVNET5.max_memory_pages);
+        let err = profile("mainnet").unwrap_err();
+        assert!(err.contains("craft-devnet5, unlimited"), "{}", err);
+    }
+
+    #[test]
+    fn test_check_rejects_floats() {
+        let violations = check(FLOAT_WASM, &CRAFT_DEVNET5);
+        let messages: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            messages,
+            ["floats: function 0 uses floating-point types or instructions (first at offset 0x24)"]
+        );
+        assert!(check(FLOAT_WASM, &UNLIMITED).is_empty());
+    }
+
+    #[test]
+    fn test_check_rejects_imports_and_memory() {
+        let violations = check(IMPORTS_WASM, &CRAFT_DEVNET5);
+        let messages: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "imports: 'env.abort' is not a host function",
+                "memory: memory starts at 200 pages, over craft's guard rail of 128",
+            ]
+        );
+        assert!(check(IMPORTS_WASM, &UNLIMITED).is_empty());
+        assert_eq!(check(b"\0asm", &UNLIMITED)[0].limit, "module");
+    }
+
+    #[test]
+    fn test_check_module_rejects_signatures_wasi_and_missing_entry_point() {
+        let violations = check_module(WRONG_IMPORTS_WASM, "finish", &CRAFT_DEVNET5);
+        let messages: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "entry point: Function 'finish' is not exported; the module exports no functions",
+                "imports: 'host_lib.get_base_fee' is imported as (i)i, but the host function is ()i",
+                "imports: 'wasi_snapshot_preview1.fd_write' is a WASI import, which rippled does not provide",
+            ]
+        );
+        assert!(check_module(FLOAT_WASM, "finish", &UNLIMITED).is_empty());
+    }
+
+    #[test]
+    fn test_check_rejects_simd() {
+        let violations = check(SIMD_WASM, &CRAFT_DEVNET5);
+        assert_eq!(violations.len(), 1, "{:?}", violations);
+        assert_eq!(violations[0].limit, "simd");
+        assert!(check(SIMD_WASM, &UNLIMITED).is_empty());
+    }
+
+    #[test]
+    fn test_check_reports_every_feature_use() {
+        let violations = check(FEATURES_WASM, &CRAFT_DEVNET5);
+        let messages: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "threads: memory 0 uses shared memory or atomics",
+                "floats: global 0 uses floating-point types or instructions",
+                "floats: function 0 uses floating-point types or instructions (first at offset 0x2e)",
+                "simd: function 1 uses SIMD types or instructions (first at offset 0x37)",
+            ]
+        );
+        let floats_allowed = Limits {
+            floats: true,
+            ..CRAFT_DEVNET5
+        };
+        let limits: Vec<&str> = check(FEATURES_WASM, &floats_allowed)
+            .iter()
+            .map(|v| v.limit)
+            .collect();
+        assert_eq!(limits, ["threads", "simd"]);
+        assert!(check(FEATURES_WASM, &UNLIMITED).is_empty());
+    }
+}
@@ -1,30 +1,38 @@
 extern crate core;
 
-mod data_provider;
-mod decoding;
-mod hashing;
-mod host_functions_wamr;
-mod mock_data;
-mod vm_wamr;
-
-use crate::mock_data::MockData;
 use clap::Parser;
 use env_logger::Builder;
 use log::LevelFilter;
 use log::{debug, error, info};
+use serde_json::json;
 use std::fs;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use wasm_host_simulator::fixture_check::{self, Severity};
+use wasm_host_simulator::fuzz;
+use wasm_host_simulator::limits::{self, Limits};
+use wasm_host_simulator::mock_data::MockData;
+use wasm_host_simulator::simulator::{
+    self, Fixture, GasCap, HeaderOverrides, SETUP_ERROR, Simulator, TestOutcome, TestReport,
+};
+use wasm_host_simulator::sweep::{self, Sweep, SweepField};
+use wasm_host_simulator::tape::Tape;
+use wasm_host_simulator::vm_wamr::RunResult;
 
 /// Wasm WASM testing utility
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// Path to the WASM file
+    /// Path to the WASM module to run
+    /// (defaults to projects/target/wasm32v1-none/debug/<project>.wasm)
+    #[arg(long)]
+    wasm: Option<String>,
+
+    /// Path to the project directory containing the fixtures
     #[arg(long)]
     dir: Option<String>,
 
-    /// Test case to run (success/failure)
+    /// Test case to run: any fixture directory name (success/failure need no expected.json)
     #[arg(short, long, default_value = "success")]
     test_case: String,
 
@@ -40,21 +48,98 @@ struct Args {
     #[arg(long, default_value = "finish")]
     function: String,
 
-    /// Gas cap can be changed for debugging.
-    /// Note that default value is the default used in rippled.
-    #[arg(long, default_value = "1000000")]
-    gas_cap: u32,
+    /// Gas cap for the run, or `none` to run without one.
+    /// Defaults to the fixture's `ComputationAllowance`, then to rippled's maximum of 1000000.
+    #[arg(long, value_parser = simulator::parse_gas_cap)]
+    gas_cap: Option<GasCap>,
+
+    /// Limits profile the module must be within to be run: craft-devnet5 or unlimited
+    #[arg(long, value_parser = limits::profile, default_value = "craft-devnet5")]
+    limits: Limits,
+
+    /// Bytes of stack to run the module with, instead of the limits profile's 131072
+    #[arg(long, value_name = "BYTES")]
+    stack_size: Option<u32>,
+
+    /// Output format: human-readable text, or a single JSON record describing the run
+    #[arg(long, value_enum, default_value = "text")]
+    output: OutputFormat,
+
+    /// Validate every fixture of the project instead of running the module
+    #[arg(long)]
+    check: bool,
+
+    /// Write the hosting ledger object, with anything stored by `update_data`, to this file
+    /// once the function returns a positive value
+    #[arg(long)]
+    state_out: Option<PathBuf>,
+
+    /// Read the hosting ledger object from this file instead of the fixture's
+    /// `ledger_object.json`, e.g. the `--state-out` of a previous run
+    #[arg(long)]
+    state_in: Option<PathBuf>,
+
+    /// Override the ledger header's `ledger_index`
+    #[arg(long)]
+    ledger_seq: Option<u32>,
+
+    /// Override the ledger header's `parent_close_time`, in seconds since the Ripple epoch
+    #[arg(long)]
+    close_time: Option<u32>,
+
+    /// Override the ledger header's `parent_hash`, as 64 hex characters
+    #[arg(long, value_parser = simulator::parse_hash)]
+    parent_hash: Option<String>,
+
+    /// Run over a range of ledger sequences, FROM..TO, and report where the result changes
+    #[arg(long, value_parser = sweep::parse_range, conflicts_with_all = ["ledger_seq", "sweep_close_time"])]
+    sweep_ledger_seq: Option<(u32, u32)>,
+
+    /// Run over a range of close times, FROM..TO, and report where the result changes
+    #[arg(long, value_parser = sweep::parse_range, conflicts_with = "close_time")]
+    sweep_close_time: Option<(u32, u32)>,
+
+    /// Run only every Nth value of a sweep, bisecting between runs whose results differ
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    sweep_step: u32,
+
+    /// Run this many randomly mutated copies of the fixture, reporting traps, gas exhaustion,
+    /// out-of-bounds host pointers and nondeterminism, each shrunk to a minimal fixture written
+    /// into the project's fixtures directory
+    #[arg(long, conflicts_with_all = ["sweep_ledger_seq", "sweep_close_time", "state_out"])]
+    fuzz: Option<u32>,
+
+    /// Run the test case under rippled's host conditions and then under varied ones (slot
+    /// order, output buffer contents, leftover rounding mode, ledger iteration order), and fail
+    /// if the return value, gas, traces or Data differ
+    #[arg(long, conflicts_with_all = ["fuzz", "sweep_ledger_seq", "sweep_close_time", "state_out"])]
+    check_determinism: bool,
+
+    /// Seed for `--fuzz`, to repeat an earlier run (defaults to the current time)
+    #[arg(long, requires = "fuzz")]
+    seed: Option<u64>,
+
+    /// Record every host function call of the run (its arguments, the buffers passed in, the
+    /// bytes returned and the return code) to this tape file
+    #[arg(long, conflicts_with_all = ["fuzz", "check_determinism", "sweep_ledger_seq", "sweep_close_time"])]
+    record_tape: Option<PathBuf>,
+
+    /// Run the tape's function with each host function call answered from this tape instead of
+    /// the fixtures, and fail where the calls differ from the tape's
+    #[arg(long, conflicts_with_all = ["record_tape", "fuzz", "check_determinism", "sweep_ledger_seq", "sweep_close_time", "state_in", "state_out"])]
+    replay_tape: Option<PathBuf>,
 }
 
-#[allow(clippy::type_complexity)]
-fn load_test_data(
-    dir: Option<String>,
-    project: &str,
-    test_case: &str,
-) -> Result<(String, String, String, String, String), Box<dyn std::error::Error>> {
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+fn fixtures_root(dir: Option<&str>, project: &str) -> PathBuf {
     // Convention: fixtures must be in projects/<project>/fixtures/<test_case>/
-    let base_path = if let Some(dir) = dir {
-        PathBuf::from(dir).join("fixtures").join(test_case)
+    if let Some(dir) = dir {
+        PathBuf::from(dir).join("fixtures")
     } else {
         PathBuf::from(env!("CARGO_MANIFEST_DIR"))
             .parent()
@@ -62,54 +147,391 @@ fn load_test_data(
             .join("projects")
             .join(project)
             .join("fixtures")
-            .join(test_case)
+    }
+}
+
+fn fixture_dir(dir: Option<&str>, project: &str, test_case: &str) -> PathBuf {
+    fixtures_root(dir, project).join(test_case)
+}
+
+/// Validates every fixture of the project, printing what was found. Fails if any has errors.
+fn check_fixtures(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let root = fixtures_root(args.dir.as_deref(), &args.project);
+    let cases = fixture_check::check_fixtures(&root)
+        .map_err(|e| format!("Cannot read fixtures in {}: {}", root.display(), e))?;
+    if cases.is_empty() {
+        println!("No fixtures found in {}", root.display());
+    }
+
+    let mut errors = 0;
+    let mut warnings = 0;
+    for (test_case, diagnostics) in &cases {
+        let case_errors = diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .count();
+        let case_warnings = diagnostics.len() - case_errors;
+        errors += case_errors;
+        warnings += case_warnings;
+        if diagnostics.is_empty() {
+            println!("{}: ok", test_case);
+        } else {
+            println!(
+                "{}: {} error(s), {} warning(s)",
+                test_case, case_errors, case_warnings
+            );
+            for diagnostic in diagnostics {
+                println!("  {}", diagnostic);
+            }
+        }
+    }
+
+    println!(
+        "Checked {} fixture(s): {} error(s), {} warning(s)",
+        cases.len(),
+        errors,
+        warnings
+    );
+    if errors > 0 {
+        return Err("Fixture check failed".into());
+    }
+    Ok(())
+}
+
+fn sweep_from_args(args: &Args) -> Option<Sweep> {
+    let (field, (from, to)) = match (args.sweep_ledger_seq, args.sweep_close_time) {
+        (Some(range), _) => (SweepField::LedgerSeq, range),
+        (None, Some(range)) => (SweepField::CloseTime, range),
+        (None, None) => return None,
     };
+    Some(Sweep {
+        field,
+        from,
+        to,
+        step: args.sweep_step,
+    })
+}
 
-    if !base_path.exists() {
-        return Err(format!(
-            "Test case '{}' not found at expected location: {}",
-            test_case,
-            base_path.display()
-        )
-        .into());
+/// Runs the function over a range of header values and reports where its result changes.
+fn run_sweep(
+    args: &Args,
+    simulator: &Simulator,
+    sweep: &Sweep,
+    data_source: &MockData,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let field = sweep.field.header_field();
+    let report = match simulator.sweep(data_source, sweep) {
+        Ok(report) => report,
+        Err(e) => {
+            report_failure(args, e.kind(), &e.to_string())?;
+            error!("Function execution failed: {}", e);
+            return Err(Box::new(e));
+        }
+    };
+
+    if args.output == OutputFormat::Json {
+        let record = json!({
+            "function": args.function,
+            "test_case": args.test_case,
+            "sweep": report.to_json(),
+        });
+        println!("{}", serde_json::to_string_pretty(&record)?);
+        return Ok(());
     }
 
-    let tx_path = base_path.join("tx.json");
-    let lo_path = base_path.join("ledger_object.json");
-    let lh_path = base_path.join("ledger_header.json");
-    let l_path = base_path.join("ledger.json");
-    let nfts_path = base_path.join("nfts.json");
+    println!(
+        "Sweep of {} over {}..{} ({} runs):",
+        field, sweep.from, sweep.to, report.runs
+    );
+    for (from, to, result) in report.ranges() {
+        println!("  {:>21}  {}", format!("{}..{}", from, to), result);
+    }
+    if report.flips.is_empty() {
+        println!("The result does not change over the range");
+    }
+    for flip in &report.flips {
+        println!(
+            "Result flips at {} {}: {} -> {}",
+            field, flip.at, flip.before, flip.after
+        );
+    }
+    Ok(())
+}
 
-    let tx_json = fs::read_to_string(tx_path)?;
-    let lo_json = fs::read_to_string(lo_path)?;
-    let lh_json = fs::read_to_string(lh_path)?;
-    let l_json = fs::read_to_string(l_path)?;
-    let nft_json = fs::read_to_string(nfts_path)?;
+/// Runs the function under each set of host conditions and fails if the runs disagree.
+fn run_determinism_check(
+    args: &Args,
+    simulator: &Simulator,
+    data_source: &MockData,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let report = match simulator.check_determinism(data_source) {
+        Ok(report) => report,
+        Err(e) => {
+            report_failure(args, e.kind(), &e.to_string())?;
+            error!("Function execution failed: {}", e);
+            return Err(Box::new(e));
+        }
+    };
 
-    Ok((tx_json, lo_json, lh_json, l_json, nft_json))
+    if args.output == OutputFormat::Json {
+        let record = json!({
+            "function": args.function,
+            "test_case": args.test_case,
+            "determinism": report.to_json(),
+        });
+        println!("{}", serde_json::to_string_pretty(&record)?);
+    } else {
+        println!(
+            "Determinism check of {} on '{}' ({} runs, {}):",
+            args.function, args.test_case, report.runs, report.result
+        );
+        if report.differences.is_empty() {
+            println!("Every run agreed on return value, gas, traces and Data");
+        }
+        for d in &report.differences {
+            println!(
+                "  {} differs with {}: {} -> {}",
+                d.aspect, d.conditions, d.expected, d.actual
+            );
+        }
+    }
+    if !report.differences.is_empty() {
+        return Err("Function is not deterministic".into());
+    }
+    Ok(())
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+/// Fuzzes the function with `cases` mutated copies of the fixture and writes a minimal fixture
+/// for each kind of failure found. Fails if anything was found.
+fn run_fuzz(
+    args: &Args,
+    simulator: &Simulator,
+    cases: u32,
+    fixture: &Fixture,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let seed = args.seed.unwrap_or_else(fuzz::seed_from_time);
+    println!(
+        "Fuzzing {} of {} from '{}' with {} cases (seed {})",
+        args.function, args.project, args.test_case, cases, seed
+    );
+    let found = simulator.fuzz(&fixture.data, cases, seed).map_err(|e| {
+        error!("Function execution failed: {}", e);
+        e
+    })?;
+
+    if found.is_empty() {
+        println!("No failures found");
+        return Ok(());
+    }
+    let root = fixtures_root(args.dir.as_deref(), &args.project);
+    for case in &found {
+        let name = case.fixture_name(seed);
+        println!(
+            "{}: {} (first in case {}, {} case(s) in all)",
+            case.finding.kind.name(),
+            case.finding.detail,
+            case.case,
+            case.count
+        );
+        for mutation in &case.mutations {
+            println!("  {}", mutation);
+        }
+        let dir = fuzz::write_fixture(&root, &fixture.dir, &fixture.data, &name, case)
+            .map_err(|e| format!("Cannot write fixture {}: {}", name, e))?;
+        println!("  written to {}", dir.display());
+    }
+    Err(format!("Fuzzing found {} kind(s) of failure", found.len()).into())
+}
+
+/// Runs the test case, writing its host function calls to a tape at `path`.
+fn record_tape(
+    simulator: &Simulator,
+    fixture: &Fixture,
+    path: &Path,
+) -> Result<TestReport, Box<dyn std::error::Error>> {
+    let (report, tape) = simulator.record_fixture(fixture);
+    if let Some(tape) = tape {
+        tape.save(path)
+            .map_err(|e| format!("Cannot write tape {}: {}", path.display(), e))?;
+        info!(
+            "{} host call(s) recorded to {}",
+            tape.calls.len(),
+            path.display()
+        );
+    }
+    Ok(report)
+}
+
+/// Replays the tape at `path` into the function and fails if its host calls or result differ
+/// from the tape's.
+fn run_replay(
+    args: &Args,
+    simulator: &Simulator,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let tape = match Tape::load(path) {
+        Ok(tape) => tape,
+        Err(e) => {
+            report_failure(args, SETUP_ERROR, &e)?;
+            return Err(e.into());
+        }
+    };
+    let report = match simulator.replay(&tape) {
+        Ok(report) => report,
+        Err(e) => {
+            report_failure(args, e.kind(), &e.to_string())?;
+            error!("Function execution failed: {}", e);
+            return Err(Box::new(e));
+        }
+    };
 
+    if args.output == OutputFormat::Json {
+        let record = json!({
+            "function": tape.function,
+            "tape": path.display().to_string(),
+            "replay": report.to_json(),
+        });
+        println!("{}", serde_json::to_string_pretty(&record)?);
+    } else {
+        println!(
+            "Replay of {} from {} ({} of {} host call(s), {}):",
+            tape.function,
+            path.display(),
+            report.replayed,
+            tape.calls.len(),
+            report.outcome
+        );
+        if report.differences.is_empty() {
+            println!("Every host call and the result matched the tape");
+        }
+        for d in &report.differences {
+            match d.call {
+                Some(call) => {
+                    println!("  call {}: expected {}, got {}", call, d.expected, d.actual)
+                }
+                None => println!("  result: expected {}, got {}", d.expected, d.actual),
+            }
+        }
+    }
+    if !report.differences.is_empty() {
+        return Err("Host calls diverged from the tape".into());
+    }
+    Ok(())
+}
+
+/// Prints a test case's result box, or its JSON record.
+fn print_report(args: &Args, report: &TestReport) -> Result<(), serde_json::Error> {
+    if args.output == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&report.to_json())?);
+        return Ok(());
+    }
+    let passed = report.passed();
+    let failures = report.failures();
+    match &report.outcome {
+        TestOutcome::Run { run, .. } => match &run.outcome {
+            Ok(return_value) => {
+                println!("-------------------------------------------------");
+                println!("| WASM FUNCTION EXECUTION RESULT                |");
+                println!("-------------------------------------------------");
+                println!("| Function:   {:<33} |", args.function);
+                println!("| Test Case:  {:<33} |", args.test_case);
+                println!("| Returned:   {:<33} |", return_value);
+                if let Some(gas) = run.gas_used {
+                    println!("| Gas Used:   {:<33} |", gas);
+                }
+                println!("| Passed:     {:<33} |", passed);
+                println!("-------------------------------------------------");
+                for failure in &failures {
+                    println!("Expectation failed: {}", failure);
+                }
+            }
+            Err(e) => print_error_box(&args.function, &args.test_case, e.kind(), &e.to_string()),
+        },
+        TestOutcome::Scenario(steps) => {
+            println!("-------------------------------------------------");
+            println!("| SCENARIO RESULT                               |");
+            println!("-------------------------------------------------");
+            println!("| Function:   {:<33} |", args.function);
+            println!("| Test Case:  {:<33} |", args.test_case);
+            for step in steps {
+                let result = match &step.run.outcome {
+                    Ok(rv) => format!("returned {}", rv),
+                    Err(e) => e.kind().to_string(),
+                };
+                let verdict = if step.failures.is_empty() {
+                    "ok"
+                } else {
+                    "FAILED"
+                };
+                println!(
+                    "| {:<45} |",
+                    format!("{}: {}, {}", step.name, result, verdict)
+                );
+            }
+            println!("| Passed:     {:<33} |", passed);
+            println!("-------------------------------------------------");
+            for failure in &failures {
+                println!("Expectation failed: {}", failure);
+            }
+        }
+        TestOutcome::Failed { outcome, error } => {
+            print_error_box(&args.function, &args.test_case, outcome, error)
+        }
+    }
+    Ok(())
+}
+
+/// The module to run: `--wasm`, or else the project's debug build under `projects/target`.
+fn wasm_file(args: &Args) -> Result<String, String> {
+    if let Some(wasm) = &args.wasm {
+        return Ok(wasm.clone());
+    }
     let base_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
         .parent()
         .unwrap()
         .join("projects");
-
     if !base_path.exists() {
-        eprintln!(
-            "Error: Could not find projects directory at expected location: {}",
+        return Err(format!(
+            "Could not find projects directory at expected location: {}",
             base_path.display()
-        );
-        std::process::exit(1);
+        ));
     }
-
-    let wasm_file = base_path
+    Ok(base_path
         .join("target/wasm32v1-none/debug")
         .join(format!("{}.wasm", args.project))
         .to_string_lossy()
-        .to_string();
+        .to_string())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    if args.check {
+        return check_fixtures(&args);
+    }
+
+    let wasm_file = match wasm_file(&args) {
+        Ok(wasm_file) => wasm_file,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let simulator = match Simulator::new(Path::new(&wasm_file)) {
+        Ok(simulator) => simulator
+            .function(&args.function)
+            .gas_cap(args.gas_cap)
+            .limits(
+                args.stack_size
+                    .map_or(args.limits, |bytes| args.limits.with_stack_size(bytes)),
+            )
+            .echo_traces(args.output == OutputFormat::Text),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
 
     // Initialize logger with appropriate level
     let log_level = if args.verbose {
@@ -135,62 +557,142 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("Target function: {} (default is 'finish')", args.function);
     info!("Using test case: {}", args.test_case);
     info!("Project: {}", args.project);
+    info!("WASM module: {}", wasm_file);
     info!(
         "Source Directory: {}",
         args.dir.as_deref().unwrap_or("default")
     );
+    if let Some(path) = &args.replay_tape {
+        return run_replay(&args, &simulator, path);
+    }
     info!("Loading test data from fixtures");
-    let (tx_json, lo_json, lh_json, l_json, nft_json) =
-        match load_test_data(args.dir, &args.project, &args.test_case) {
-            Ok((tx, lo, lh, l, nft)) => {
-                debug!("Test data loaded successfully");
-                (tx, lo, lh, l, nft)
-            }
-            Err(e) => {
-                error!("Failed to load test data: {}", e);
-                return Err(e);
-            }
-        };
-
-    let data_source = MockData::new(&tx_json, &lo_json, &lh_json, &l_json, &nft_json);
-    info!("Executing function: {}", args.function);
-    // TODO: Make Gas Cap optional via https://github.com/ripple/craft/issues/141
-    match vm_wamr::run_func(wasm_file, &args.function, Some(args.gas_cap), data_source) {
-        Ok(result) => {
-            if (result && args.test_case == "success") || (!result && args.test_case == "failure") {
-                println!("-------------------------------------------------");
-                println!("| WASM FUNCTION EXECUTION RESULT                |");
-                println!("-------------------------------------------------");
-                println!("| Function:   {:<33} |", args.function);
-                println!("| Test Case:  {:<33} |", args.test_case);
-                println!("| Result:     {:<33} |", result);
-                println!("-------------------------------------------------");
-                info!("Function completed with result: {}", result);
-            } else {
-                println!("-------------------------------------------------");
-                println!("| WASM FUNCTION EXECUTION RESULT                |");
-                println!("-------------------------------------------------");
-                println!("| Function:   {:<33} |", args.function);
-                println!("| Test Case:  {:<33} |", args.test_case);
-                println!("| Result:     {:<33} |", result);
-                println!("-------------------------------------------------");
-                info!("Function completed with result: {}", result);
-                return Err("Function result did not match expected outcome".into());
-            }
+    let base_path = fixture_dir(args.dir.as_deref(), &args.project, &args.test_case);
+    let mut fixture = match Fixture::load(&base_path, &args.test_case, args.state_in.as_deref()) {
+        Ok(fixture) => {
+            debug!("Test data loaded successfully");
+            fixture
         }
         Err(e) => {
-            println!("-------------------------------------------------");
-            println!("| WASM FUNCTION EXECUTION ERROR                 |");
-            println!("-------------------------------------------------");
-            println!("| Function:   {:<33} |", args.function);
-            println!("| Test Case:  {:<33} |", args.test_case);
-            println!("| Error:      {:<33} |", e);
-            println!("-------------------------------------------------");
+            report_failure(&args, SETUP_ERROR, &e)?;
+            error!("Failed to set up test case: {}", e);
+            return Err(e.into());
+        }
+    };
+
+    HeaderOverrides {
+        ledger_seq: args.ledger_seq,
+        close_time: args.close_time,
+        parent_hash: args.parent_hash.clone(),
+    }
+    .apply(&mut fixture.data);
+
+    let unsupported = if args.check_determinism {
+        Some("checked for determinism")
+    } else if args.fuzz.is_some() {
+        Some("fuzzed")
+    } else if sweep_from_args(&args).is_some() {
+        Some("swept")
+    } else {
+        None
+    };
+    if let (Some(mode), Some(_)) = (unsupported, &fixture.scenario) {
+        let e = format!("A scenario cannot be {}", mode);
+        report_failure(&args, SETUP_ERROR, &e)?;
+        return Err(e.into());
+    }
+    if args.check_determinism {
+        return run_determinism_check(&args, &simulator, &fixture.data);
+    }
+    if let Some(cases) = args.fuzz {
+        return run_fuzz(&args, &simulator, cases, &fixture);
+    }
+    if let Some(sweep) = sweep_from_args(&args) {
+        return run_sweep(&args, &simulator, &sweep, &fixture.data);
+    }
+
+    let report = match &args.record_tape {
+        Some(path) => record_tape(&simulator, &fixture, path)?,
+        None => simulator.run_fixture(&fixture),
+    };
+    if let (Some(path), Some(ledger_object)) = (&args.state_out, report.ledger_object()) {
+        fs::write(path, serde_json::to_string_pretty(ledger_object)?)
+            .map_err(|e| format!("Cannot write state {}: {}", path.display(), e))?;
+        info!("Ledger object state written to {}", path.display());
+    }
+    print_report(&args, &report)?;
+
+    match &report.outcome {
+        TestOutcome::Run {
+            run: RunResult {
+                outcome: Ok(rv), ..
+            },
+            ..
+        } => {
+            info!("Function completed with result: {}", rv);
+        }
+        TestOutcome::Run {
+            run: RunResult {
+                outcome: Err(e), ..
+            },
+            ..
+        } => {
             error!("Function execution failed: {}", e);
-            return Err(Box::new(e));
+            return Err(e.to_string().into());
         }
+        TestOutcome::Scenario(_) => info!("Scenario completed"),
+        TestOutcome::Failed { error, .. } => {
+            error!("Function execution failed: {}", error);
+            return Err(error.clone().into());
+        }
+    }
+    if !report.passed() {
+        return Err("Function result did not match expected outcome".into());
     }
 
     info!("Wasm host application execution completed");
     Ok(())
 }
+
+/// Reports a test case that produced no return value: as a JSON record, or as an error box.
+fn report_failure(
+    args: &Args,
+    outcome: &'static str,
+    error: &str,
+) -> Result<(), serde_json::Error> {
+    print_report(
+        args,
+        &TestReport::failed(&args.function, &args.test_case, outcome, error),
+    )
+}
+fn print_error_box(function: &str, test_case: &str, outcome: &str, error: &str) {
+    println!("-------------------------------------------------");
+    match outcome {
+        "out_of_gas" => println!("| WASM FUNCTION OUT OF GAS                      |"),
+        SETUP_ERROR => println!("| TEST CASE SETUP ERROR                         |"),
+        _ => println!("| WASM FUNCTION EXECUTION ERROR                 |"),
+    }
+    println!("-------------------------------------------------");
+    println!("| Function:   {:<33} |", function);
+    println!("| Test Case:  {:<33} |", test_case);
+    println!("| Error:      {:<33} |", error);
+    println!("-------------------------------------------------");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wasm_file() {
+        let args = Args::parse_from(["sim", "--project", "notary", "--wasm", "/tmp/notary.wasm"]);
+        assert_eq!(wasm_file(&args).unwrap(), "/tmp/notary.wasm");
+
+        let args = Args::parse_from(["sim", "--project", "notary"]);
+        let default = wasm_file(&args).unwrap();
+        assert!(
+            default.ends_with("projects/target/wasm32v1-none/debug/notary.wasm"),
+            "{}",
+            default
+        );
+    }
+}
@@ -2,8 +2,11 @@
 
 use crate::data_provider::HostError;
 use crate::decoding::{AccountId, Decodable, SField_To_Name, decode};
-use crate::hashing::Hash256;
+use crate::hashing::{Hash256, LedgerNameSpace, index_hash};
+use crate::keylets::ledger_entry_key;
+use serde_json::json;
 use std::collections::HashMap;
+use xrpl::core::addresscodec::utils::encode_base58;
 
 pub type Bytes = Vec<u8>;
 
@@ -15,7 +18,60 @@ pub enum DataSource {
     KeyletLedgerObj(Keylet),
 }
 
+/// Why a fixture could not be loaded into [`MockData`].
 #[derive(Debug)]
+pub enum MockDataError {
+    /// A fixture file is not valid JSON, or not of the expected shape.
+    InvalidJson {
+        file: &'static str,
+        error: serde_json::Error,
+    },
+    /// A key or field in a fixture file could not be decoded.
+    InvalidValue {
+        file: &'static str,
+        key: String,
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for MockDataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MockDataError::InvalidJson { file, error } => write!(f, "{}: {}", file, error),
+            MockDataError::InvalidValue { file, key, reason } => {
+                write!(f, "{}: '{}': {}", file, key, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MockDataError {}
+
+fn parse_json(file: &'static str, json: &str) -> Result<serde_json::Value, MockDataError> {
+    serde_json::from_str(json).map_err(|error| MockDataError::InvalidJson { file, error })
+}
+
+/// The objects of one `ledger.json` entry under their IDs: either a bare object, or a map of
+/// objects under their keys. Errors name the key or entry type at fault.
+fn ledger_entry_objects(
+    entry: serde_json::Map<String, serde_json::Value>,
+) -> Result<Vec<(Keylet, serde_json::Value)>, (String, String)> {
+    if let Some(entry_type) = entry.get("LedgerEntryType") {
+        let key = entry_type.as_str().unwrap_or("").to_string();
+        let object = serde_json::Value::Object(entry);
+        let keylet = ledger_entry_key(None, &object).map_err(|reason| (key, reason))?;
+        return Ok(vec![(keylet, object)]);
+    }
+    entry
+        .into_iter()
+        .map(|(key, value)| match ledger_entry_key(Some(&key), &value) {
+            Ok(keylet) => Ok((keylet, value)),
+            Err(reason) => Err((key, reason)),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct MockData {
     tx: serde_json::Value,
     hosting_ledger_obj: serde_json::Value,
@@ -31,58 +87,101 @@ impl MockData {
         header_str: &str,
         ledger_str: &str,
         nfts_str: &str,
-    ) -> Self {
-        let tx = serde_json::from_str(tx_str).expect("Tx JSON bad formatted");
-        let hosting_ledger_obj = serde_json::from_str(hosting_ledger_obj_str)
-            .expect("Hosting ledger object JSON bad formatted");
-        let header = serde_json::from_str(header_str).expect("Ledger header JSON bad formatted");
+    ) -> Result<Self, MockDataError> {
+        let tx = parse_json("tx.json", tx_str)?;
+        let hosting_ledger_obj = parse_json("ledger_object.json", hosting_ledger_obj_str)?;
+        let header = parse_json("ledger_header.json", header_str)?;
 
         let ledger = {
-            let parsed_data: Vec<HashMap<String, serde_json::Value>> =
-                serde_json::from_str(ledger_str).expect("Ledger JSON bad formatted");
+            let entries: Vec<serde_json::Map<String, serde_json::Value>> = serde_json::from_value(
+                parse_json("ledger.json", ledger_str)?,
+            )
+            .map_err(|error| MockDataError::InvalidJson {
+                file: "ledger.json",
+                error,
+            })?;
             let mut combined_hashmap: HashMap<Keylet, serde_json::Value> = HashMap::new();
-            for map_entry in parsed_data {
-                for (key, value) in map_entry {
-                    let keylet: Keylet =
-                        decode(&key, Decodable::UINT256).expect("ledger file, bad keylet");
-                    // println!("MockData keylet {:?}", keylet);
-                    combined_hashmap.insert(keylet, value);
-                }
+            for entry in entries {
+                let objects = ledger_entry_objects(entry).map_err(|(key, reason)| {
+                    MockDataError::InvalidValue {
+                        file: "ledger.json",
+                        key,
+                        reason,
+                    }
+                })?;
+                combined_hashmap.extend(objects);
             }
             combined_hashmap
         };
 
         let nfts = {
             let mut nft_map = HashMap::new();
-            let parsed_json: serde_json::Value =
-                serde_json::from_str(nfts_str).expect("Failed to parse NFT JSON");
-            for item in parsed_json.as_array().expect("NFT JSON not an array") {
-                let nft_id = item["nft_id"].as_str();
-                let owner = item["owner"].as_str();
-                let uri = item.get("uri");
-
-                if let (Some(id), Some(owner), Some(uri)) = (nft_id, owner, uri) {
-                    nft_map.insert(
-                        decode(id, Decodable::UINT256).expect("NFT file, bad nft_id"),
-                        (
-                            decode(owner, Decodable::ACCOUNT).expect("NFT file, bad owner"),
-                            uri.clone(),
-                        ),
-                    );
-                } else {
-                    panic!("NFT missing field(s)");
-                }
+            let entries: Vec<serde_json::Value> =
+                serde_json::from_value(parse_json("nfts.json", nfts_str)?).map_err(|error| {
+                    MockDataError::InvalidJson {
+                        file: "nfts.json",
+                        error,
+                    }
+                })?;
+            for (i, entry) in entries.iter().enumerate() {
+                let invalid = |reason: &str| MockDataError::InvalidValue {
+                    file: "nfts.json",
+                    key: entry["nft_id"]
+                        .as_str()
+                        .map_or_else(|| format!("#{}", i), str::to_string),
+                    reason: reason.to_string(),
+                };
+                let (Some(id), Some(owner), Some(uri)) = (
+                    entry["nft_id"].as_str(),
+                    entry["owner"].as_str(),
+                    entry.get("uri"),
+                ) else {
+                    return Err(invalid("NFT needs nft_id, owner and uri"));
+                };
+                let id = decode(id, Decodable::UINT256)
+                    .ok_or_else(|| invalid("nft_id is not a 256-bit hex NFTokenID"))?;
+                let owner = decode(owner, Decodable::ACCOUNT)
+                    .ok_or_else(|| invalid("owner is not a valid account address"))?;
+                nft_map.insert(id, (owner, uri.clone()));
             }
             nft_map
         };
 
-        MockData {
+        Ok(MockData {
             tx,
             hosting_ledger_obj,
             header,
             ledger,
             nfts,
-        }
+        })
+    }
+
+    /// Loads the state [`MockData::to_json`] saved.
+    pub fn from_json(value: &serde_json::Value) -> Result<Self, MockDataError> {
+        let file = |name: &str, empty: &str| {
+            value
+                .get(name)
+                .map_or_else(|| empty.to_string(), |v| v.to_string())
+        };
+        MockData::new(
+            &file("tx", "{}"),
+            &file("ledger_object", "{}"),
+            &file("ledger_header", "{}"),
+            &file("ledger", "[]"),
+            &file("nfts", "[]"),
+        )
+    }
+
+    /// The state as it stands now, with each fixture file's contents under its name without
+    /// `.json`.
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "tx": self.tx,
+            "ledger_object": self.get_current_ledger_obj(),
+            "ledger_header": self.header,
+            "ledger": self.get_ledger_json(),
+            "nfts": self.get_nfts_json(),
+        })
     }
 
     pub fn obj_exist(&self, keylet: &Keylet) -> bool {
@@ -142,10 +241,172 @@ impl MockData {
         self.header.get("parent_hash")
     }
 
+    pub fn get_base_fee(&self) -> Option<&serde_json::Value> {
+        self.header.get("base_fee")
+    }
+
+    pub fn get_amendments(&self) -> Option<&serde_json::Value> {
+        self.header.get("amendments")
+    }
+
+    /// The transaction's `ComputationAllowance`, the gas the sender is willing to pay for.
+    pub fn get_computation_allowance(&self) -> Option<u32> {
+        match self.tx.get("ComputationAllowance")? {
+            serde_json::Value::Number(n) => n.as_u64().and_then(|n| u32::try_from(n).ok()),
+            serde_json::Value::String(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+
     pub fn set_current_ledger_obj_data(&mut self, data: Vec<u8>) {
         self.hosting_ledger_obj["data"] = serde_json::Value::from(data);
     }
 
+    /// The current ledger object's data: whatever `update_data` last wrote, otherwise its `Data` field.
+    pub fn get_current_ledger_obj_data(&self) -> Option<Vec<u8>> {
+        if let Some(written) = self.hosting_ledger_obj.get("data") {
+            return serde_json::from_value(written.clone()).ok();
+        }
+        self.hosting_ledger_obj
+            .get("Data")
+            .and_then(|v| v.as_str())
+            .and_then(|s| hex::decode(s).ok())
+    }
+
+    /// The current ledger object as it stands now, with anything written by `update_data` in its
+    /// `Data` field, in the same form as `ledger_object.json`.
+    pub fn get_current_ledger_obj(&self) -> serde_json::Value {
+        let mut object = self.hosting_ledger_obj.clone();
+        if let Some(data) = self.get_current_ledger_obj_data()
+            && let Some(fields) = object.as_object_mut()
+        {
+            fields.remove("data");
+            fields.insert(
+                "Data".to_string(),
+                serde_json::Value::from(hex::encode_upper(data)),
+            );
+        }
+        object
+    }
+
+    pub fn get_tx(&self) -> &serde_json::Value {
+        &self.tx
+    }
+
+    pub fn get_header(&self) -> &serde_json::Value {
+        &self.header
+    }
+
+    /// IDs of every object in the ledger, in order.
+    pub fn ledger_keylets(&self) -> Vec<Keylet> {
+        let mut keylets: Vec<Keylet> = self.ledger.keys().cloned().collect();
+        keylets.sort();
+        keylets
+    }
+
+    /// The ledger in `ledger.json` form, each object under its ID, in ID order.
+    pub fn get_ledger_json(&self) -> serde_json::Value {
+        let entries = self
+            .ledger_keylets()
+            .into_iter()
+            .map(|keylet| {
+                let mut entry = serde_json::Map::new();
+                entry.insert(hex::encode_upper(&keylet), self.ledger[&keylet].clone());
+                serde_json::Value::Object(entry)
+            })
+            .collect();
+        serde_json::Value::Array(entries)
+    }
+
+    /// The NFTs in `nfts.json` form, in NFTokenID order.
+    pub fn get_nfts_json(&self) -> serde_json::Value {
+        let mut ids: Vec<&Hash256> = self.nfts.keys().collect();
+        ids.sort();
+        ids.into_iter()
+            .map(|id| {
+                let (owner, uri) = &self.nfts[id];
+                json!({
+                    "nft_id": hex::encode_upper(id),
+                    "owner": encode_base58(owner, &[0x0], Some(20)).unwrap_or_default(),
+                    "uri": uri,
+                })
+            })
+            .collect()
+    }
+
+    /// Replaces the transaction, e.g. with the next one of a scenario.
+    pub fn set_tx(&mut self, tx: serde_json::Value) {
+        self.tx = tx;
+    }
+
+    /// Replaces the current ledger object, e.g. with the one a previous run left behind.
+    pub fn set_current_ledger_obj(&mut self, object: serde_json::Value) {
+        self.hosting_ledger_obj = object;
+    }
+
+    /// Sets a field of the ledger header, e.g. to override `ledger_header.json` for one run.
+    pub fn set_header_field(&mut self, field: &str, value: serde_json::Value) {
+        if !self.header.is_object() {
+            self.header = serde_json::Value::Object(serde_json::Map::new());
+        }
+        self.header[field] = value;
+    }
+
+    /// Moves the ledger header forward by `ledgers` ledgers and `seconds` of close time.
+    pub fn advance_ledger(&mut self, ledgers: u32, seconds: u32) -> Result<(), String> {
+        for (field, by) in [("ledger_index", ledgers), ("parent_close_time", seconds)] {
+            if by == 0 {
+                continue;
+            }
+            let current = self
+                .header
+                .get(field)
+                .and_then(serde_json::Value::as_i64)
+                .ok_or_else(|| format!("ledger header has no numeric '{}' to advance", field))?;
+            self.header[field] = serde_json::Value::from(current + i64::from(by));
+        }
+        Ok(())
+    }
+
+    /// Adds the objects of a `ledger.json` entry, replacing any already stored under their IDs.
+    pub fn insert_ledger_entry(
+        &mut self,
+        entry: serde_json::Map<String, serde_json::Value>,
+    ) -> Result<(), String> {
+        let objects = ledger_entry_objects(entry)
+            .map_err(|(key, reason)| format!("'{}': {}", key, reason))?;
+        self.ledger.extend(objects);
+        Ok(())
+    }
+
+    /// The ledger object stored under `keylet`.
+    pub fn get_ledger_obj(&self, keylet: &Keylet) -> Option<&serde_json::Value> {
+        self.ledger.get(keylet)
+    }
+
+    /// Stores `object` under `keylet`, replacing any object already there.
+    pub fn set_ledger_obj(&mut self, keylet: Keylet, object: serde_json::Value) {
+        self.ledger.insert(keylet, object);
+    }
+
+    /// Removes a ledger object, returning whether it was there.
+    pub fn delete_ledger_obj(&mut self, keylet: &Keylet) -> bool {
+        self.ledger.remove(keylet).is_some()
+    }
+
+    /// Sets the `Balance` of an account's AccountRoot, which must be in the ledger.
+    pub fn set_balance(&mut self, account: &str, balance: serde_json::Value) -> Result<(), String> {
+        let account_id = decode(account, Decodable::ACCOUNT)
+            .ok_or_else(|| format!("'{}' is not a valid account address", account))?;
+        let keylet = index_hash(LedgerNameSpace::Account, &account_id);
+        let root = self
+            .ledger
+            .get_mut(&keylet)
+            .ok_or_else(|| format!("no AccountRoot for {} in the ledger", account))?;
+        root["Balance"] = balance;
+        Ok(())
+    }
+
     pub fn get_nft_uri(
         &self,
         nft_id: &Hash256,
@@ -163,3 +424,99 @@ impl MockData {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "2B6AC232AA4C4BE41BF49D2459FA4A0347E1B543A4C92FCEE0821C0201E2E9A8";
+
+    fn load(ledger: &str, nfts: &str) -> Result<MockData, MockDataError> {
+        MockData::new("{}", "{}", "{}", ledger, nfts)
+    }
+
+    #[test]
+    fn test_get_computation_allowance() {
+        let allowance = |tx: &str| {
+            MockData::new(tx, "{}", "{}", "[]", "[]")
+                .unwrap()
+                .get_computation_allowance()
+        };
+        assert_eq!(allowance(r#"{ "ComputationAllowance": 5000 }"#), Some(5000));
+        assert_eq!(
+            allowance(r#"{ "ComputationAllowance": "5000" }"#),
+            Some(5000)
+        );
+        assert_eq!(allowance(r#"{ "ComputationAllowance": 4294967296 }"#), None);
+        assert_eq!(allowance(r#"{ "ComputationAllowance": -1 }"#), None);
+        assert_eq!(allowance("{}"), None);
+    }
+
+    #[test]
+    fn test_new_reports_file_and_key() {
+        assert!(load(&format!(r#"[{{ "{ID}": {{}} }}]"#), "[]").is_ok());
+
+        let err = MockData::new("{", "{}", "{}", "[]", "[]").unwrap_err();
+        assert!(err.to_string().starts_with("tx.json: EOF while parsing"));
+
+        let err = load(r#"[{ "XYZ": {} }]"#, "[]").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ledger.json: 'XYZ': not a 256-bit hex ledger object ID"
+        );
+
+        let err = load("[]", &format!(r#"[{{ "nft_id": "{ID}", "owner": "r" }}]"#)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("nfts.json: '{ID}': NFT needs nft_id, owner and uri")
+        );
+    }
+
+    #[test]
+    fn test_ledger_objects_without_key_use_derived_keylet() {
+        let account = r#"{ "LedgerEntryType": "AccountRoot", "Account": "rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh" }"#;
+        let data = load(&format!("[{account}]"), "[]").unwrap();
+        assert!(data.obj_exist(&hex::decode(ID).unwrap()));
+
+        let other = "0000000000000000000000000000000000000000000000000000000000000001";
+        let err = load(&format!(r#"[{{ "{other}": {account} }}]"#), "[]").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "ledger.json: '{other}': does not match the ID {ID} derived from the AccountRoot's fields"
+            )
+        );
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let owner = "rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh";
+        let account = format!(r#"{{ "LedgerEntryType": "AccountRoot", "Account": "{owner}" }}"#);
+        let nfts = format!(r#"[{{ "nft_id": "{ID}", "owner": "{owner}", "uri": "68747470" }}]"#);
+        let data = load(&format!("[{account}]"), &nfts).unwrap();
+
+        let state = data.to_json();
+        assert_eq!(state["nfts"][0]["owner"], owner);
+        assert_eq!(MockData::from_json(&state).unwrap(), data);
+    }
+
+    #[test]
+    fn test_current_ledger_obj_carries_written_data() {
+        let escrow = r#"{ "LedgerEntryType": "Escrow", "Data": "01" }"#;
+        let mut data = MockData::new("{}", escrow, "{}", "[]", "[]").unwrap();
+        assert_eq!(data.get_current_ledger_obj()["Data"], "01");
+
+        data.set_current_ledger_obj_data(vec![0xAB, 0xCD]);
+        let object = data.get_current_ledger_obj();
+        assert_eq!(object["Data"], "ABCD");
+        assert_eq!(object["LedgerEntryType"], "Escrow");
+        assert!(object.get("data").is_none());
+
+        // The saved object loads back with the same data
+        let reloaded = MockData::new("{}", &object.to_string(), "{}", "[]", "[]").unwrap();
+        assert_eq!(
+            reloaded.get_current_ledger_obj_data(),
+            Some(vec![0xAB, 0xCD])
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use crate::data_provider::error_code_to_string;
+use serde_json::{Value, json};
+
+/// A line emitted by one of the `trace*` host functions.
+#[derive(Debug, Clone)]
+pub struct TraceLine {
+    /// The host function that produced the line, e.g. `trace_num`.
+    pub origin: &'static str,
+    pub message: String,
+}
+
+/// A negative result returned to the guest by a host function.
+#[derive(Debug, Clone)]
+pub struct HostCallError {
+    pub function: &'static str,
+    pub code: i32,
+}
+
+/// How often the guest called one host function, and the gas those calls were charged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostCallStats {
+    pub function: &'static str,
+    pub calls: u32,
+    pub gas: u64,
+}
+
+/// Everything the guest observed through host calls during a single run.
+#[derive(Debug, Default)]
+pub struct RunLog {
+    /// Print trace lines as they are emitted, in addition to recording them.
+    pub echo_traces: bool,
+    pub traces: Vec<TraceLine>,
+    pub host_errors: Vec<HostCallError>,
+    /// Per host function call counts and gas, in order of first call.
+    pub host_calls: Vec<HostCallStats>,
+}
+
+impl RunLog {
+    pub fn new(echo_traces: bool) -> Self {
+        Self {
+            echo_traces,
+            ..Default::default()
+        }
+    }
+
+    pub fn trace(&mut self, origin: &'static str, message: String) {
+        if self.echo_traces {
+            println!("WASM TRACE: {message}");
+        }
+        self.traces.push(TraceLine { origin, message });
+    }
+
+    /// Records a trace of an error code, echoed with an `[ERROR]` tag.
+    pub fn trace_error(&mut self, origin: &'static str, message: String) {
+        if self.echo_traces {
+            println!("WASM TRACE[ERROR]: {message}");
+        }
+        self.traces.push(TraceLine { origin, message });
+    }
+
+    pub fn record_host_call(&mut self, function: &'static str, gas: u32, result: i32) {
+        match self.host_calls.iter_mut().find(|s| s.function == function) {
+            Some(stats) => {
+                stats.calls += 1;
+                stats.gas += gas as u64;
+            }
+            None => self.host_calls.push(HostCallStats {
+                function,
+                calls: 1,
+                gas: gas as u64,
+            }),
+        }
+        if result < 0 {
+            self.host_errors.push(HostCallError {
+                function,
+                code: result,
+            });
+        }
+    }
+
+    pub fn traces_json(&self) -> Value {
+        self.traces
+            .iter()
+            .map(|t| json!({ "origin": t.origin, "message": t.message }))
+            .collect()
+    }
+
+    pub fn host_errors_json(&self) -> Value {
+        self.host_errors
+            .iter()
+            .map(|e| {
+                json!({
+                    "function": e.function,
+                    "code": e.code,
+                    "name": error_code_to_string(e.code as i64),
+                })
+            })
+            .collect()
+    }
+
+    /// Gas charged for host function calls, as opposed to executing Wasm instructions.
+    pub fn host_gas(&self) -> u64 {
+        self.host_calls.iter().map(|s| s.gas).sum()
+    }
+
+    /// Splits `gas_used` into instruction gas and the gas charged by each host function.
+    pub fn gas_profile_json(&self, gas_used: Option<u32>) -> Value {
+        let host_gas = self.host_gas();
+        json!({
+            "total": gas_used,
+            "instructions": gas_used.map(|total| (total as u64).saturating_sub(host_gas)),
+            "host_functions": self
+                .host_calls
+                .iter()
+                .map(|s| json!({ "function": s.function, "calls": s.calls, "gas": s.gas }))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_negative_results_are_recorded() {
+        let mut log = RunLog::new(false);
+        log.record_host_call("get_tx_field", 70, 20);
+        log.record_host_call("get_ledger_obj_field", 70, -2);
+        log.trace("trace_num", "count 3".to_string());
+
+        assert_eq!(
+            log.host_errors_json(),
+            json!([{
+                "function": "get_ledger_obj_field",
+                "code": -2,
+                "name": "FIELD_NOT_FOUND (-2)",
+            }])
+        );
+        assert_eq!(
+            log.traces_json(),
+            json!([{ "origin": "trace_num", "message": "count 3" }])
+        );
+    }
+
+    #[test]
+    fn test_gas_profile_splits_host_and_instruction_gas() {
+        let mut log = RunLog::new(false);
+        log.record_host_call("get_tx_field", 70, 20);
+        log.record_host_call("trace_num", 500, 0);
+        log.record_host_call("get_tx_field", 70, -2);
+
+        assert_eq!(
+            log.gas_profile_json(Some(1000)),
+            json!({
+                "total": 1000,
+                "instructions": 360,
+                "host_functions": [
+                    { "function": "get_tx_field", "calls": 2, "gas": 140 },
+                    { "function": "trace_num", "calls": 1, "gas": 500 },
+                ],
+            })
+        );
+        assert_eq!(log.gas_profile_json(None)["instructions"], Value::Null);
+    }
+}
@@ -0,0 +1,306 @@
+use crate::decoding::{Decodable, decode};
+use crate::expectations::Expectations;
+use crate::mock_data::MockData;
+use crate::run_log::RunLog;
+use crate::vm_wamr::{RunResult, VmError};
+use serde_json::{Map, Value};
+
+/// Name of the file that turns a fixture directory into a multi-transaction scenario.
+pub const SCENARIO_FILE: &str = "scenario.json";
+
+/// One transaction of a scenario, along with the ledger changes made before it runs.
+#[derive(Debug, Default)]
+pub struct Step {
+    pub name: String,
+    /// The transaction to run; the previous step's (or the fixture's `tx.json`) when absent.
+    pub tx: Option<Value>,
+    pub advance_ledgers: u32,
+    pub advance_time: u32,
+    /// `ledger.json` entries to add to the ledger.
+    pub insert: Vec<Map<String, Value>>,
+    /// IDs of ledger objects to remove.
+    pub delete: Vec<String>,
+    /// New `Balance`s of AccountRoots, by account address.
+    pub balances: Vec<(String, Value)>,
+    pub expectations: Expectations,
+}
+
+/// An ordered sequence of transactions run against one evolving ledger, loaded from a
+/// fixture's `scenario.json`. The fixture's other files give the starting state.
+#[derive(Debug)]
+pub struct Scenario {
+    pub steps: Vec<Step>,
+}
+
+/// How one step of a scenario went.
+pub struct StepRun {
+    pub name: String,
+    pub run: RunResult,
+    /// Each expectation of the step that the run did not meet.
+    pub failures: Vec<String>,
+}
+
+fn as_u32(value: &Value, field: &str) -> Result<u32, String> {
+    value
+        .as_u64()
+        .and_then(|n| u32::try_from(n).ok())
+        .ok_or_else(|| format!("'{}' must be a u32, got {}", field, value))
+}
+
+impl Step {
+    fn from_value(index: usize, value: &Value) -> Result<Self, String> {
+        let obj = value
+            .as_object()
+            .ok_or_else(|| "expected a JSON object".to_string())?;
+        let mut step = Step {
+            name: format!("step {}", index + 1),
+            ..Default::default()
+        };
+        for (key, value) in obj {
+            match key.as_str() {
+                "name" => {
+                    step.name = value
+                        .as_str()
+                        .ok_or_else(|| "'name' must be a string".to_string())?
+                        .to_string();
+                }
+                "tx" if value.is_object() => step.tx = Some(value.clone()),
+                "tx" => return Err("'tx' must be a transaction object".to_string()),
+                "advance_ledgers" => step.advance_ledgers = as_u32(value, key)?,
+                "advance_time" => step.advance_time = as_u32(value, key)?,
+                "insert" => {
+                    let entries = value
+                        .as_array()
+                        .ok_or_else(|| "'insert' must be an array of ledger objects".to_string())?;
+                    for entry in entries {
+                        let entry = entry.as_object().ok_or_else(|| {
+                            "'insert' must be an array of ledger objects".to_string()
+                        })?;
+                        step.insert.push(entry.clone());
+                    }
+                }
+                "delete" => {
+                    let ids = value
+                        .as_array()
+                        .ok_or_else(|| "'delete' must be an array of object IDs".to_string())?;
+                    for id in ids {
+                        let id = id
+                            .as_str()
+                            .filter(|id| decode(id, Decodable::UINT256).is_some())
+                            .ok_or_else(|| {
+                                format!("'{}' is not a 256-bit hex ledger object ID", id)
+                            })?;
+                        step.delete.push(id.to_string());
+                    }
+                }
+                "balances" => {
+                    let balances = value
+                        .as_object()
+                        .ok_or_else(|| "'balances' must map accounts to balances".to_string())?;
+                    for (account, balance) in balances {
+                        step.balances.push((account.clone(), balance.clone()));
+                    }
+                }
+                "expected" => {
+                    step.expectations = Expectations::from_value(value)
+                        .map_err(|e| format!("'expected': {}", e))?;
+                }
+                other => return Err(format!("unknown field '{}'", other)),
+            }
+        }
+        Ok(step)
+    }
+
+    /// Makes the step's ledger changes and sets its transaction.
+    pub fn apply(&self, state: &mut MockData) -> Result<(), String> {
+        state.advance_ledger(self.advance_ledgers, self.advance_time)?;
+        for entry in &self.insert {
+            state.insert_ledger_entry(entry.clone())?;
+        }
+        for id in &self.delete {
+            let keylet = decode(id, Decodable::UINT256).unwrap_or_default();
+            if !state.delete_ledger_obj(&keylet) {
+                return Err(format!(
+                    "cannot delete '{}': no such object in the ledger",
+                    id
+                ));
+            }
+        }
+        for (account, balance) in &self.balances {
+            state.set_balance(account, balance.clone())?;
+        }
+        if let Some(tx) = &self.tx {
+            state.set_tx(tx.clone());
+        }
+        Ok(())
+    }
+}
+
+impl Scenario {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let value: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let steps = value
+            .get("steps")
+            .and_then(Value::as_array)
+            .ok_or_else(|| "expected an object with a 'steps' array".to_string())?;
+        if steps.is_empty() {
+            return Err("'steps' is empty".to_string());
+        }
+        let steps = steps
+            .iter()
+            .enumerate()
+            .map(|(i, step)| {
+                Step::from_value(i, step).map_err(|e| format!("step {}: {}", i + 1, e))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Scenario { steps })
+    }
+
+    /// Runs every step in order with `run`, carrying the hosting ledger object, and so its
+    /// `Data`, from each step that returned a positive value into the next. Like rippled, a
+    /// step that returned anything else leaves the ledger object as it was.
+    ///
+    /// Fails if a step's ledger changes cannot be made. Stops after a step whose function could
+    /// not be run at all.
+    pub fn run(
+        &self,
+        mut state: MockData,
+        mut run: impl FnMut(MockData) -> Result<RunResult, VmError>,
+    ) -> Result<Vec<StepRun>, String> {
+        let mut runs = Vec::new();
+        for step in &self.steps {
+            step.apply(&mut state)
+                .map_err(|e| format!("{}: {}", step.name, e))?;
+            let result = match run(state.clone()) {
+                Ok(result) => result,
+                Err(e) => {
+                    // Later steps would fail the same way
+                    let result = RunResult {
+                        outcome: Err(e),
+                        gas_used: None,
+                        log: RunLog::default(),
+                        data: None,
+                        ledger_object: Value::Null,
+                    };
+                    runs.push(StepRun {
+                        name: step.name.clone(),
+                        failures: step.expectations.check(&result),
+                        run: result,
+                    });
+                    break;
+                }
+            };
+            if let Some(ledger_object) = result.kept_ledger_object() {
+                state.set_current_ledger_obj(ledger_object.clone());
+            }
+            runs.push(StepRun {
+                name: step.name.clone(),
+                failures: step.expectations.check(&result),
+                run: result,
+            });
+        }
+        Ok(runs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::returned;
+
+    const ACCOUNT: &str = "rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh";
+    const ACCOUNT_ROOT_ID: &str =
+        "2B6AC232AA4C4BE41BF49D2459FA4A0347E1B543A4C92FCEE0821C0201E2E9A8";
+
+    fn state() -> MockData {
+        MockData::new(
+            r#"{ "TransactionType": "EscrowFinish", "Sequence": 1 }"#,
+            r#"{ "LedgerEntryType": "Escrow", "Data": "00" }"#,
+            r#"{ "ledger_index": 10, "parent_close_time": 1000 }"#,
+            &format!(r#"[{{ "LedgerEntryType": "AccountRoot", "Account": "{ACCOUNT}", "Balance": "5" }}]"#),
+            "[]",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_from_json_rejects_bad_steps() {
+        assert!(Scenario::from_json(r#"{ "steps": [] }"#).is_err());
+        assert_eq!(
+            Scenario::from_json(r#"{ "steps": [{}, { "advance_ledgers": -1 }] }"#).unwrap_err(),
+            "step 2: 'advance_ledgers' must be a u32, got -1"
+        );
+        assert_eq!(
+            Scenario::from_json(r#"{ "steps": [{ "expected": { "return": 1 } }] }"#).unwrap_err(),
+            "step 1: 'expected': unknown field 'return'"
+        );
+    }
+
+    #[test]
+    fn test_run_carries_state_between_steps() {
+        let scenario = Scenario::from_json(&format!(
+            r#"{{ "steps": [
+                {{ "name": "first", "expected": {{ "return_value": 1, "data": "01" }} }},
+                {{
+                    "advance_ledgers": 2,
+                    "advance_time": 30,
+                    "tx": {{ "TransactionType": "EscrowFinish", "Sequence": 2 }},
+                    "balances": {{ "{ACCOUNT}": "7" }},
+                    "expected": {{ "return_value": 0, "data": "02" }}
+                }},
+                {{ "delete": ["{ACCOUNT_ROOT_ID}"] }}
+            ] }}"#
+        ))
+        .unwrap();
+
+        // Counts its runs in Data, rejects the second finish and reports what it saw
+        let mut seen = Vec::new();
+        let runs = scenario
+            .run(state(), |mut data| {
+                let count = data.get_current_ledger_obj_data().unwrap()[0] + 1;
+                data.set_current_ledger_obj_data(vec![count]);
+                seen.push((
+                    data.get_ledger_sqn().cloned(),
+                    data.get_parent_ledger_time().cloned(),
+                    data.obj_exist(&hex::decode(ACCOUNT_ROOT_ID).unwrap()),
+                ));
+                Ok(RunResult {
+                    data: data.get_current_ledger_obj_data(),
+                    ledger_object: data.get_current_ledger_obj(),
+                    ..returned((seen.len() != 2) as i32)
+                })
+            })
+            .unwrap();
+
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[0].name, "first");
+        assert_eq!(runs[2].name, "step 3");
+        assert!(runs.iter().all(|r| r.failures.is_empty()));
+        // The rejected second step's write was not kept
+        assert_eq!(runs[2].run.data, Some(vec![2]));
+        assert_eq!(
+            seen,
+            vec![
+                (Some(Value::from(10)), Some(Value::from(1000)), true),
+                (Some(Value::from(12)), Some(Value::from(1030)), true),
+                (Some(Value::from(12)), Some(Value::from(1030)), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_reports_ledger_changes_that_cannot_be_made() {
+        let scenario = Scenario::from_json(
+            r#"{ "steps": [{ "balances": { "rPEPPER7kfTD9w2To4CQk6UCfuHM9c6GDY": "1" } }] }"#,
+        )
+        .unwrap();
+        let err = scenario
+            .run(state(), |_| unreachable!("the step never runs"))
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            "step 1: no AccountRoot for rPEPPER7kfTD9w2To4CQk6UCfuHM9c6GDY in the ledger"
+        );
+    }
+}
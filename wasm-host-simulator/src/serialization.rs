@@ -0,0 +1,463 @@
+use crate::decoding::{
+    ACCOUNT_ID_LEN, CURRENCY_LEN, Decodable, MPT_ID_LEN, SField_To_Name, decode,
+    decode_amount_json, decode_currency, decode_issue_json,
+};
+use bigdecimal::{BigDecimal, ToPrimitive};
+use lazy_static::lazy_static;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::str::FromStr;
+use xrpl::core::binarycodec::definitions::{get_ledger_entry_type_code, get_transaction_type_code};
+
+pub const OBJECT_END_MARKER: u8 = 0xE1;
+pub const ARRAY_END_MARKER: u8 = 0xF1;
+
+const PATH_SEPARATOR: u8 = 0xFF;
+const PATHSET_END: u8 = 0x00;
+const PATH_STEP_ACCOUNT: u8 = 0x01;
+const PATH_STEP_CURRENCY: u8 = 0x10;
+const PATH_STEP_ISSUER: u8 = 0x20;
+
+/// UInt64 fields rippled renders in base 10 in JSON; all others are hex.
+const BASE_TEN_UINT64_FIELDS: [&str; 4] = [
+    "MaximumAmount",
+    "OutstandingAmount",
+    "MPTAmount",
+    "LockedAmount",
+];
+
+lazy_static! {
+    static ref SField_By_Name: HashMap<&'static str, i32> = SField_To_Name
+        .iter()
+        .map(|(code, name)| (name.as_str(), *code))
+        .collect();
+}
+
+pub fn sfield_code(name: &str) -> Option<i32> {
+    SField_By_Name.get(name).copied()
+}
+
+const STI_OBJECT: i32 = 14;
+const STI_ARRAY: i32 = 15;
+
+fn type_code(field: i32) -> i32 {
+    field >> 16
+}
+
+fn nth(field: i32) -> i32 {
+    field & 0xFFFF
+}
+
+/// Whether the field is part of the binary format. `hash`, `index` and the high-level
+/// `Transaction`/`LedgerEntry` types only exist in JSON.
+fn is_serialized(field: i32) -> bool {
+    (1..256).contains(&type_code(field)) && (1..256).contains(&nth(field))
+}
+
+/// The field ID that precedes a field's value, one to three bytes depending on how large its
+/// type and field codes are.
+pub fn field_header(field: i32) -> Vec<u8> {
+    let (type_code, nth) = (type_code(field) as u8, nth(field) as u8);
+    match (type_code < 16, nth < 16) {
+        (true, true) => vec![(type_code << 4) | nth],
+        (true, false) => vec![type_code << 4, nth],
+        (false, true) => vec![nth, type_code],
+        (false, false) => vec![0, type_code, nth],
+    }
+}
+
+/// Prefixes `data` with its variable-length encoded size.
+pub fn encode_vl(data: &[u8]) -> Result<Vec<u8>, String> {
+    let len = data.len();
+    let mut bytes = match len {
+        0..=192 => vec![len as u8],
+        193..=12480 => {
+            let len = len - 193;
+            vec![193 + (len >> 8) as u8, (len & 0xFF) as u8]
+        }
+        12481..=918744 => {
+            let len = len - 12481;
+            vec![
+                241 + (len >> 16) as u8,
+                ((len >> 8) & 0xFF) as u8,
+                (len & 0xFF) as u8,
+            ]
+        }
+        _ => return Err(format!("{} bytes is too long for a VL field", len)),
+    };
+    bytes.extend_from_slice(data);
+    Ok(bytes)
+}
+
+fn field_name(field: i32) -> &'static str {
+    SField_To_Name.get(&field).map_or("?", String::as_str)
+}
+
+fn invalid(field: i32, value: &Value) -> String {
+    format!("{} cannot be serialized from {}", field_name(field), value)
+}
+
+fn parse_uint(field: i32, value: &Value, radix: u32) -> Result<u64, String> {
+    match value {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => u64::from_str_radix(s, radix).ok(),
+        _ => None,
+    }
+    .ok_or_else(|| invalid(field, value))
+}
+
+fn parse_hex(field: i32, value: &Value, len: Option<usize>) -> Result<Vec<u8>, String> {
+    value
+        .as_str()
+        .and_then(|s| hex::decode(s).ok())
+        .filter(|bytes| len.is_none_or(|len| bytes.len() == len))
+        .ok_or_else(|| invalid(field, value))
+}
+
+fn parse_account(field: i32, value: &Value) -> Result<Vec<u8>, String> {
+    value
+        .as_str()
+        .and_then(|s| decode(s, Decodable::ACCOUNT))
+        .ok_or_else(|| invalid(field, value))
+}
+
+/// A UInt16, UInt32 or UInt8 value, also accepting the names of transaction and ledger entry types.
+fn parse_small_uint(field: i32, value: &Value, max: u64) -> Result<u64, String> {
+    let by_name = match (SField_To_Name.get(&field).map(String::as_str), value) {
+        (Some("TransactionType"), Value::String(s)) => get_transaction_type_code(s).copied(),
+        (Some("LedgerEntryType"), Value::String(s)) => get_ledger_entry_type_code(s).copied(),
+        _ => None,
+    };
+    match by_name {
+        Some(code) => Ok(code as u16 as u64),
+        None => parse_uint(field, value, 10),
+    }
+    .and_then(|n| {
+        if n <= max {
+            Ok(n)
+        } else {
+            Err(invalid(field, value))
+        }
+    })
+}
+
+/// rippled's `Number`: a mantissa normalized to 16 significant digits and a base-10 exponent.
+fn serialize_number(field: i32, value: &Value) -> Result<Vec<u8>, String> {
+    const MIN_MANTISSA: i64 = 1_000_000_000_000_000;
+
+    let text = match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        _ => return Err(invalid(field, value)),
+    };
+    let decimal = BigDecimal::from_str(&text).map_err(|_| invalid(field, value))?;
+    let (mantissa, exponent) = if decimal == BigDecimal::from(0) {
+        (0i64, i32::MIN)
+    } else {
+        // Rounded to at most 16 digits, then scaled up to exactly 16
+        let (digits, scale) = decimal.with_prec(16).as_bigint_and_exponent();
+        let mut mantissa = digits.to_i64().ok_or_else(|| invalid(field, value))?;
+        let mut exponent = i32::try_from(-scale).map_err(|_| invalid(field, value))?;
+        while mantissa.abs() < MIN_MANTISSA {
+            mantissa *= 10;
+            exponent -= 1;
+        }
+        (mantissa, exponent)
+    };
+    Ok([mantissa.to_be_bytes().as_slice(), &exponent.to_be_bytes()].concat())
+}
+
+fn serialize_pathset(field: i32, value: &Value) -> Result<Vec<u8>, String> {
+    let paths = value.as_array().ok_or_else(|| invalid(field, value))?;
+    let mut bytes = Vec::new();
+    for (i, path) in paths.iter().enumerate() {
+        if i > 0 {
+            bytes.push(PATH_SEPARATOR);
+        }
+        for step in path.as_array().ok_or_else(|| invalid(field, value))? {
+            let mut kind = 0;
+            let mut data = Vec::new();
+            if let Some(account) = step.get("account") {
+                kind |= PATH_STEP_ACCOUNT;
+                data.extend(parse_account(field, account)?);
+            }
+            if let Some(currency) = step.get("currency") {
+                kind |= PATH_STEP_CURRENCY;
+                let currency = currency
+                    .as_str()
+                    .and_then(decode_currency)
+                    .filter(|c| c.len() == CURRENCY_LEN)
+                    .ok_or_else(|| invalid(field, value))?;
+                data.extend(currency);
+            }
+            if let Some(issuer) = step.get("issuer") {
+                kind |= PATH_STEP_ISSUER;
+                data.extend(parse_account(field, issuer)?);
+            }
+            bytes.push(kind);
+            bytes.extend(data);
+        }
+    }
+    bytes.push(PATHSET_END);
+    Ok(bytes)
+}
+
+/// An `Issue`: the currency alone for XRP, currency and issuer for tokens, and for MPTs the
+/// issuer, the reserved `noAccount` ID and the issuance sequence.
+fn serialize_issue(field: i32, value: &Value) -> Result<Vec<u8>, String> {
+    let bytes = decode_issue_json(value.clone()).ok_or_else(|| invalid(field, value))?;
+    if value.get("mpt_issuance_id").is_none() {
+        return Ok(bytes);
+    }
+    if bytes.len() != MPT_ID_LEN {
+        return Err(invalid(field, value));
+    }
+    let (sequence, issuer) = bytes.split_at(4);
+    let mut no_account = [0u8; ACCOUNT_ID_LEN];
+    no_account[ACCOUNT_ID_LEN - 1] = 1;
+    Ok([issuer, &no_account, sequence].concat())
+}
+
+fn serialize_xchain_bridge(field: i32, value: &Value) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    for (door, issue) in [
+        ("LockingChainDoor", "LockingChainIssue"),
+        ("IssuingChainDoor", "IssuingChainIssue"),
+    ] {
+        let door = value.get(door).ok_or_else(|| invalid(field, value))?;
+        bytes.extend(encode_vl(&parse_account(field, door)?)?);
+        let issue = value.get(issue).ok_or_else(|| invalid(field, value))?;
+        bytes.extend(serialize_issue(field, issue)?);
+    }
+    Ok(bytes)
+}
+
+/// The canonical binary form of `value` as field `field`, without the field header, exactly as
+/// rippled's `STBase::add` writes it.
+///
+/// Blobs and account IDs carry their length prefix. Objects and arrays do not carry their end
+/// marker, which is written by the enclosing object along with the field header.
+pub fn serialize_value(field: i32, value: &Value) -> Result<Vec<u8>, String> {
+    match Decodable::from_sfield(field) {
+        Decodable::UINT8 => Ok(vec![parse_small_uint(field, value, u8::MAX as u64)? as u8]),
+        Decodable::UINT16 | Decodable::Uint16_TX_TYPE | Decodable::UINT16_LEDGER_OBJECT_TYPE => {
+            let n = parse_small_uint(field, value, u16::MAX as u64)?;
+            Ok((n as u16).to_be_bytes().to_vec())
+        }
+        Decodable::UINT32 => {
+            let n = parse_small_uint(field, value, u32::MAX as u64)?;
+            Ok((n as u32).to_be_bytes().to_vec())
+        }
+        Decodable::UINT64 => {
+            let radix = if BASE_TEN_UINT64_FIELDS.contains(&field_name(field)) {
+                10
+            } else {
+                16
+            };
+            Ok(parse_uint(field, value, radix)?.to_be_bytes().to_vec())
+        }
+        Decodable::UINT128 => parse_hex(field, value, Some(16)),
+        Decodable::UINT160 => parse_hex(field, value, Some(20)),
+        Decodable::UINT96 => parse_hex(field, value, Some(12)),
+        Decodable::UINT192 => parse_hex(field, value, Some(24)),
+        Decodable::UINT256 => parse_hex(field, value, Some(32)),
+        Decodable::UINT384 => parse_hex(field, value, Some(48)),
+        Decodable::UINT512 => parse_hex(field, value, Some(64)),
+        Decodable::AMOUNT => decode_amount_json(value.clone()).ok_or_else(|| invalid(field, value)),
+        Decodable::VL_HEX | Decodable::VL_OTHER => encode_vl(&parse_hex(field, value, None)?),
+        Decodable::ACCOUNT => encode_vl(&parse_account(field, value)?),
+        Decodable::NUMBER => serialize_number(field, value),
+        Decodable::OBJECT => {
+            let object = value.as_object().ok_or_else(|| invalid(field, value))?;
+            serialize_object(object)
+        }
+        Decodable::ARRAY => {
+            let elements = value.as_array().ok_or_else(|| invalid(field, value))?;
+            let mut bytes = Vec::new();
+            for element in elements {
+                // Each element is a single-field object wrapping an inner object, e.g. {"Memo": {...}}
+                let inner = element.as_object().filter(|o| o.len() == 1);
+                let (name, object) = inner
+                    .and_then(|o| o.iter().next())
+                    .ok_or_else(|| invalid(field, value))?;
+                let element_field = sfield_code(name)
+                    .filter(|f| type_code(*f) == STI_OBJECT)
+                    .ok_or_else(|| format!("{} is not an inner object field", name))?;
+                bytes.extend(serialize_field(element_field, object)?);
+            }
+            Ok(bytes)
+        }
+        Decodable::VECTOR256 => {
+            let hashes = value.as_array().ok_or_else(|| invalid(field, value))?;
+            let mut data = Vec::with_capacity(hashes.len() * 32);
+            for hash in hashes {
+                data.extend(parse_hex(field, hash, Some(32))?);
+            }
+            encode_vl(&data)
+        }
+        Decodable::PATHSET => serialize_pathset(field, value),
+        Decodable::ISSUE => serialize_issue(field, value),
+        Decodable::XCHAIN_BRIDGE => serialize_xchain_bridge(field, value),
+        Decodable::CURRENCY => value
+            .as_str()
+            .and_then(decode_currency)
+            .ok_or_else(|| invalid(field, value)),
+        Decodable::AS_IS | Decodable::NOT => {
+            Err(format!("{} has no binary serialization", field_name(field)))
+        }
+    }
+}
+
+/// A field header followed by the field's canonical value, and the end marker of an object or
+/// array.
+pub fn serialize_field(field: i32, value: &Value) -> Result<Vec<u8>, String> {
+    let mut bytes = field_header(field);
+    bytes.extend(serialize_value(field, value)?);
+    match type_code(field) {
+        STI_OBJECT => bytes.push(OBJECT_END_MARKER),
+        STI_ARRAY => bytes.push(ARRAY_END_MARKER),
+        _ => {}
+    }
+    Ok(bytes)
+}
+
+/// The fields of an STObject in canonical order, without the object end marker.
+///
+/// Keys that are not serialized SFields (`index`, `hash`, annotations, ...) are skipped, as they
+/// never appear in rippled's binary form.
+pub fn serialize_object(object: &Map<String, Value>) -> Result<Vec<u8>, String> {
+    let mut fields: Vec<(i32, &Value)> = object
+        .iter()
+        .filter_map(|(name, value)| sfield_code(name).map(|code| (code, value)))
+        .filter(|(code, _)| is_serialized(*code))
+        .collect();
+    fields.sort_by_key(|(code, _)| *code);
+
+    let mut bytes = Vec::new();
+    for (code, value) in fields {
+        bytes.extend(serialize_field(code, value)?);
+    }
+    Ok(bytes)
+}
+
+/// What to hand the guest for a field, following rippled's host functions: UInt16 and UInt32 in
+/// native (little-endian) byte order, account IDs and blobs without their length prefix, MPT
+/// issues as their bare issuance ID, and every other type in its canonical form.
+///
+/// Inner objects come back as their serialized fields and arrays as their serialized elements,
+/// neither with an end marker. `value` may also be one element of an array or Vector256, picked
+/// out by a locator index.
+pub fn field_bytes(field: i32, value: &Value) -> Result<Vec<u8>, String> {
+    match Decodable::from_sfield(field) {
+        Decodable::UINT16 | Decodable::Uint16_TX_TYPE | Decodable::UINT16_LEDGER_OBJECT_TYPE => {
+            parse_small_uint(field, value, u16::MAX as u64)
+                .map(|n| (n as u16).to_le_bytes().to_vec())
+        }
+        Decodable::UINT32 => parse_small_uint(field, value, u32::MAX as u64)
+            .map(|n| (n as u32).to_le_bytes().to_vec()),
+        Decodable::ACCOUNT => parse_account(field, value),
+        Decodable::VL_HEX | Decodable::VL_OTHER => parse_hex(field, value, None),
+        Decodable::ISSUE => decode_issue_json(value.clone()).ok_or_else(|| invalid(field, value)),
+        // An array element is a single-field object wrapping the inner object
+        Decodable::ARRAY if value.is_object() => serialize_object(value.as_object().unwrap()),
+        Decodable::VECTOR256 if value.is_string() => parse_hex(field, value, Some(32)),
+        _ => serialize_value(field, value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn code(name: &str) -> i32 {
+        sfield_code(name).unwrap()
+    }
+
+    #[test]
+    fn test_field_header() {
+        assert_eq!(field_header(code("Flags")), vec![0x22]);
+        assert_eq!(field_header(code("OracleDocumentID")), vec![0x20, 0x33]);
+        assert_eq!(field_header(code("TickSize")), vec![0x00, 0x10, 0x10]);
+        assert_eq!(field_header(code("CloseResolution")), vec![0x01, 0x10]);
+    }
+
+    #[test]
+    fn test_encode_vl() {
+        assert_eq!(encode_vl(&[0xAB; 3]).unwrap()[..1], [3]);
+        assert_eq!(encode_vl(&[0; 193]).unwrap()[..2], [193, 0]);
+        assert_eq!(encode_vl(&[0; 12481]).unwrap()[..3], [241, 0, 0]);
+    }
+
+    #[test]
+    fn test_field_bytes_follow_rippled() {
+        let bytes = |name: &str, value: Value| field_bytes(code(name), &value).unwrap();
+
+        // UInt16 and UInt32 are copied in native byte order, UInt64 is serialized big-endian
+        assert_eq!(bytes("Sequence", json!(5)), vec![5, 0, 0, 0]);
+        assert_eq!(bytes("TransferFee", json!(1)), vec![1, 0]);
+        assert_eq!(
+            bytes("AssetPrice", json!("2e4")),
+            vec![0, 0, 0, 0, 0, 0, 0x02, 0xE4]
+        );
+        assert_eq!(
+            bytes("MPTAmount", json!("200")),
+            200u64.to_be_bytes().to_vec()
+        );
+        assert_eq!(bytes("TickSize", json!(5)), vec![5]);
+        assert_eq!(bytes("Domain", json!("ABCD")), vec![0xAB, 0xCD]);
+        assert_eq!(
+            bytes("Indexes", json!(["00".repeat(32)])),
+            [vec![32], vec![0; 32]].concat()
+        );
+        assert_eq!(
+            bytes("Number", json!("0")),
+            [0i64.to_be_bytes().to_vec(), i32::MIN.to_be_bytes().to_vec()].concat()
+        );
+        assert_eq!(
+            bytes("Number", json!("-1.5")),
+            [
+                (-1_500_000_000_000_000i64).to_be_bytes().to_vec(),
+                (-15i32).to_be_bytes().to_vec()
+            ]
+            .concat()
+        );
+        assert_eq!(bytes("Amendments", json!("11".repeat(32))), vec![0x11; 32]);
+        assert!(field_bytes(code("Sequence"), &json!("x")).is_err());
+    }
+
+    #[test]
+    fn test_serialize_object_is_canonical() {
+        let memo_fields = vec![0x7C, 0x01, 0x01, 0x7D, 0x02, 0x02, 0x03];
+        let memo_element = [vec![0xEA], memo_fields.clone(), vec![OBJECT_END_MARKER]].concat();
+        let memo = json!({ "MemoType": "01", "MemoData": "0203" });
+        let memos = json!([{ "Memo": memo }]);
+
+        assert_eq!(
+            serialize_field(code("Memos"), &memos).unwrap(),
+            [vec![0xF9], memo_element.clone(), vec![ARRAY_END_MARKER]].concat()
+        );
+        // Arrays, their elements and inner objects are returned without the enclosing end marker
+        assert_eq!(field_bytes(code("Memos"), &memos).unwrap(), memo_element);
+        assert_eq!(field_bytes(code("Memos"), &memos[0]).unwrap(), memo_element);
+        assert_eq!(field_bytes(code("Memo"), &memo).unwrap(), memo_fields);
+
+        // Fields are ordered by type then field code; `index` is not serialized
+        let object = json!({
+            "index": "00",
+            "Account": "rrrrrrrrrrrrrrrrrrrrrhoLvTp",
+            "Sequence": 1,
+            "Flags": 0,
+        });
+        assert_eq!(
+            serialize_object(object.as_object().unwrap()).unwrap(),
+            [
+                vec![0x22, 0, 0, 0, 0],
+                vec![0x24, 0, 0, 0, 1],
+                vec![0x81, 0x14],
+                vec![0; 20],
+            ]
+            .concat()
+        );
+    }
+}
@@ -0,0 +1,745 @@
+use crate::data_provider::HostConditions;
+use crate::debugger::Debugger;
+use crate::determinism::{self, DeterminismReport};
+use crate::expectations::{self, EXPECTED_FILE, Expectations};
+use crate::fixture_check::{self, Severity};
+use crate::fuzz::{self, FoundCase};
+use crate::limits::{self, Limits, Violation};
+use crate::mock_data::MockData;
+use crate::scenario::{SCENARIO_FILE, Scenario, StepRun};
+use crate::sweep::{Sweep, SweepReport};
+use crate::tape::{Recorder, ReplayReport, Replayer, Tape};
+use crate::vm_wamr::{self, RunResult, VmError};
+use log::{error, info, warn};
+use serde_json::{Value, json};
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::OnceLock;
+use wamr_rust_sdk::RuntimeError;
+
+/// The largest gas cap rippled allows for a single extension run.
+pub const DEFAULT_GAS_CAP: u32 = 1_000_000;
+
+/// Outcome reported when the fixture could not be loaded, so the function never ran.
+pub const SETUP_ERROR: &str = "setup_error";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GasCap {
+    Unlimited,
+    Limit(u32),
+}
+
+pub fn parse_gas_cap(s: &str) -> Result<GasCap, String> {
+    if s.eq_ignore_ascii_case("none") {
+        return Ok(GasCap::Unlimited);
+    }
+    s.parse()
+        .map(GasCap::Limit)
+        .map_err(|_| format!("expected a number of gas units or 'none', got '{}'", s))
+}
+
+pub fn parse_hash(s: &str) -> Result<String, String> {
+    match hex::decode(s) {
+        Ok(bytes) if bytes.len() == 32 => Ok(s.to_uppercase()),
+        _ => Err(format!(
+            "expected a 256-bit hash as 64 hex characters, got '{}'",
+            s
+        )),
+    }
+}
+
+/// Ledger header values to use instead of a fixture's `ledger_header.json`.
+#[derive(Debug, Clone, Default)]
+pub struct HeaderOverrides {
+    pub ledger_seq: Option<u32>,
+    /// Parent close time, in seconds since the Ripple epoch.
+    pub close_time: Option<u32>,
+    /// Parent ledger hash, as 64 hex characters.
+    pub parent_hash: Option<String>,
+}
+
+impl HeaderOverrides {
+    pub fn apply(&self, data: &mut MockData) {
+        if let Some(seq) = self.ledger_seq {
+            info!("Ledger sequence: {}", seq);
+            data.set_header_field("ledger_index", json!(seq));
+        }
+        if let Some(time) = self.close_time {
+            info!("Parent close time: {}", time);
+            data.set_header_field("parent_close_time", json!(time));
+        }
+        if let Some(hash) = &self.parent_hash {
+            info!("Parent hash: {}", hash);
+            data.set_header_field("parent_hash", json!(hash));
+        }
+    }
+}
+
+/// A test case loaded from its fixture directory.
+pub struct Fixture {
+    pub name: String,
+    pub dir: PathBuf,
+    pub data: MockData,
+    pub expectations: Option<Expectations>,
+    /// The transactions to run in turn, if the fixture has a `scenario.json`.
+    pub scenario: Option<Scenario>,
+}
+
+fn read_fixture_file(dir: &Path, file: &str) -> Result<String, String> {
+    let path = dir.join(file);
+    fs::read_to_string(&path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))
+}
+
+impl Fixture {
+    /// Validates and loads the fixture directory `dir` of test case `name`, taking the hosting
+    /// ledger object from `state_in` when given.
+    pub fn load(dir: &Path, name: &str, state_in: Option<&Path>) -> Result<Self, String> {
+        if !dir.exists() {
+            return Err(format!(
+                "Test case '{}' not found at expected location: {}",
+                name,
+                dir.display()
+            ));
+        }
+        let tx_json = read_fixture_file(dir, "tx.json")?;
+        let lo_json = match state_in {
+            Some(state_in) => fs::read_to_string(state_in)
+                .map_err(|e| format!("Cannot read state {}: {}", state_in.display(), e))?,
+            None => read_fixture_file(dir, "ledger_object.json")?,
+        };
+        let lh_json = read_fixture_file(dir, "ledger_header.json")?;
+        let l_json = read_fixture_file(dir, "ledger.json")?;
+        let nft_json = read_fixture_file(dir, "nfts.json")?;
+
+        let diagnostics = fixture_check::check_fixture(dir);
+        for diagnostic in &diagnostics {
+            match diagnostic.severity {
+                Severity::Error => error!("{}", diagnostic),
+                Severity::Warning => warn!("{}", diagnostic),
+            }
+        }
+        if let Some(first) = diagnostics.iter().find(|d| d.severity == Severity::Error) {
+            return Err(format!("Invalid fixture '{}': {}", name, first));
+        }
+
+        let data = MockData::new(&tx_json, &lo_json, &lh_json, &l_json, &nft_json)
+            .map_err(|e| format!("Invalid fixture '{}': {}", name, e))?;
+
+        let path = dir.join(EXPECTED_FILE);
+        let expectations = if path.exists() {
+            let json = read_fixture_file(dir, EXPECTED_FILE)?;
+            let expectations = Expectations::from_json(&json)
+                .map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+            Some(expectations)
+        } else {
+            None
+        };
+
+        let path = dir.join(SCENARIO_FILE);
+        let scenario = if path.exists() {
+            let json = read_fixture_file(dir, SCENARIO_FILE)?;
+            let scenario = Scenario::from_json(&json)
+                .map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+            Some(scenario)
+        } else {
+            None
+        };
+
+        Ok(Fixture {
+            name: name.to_string(),
+            dir: dir.to_path_buf(),
+            data,
+            expectations,
+            scenario,
+        })
+    }
+}
+
+/// Runs one function of a WASM module against fixtures, in the host environment rippled
+/// provides to extensions.
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    wasm_file: String,
+    function: String,
+    gas_cap: Option<GasCap>,
+    echo_traces: bool,
+    limits: Limits,
+    /// How the module goes beyond `limits`, found before its first run.
+    violations: OnceLock<Vec<Violation>>,
+}
+
+/// How a test case went.
+pub enum TestOutcome {
+    /// The function ran once; `failures` lists each expectation the run did not meet.
+    Run {
+        run: RunResult,
+        failures: Vec<String>,
+    },
+    /// Each step of a scenario, in order.
+    Scenario(Vec<StepRun>),
+    /// The test case could not be set up, or the function could not be run at all.
+    Failed {
+        /// `setup_error`, or the [`VmError::kind`] of the failure.
+        outcome: &'static str,
+        error: String,
+    },
+}
+
+/// The result of running a test case, as reported by `--output json`.
+pub struct TestReport {
+    pub function: String,
+    pub test_case: String,
+    pub outcome: TestOutcome,
+}
+
+impl Simulator {
+    /// A simulator running `finish` in the module at `wasm_file`, capped at the fixture's
+    /// `ComputationAllowance`.
+    pub fn new(wasm_file: &Path) -> Result<Self, String> {
+        if !wasm_file.is_file() {
+            return Err(format!("WASM module not found: {}", wasm_file.display()));
+        }
+        Ok(Simulator {
+            wasm_file: wasm_file.to_string_lossy().to_string(),
+            function: "finish".to_string(),
+            gas_cap: None,
+            echo_traces: false,
+            limits: Limits::default(),
+            violations: OnceLock::new(),
+        })
+    }
+
+    /// The exported function to run.
+    pub fn function(mut self, function: &str) -> Self {
+        self.function = function.to_string();
+        self
+    }
+
+    /// Gas cap for every run. When unset, each run is capped at its transaction's
+    /// `ComputationAllowance`, then at rippled's maximum.
+    pub fn gas_cap(mut self, gas_cap: Option<GasCap>) -> Self {
+        self.gas_cap = gas_cap;
+        self
+    }
+
+    /// Print trace lines as test cases run them, as well as recording them.
+    pub fn echo_traces(mut self, echo_traces: bool) -> Self {
+        self.echo_traces = echo_traces;
+        self
+    }
+
+    /// Limits the module must be within to be run at all, and the stack it is run with.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self.violations = OnceLock::new();
+        self
+    }
+
+    pub fn function_name(&self) -> &str {
+        &self.function
+    }
+
+    /// The gas cap for running against `data`.
+    pub fn gas_cap_for(&self, data: &MockData) -> Option<u32> {
+        match self.gas_cap {
+            Some(GasCap::Unlimited) => None,
+            Some(GasCap::Limit(cap)) => Some(cap),
+            None => Some(data.get_computation_allowance().unwrap_or(DEFAULT_GAS_CAP)),
+        }
+    }
+
+    /// Checks the module against the limits, reading and scanning it only before the first run.
+    fn check_limits(&self) -> Result<(), VmError> {
+        let violations = match self.violations.get() {
+            Some(violations) => violations,
+            None => {
+                let wasm = fs::read(&self.wasm_file).map_err(RuntimeError::WasmFileFSError)?;
+                self.violations
+                    .get_or_init(|| limits::check(&wasm, &self.limits))
+            }
+        };
+        if violations.is_empty() {
+            return Ok(());
+        }
+        Err(VmError::LimitsExceeded {
+            profile: self.limits.name,
+            violations: violations.clone(),
+        })
+    }
+
+    fn call(
+        &self,
+        data: MockData,
+        conditions: HostConditions,
+        echo_traces: bool,
+    ) -> Result<RunResult, VmError> {
+        self.check_limits()?;
+        let gas_cap = self.gas_cap_for(&data);
+        vm_wamr::run_func_with_conditions(
+            self.wasm_file.clone(),
+            &self.function,
+            gas_cap,
+            data,
+            echo_traces,
+            conditions,
+            self.limits.stack_size,
+        )
+    }
+
+    /// Runs the function once against `data`.
+    pub fn run(&self, data: MockData) -> Result<RunResult, VmError> {
+        self.call(data, HostConditions::default(), self.echo_traces)
+    }
+
+    /// Runs a test case, all of its steps if it is a scenario, and checks the results against
+    /// its expectations.
+    pub fn run_fixture(&self, fixture: &Fixture) -> TestReport {
+        let outcome = match &fixture.scenario {
+            Some(scenario) => {
+                info!("Running scenario of {} step(s)", scenario.steps.len());
+                match scenario.run(fixture.data.clone(), |data| self.run(data)) {
+                    Ok(steps) => TestOutcome::Scenario(steps),
+                    Err(error) => TestOutcome::Failed {
+                        outcome: SETUP_ERROR,
+                        error,
+                    },
+                }
+            }
+            None => {
+                match self.gas_cap_for(&fixture.data) {
+                    Some(cap) => info!("Gas cap: {}", cap),
+                    None => info!("Gas cap: none"),
+                }
+                info!("Executing function: {}", self.function);
+                checked(fixture, self.run(fixture.data.clone()))
+            }
+        };
+        TestReport {
+            function: self.function.clone(),
+            test_case: fixture.name.clone(),
+            outcome,
+        }
+    }
+
+    /// Runs a test case like [`Simulator::run_fixture`], with `debugger` stopping the run at
+    /// host function calls. Scenarios cannot be debugged.
+    pub fn debug_fixture(&self, fixture: &Fixture, debugger: Box<dyn Debugger>) -> TestReport {
+        self.watch(fixture, debugger, true, "debugged")
+    }
+
+    /// Runs a test case like [`Simulator::run_fixture`], recording each host function call to a
+    /// tape. There is no tape if the function could not be run. Scenarios cannot be recorded.
+    pub fn record_fixture(&self, fixture: &Fixture) -> (TestReport, Option<Tape>) {
+        let recorder = Rc::new(RefCell::new(Recorder::default()));
+        let report = self.watch(
+            fixture,
+            Box::new(recorder.clone()),
+            self.echo_traces,
+            "recorded",
+        );
+        let tape = match &report.outcome {
+            TestOutcome::Run { run, .. } => Some(recorder.take().into_tape(
+                &self.function,
+                self.gas_cap_for(&fixture.data),
+                &fixture.data,
+                run,
+            )),
+            _ => None,
+        };
+        (report, tape)
+    }
+
+    /// Runs the tape's function on the tape's state with each host function call answered from
+    /// the tape, and reports where the calls differ from the tape's. The run is capped at the
+    /// tape's gas cap unless one is set.
+    pub fn replay(&self, tape: &Tape) -> Result<ReplayReport, VmError> {
+        let gas_cap = match self.gas_cap {
+            Some(GasCap::Unlimited) => None,
+            Some(GasCap::Limit(cap)) => Some(cap),
+            None => tape.gas_cap,
+        };
+        info!(
+            "Replaying {} host call(s) into {}",
+            tape.calls.len(),
+            tape.function
+        );
+        self.check_limits()?;
+        let replayer = Rc::new(RefCell::new(Replayer::new(tape)));
+        let run = vm_wamr::debug_func(
+            self.wasm_file.clone(),
+            &tape.function,
+            gas_cap,
+            tape.state.clone(),
+            self.echo_traces,
+            Box::new(replayer.clone()),
+            self.limits.stack_size,
+        )?;
+        let replayer = Rc::into_inner(replayer).expect("the run has finished with the replayer");
+        Ok(replayer.into_inner().finish(&run))
+    }
+
+    fn watch(
+        &self,
+        fixture: &Fixture,
+        debugger: Box<dyn Debugger>,
+        echo_traces: bool,
+        action: &str,
+    ) -> TestReport {
+        let outcome = if fixture.scenario.is_some() {
+            TestOutcome::Failed {
+                outcome: SETUP_ERROR,
+                error: format!("A scenario cannot be {}", action),
+            }
+        } else {
+            let run = self.check_limits().and_then(|()| {
+                vm_wamr::debug_func(
+                    self.wasm_file.clone(),
+                    &self.function,
+                    self.gas_cap_for(&fixture.data),
+                    fixture.data.clone(),
+                    echo_traces,
+                    debugger,
+                    self.limits.stack_size,
+                )
+            });
+            checked(fixture, run)
+        };
+        TestReport {
+            function: self.function.clone(),
+            test_case: fixture.name.clone(),
+            outcome,
+        }
+    }
+
+    /// Runs the function over a range of ledger sequences or close times.
+    pub fn sweep(&self, data: &MockData, sweep: &Sweep) -> Result<SweepReport, VmError> {
+        info!(
+            "Sweeping {} over {}..{}",
+            sweep.field.header_field(),
+            sweep.from,
+            sweep.to
+        );
+        sweep.run(data, |data| {
+            self.call(data, HostConditions::default(), false)
+        })
+    }
+
+    /// Runs the function under rippled's host conditions and under varied ones.
+    pub fn check_determinism(&self, data: &MockData) -> Result<DeterminismReport, VmError> {
+        info!("Checking determinism of {}", self.function);
+        determinism::check(data, |data, conditions| self.call(data, conditions, false))
+    }
+
+    /// Runs the function against `cases` mutated copies of `data`.
+    pub fn fuzz(&self, data: &MockData, cases: u32, seed: u64) -> Result<Vec<FoundCase>, VmError> {
+        info!(
+            "Fuzzing {} with {} cases (seed {})",
+            self.function, cases, seed
+        );
+        fuzz::fuzz(data, cases, seed, |data| {
+            self.call(data, HostConditions::default(), false)
+        })
+    }
+}
+
+/// The outcome of a single run of a test case, checked against its expectations.
+fn checked(fixture: &Fixture, run: Result<RunResult, VmError>) -> TestOutcome {
+    match run {
+        Ok(run) => {
+            let failures = match &fixture.expectations {
+                Some(expectations) => expectations.check(&run),
+                None => expectations::check_by_name(&fixture.name, &run),
+            };
+            TestOutcome::Run { run, failures }
+        }
+        Err(e) => TestOutcome::Failed {
+            outcome: e.kind(),
+            error: e.to_string(),
+        },
+    }
+}
+
+impl TestReport {
+    /// A report for a test case that could not be run.
+    pub fn failed(function: &str, test_case: &str, outcome: &'static str, error: &str) -> Self {
+        TestReport {
+            function: function.to_string(),
+            test_case: test_case.to_string(),
+            outcome: TestOutcome::Failed {
+                outcome,
+                error: error.to_string(),
+            },
+        }
+    }
+
+    /// Each expectation the test case did not meet; for a scenario, prefixed with the step.
+    pub fn failures(&self) -> Vec<String> {
+        match &self.outcome {
+            TestOutcome::Run { failures, .. } => failures.clone(),
+            TestOutcome::Scenario(steps) => steps
+                .iter()
+                .flat_map(|step| {
+                    step.failures
+                        .iter()
+                        .map(|f| format!("{}: {}", step.name, f))
+                })
+                .collect(),
+            TestOutcome::Failed { .. } => vec![],
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        !matches!(self.outcome, TestOutcome::Failed { .. }) && self.failures().is_empty()
+    }
+
+    /// The hosting ledger object after the last run that returned a positive value, with
+    /// anything it stored.
+    pub fn ledger_object(&self) -> Option<&Value> {
+        match &self.outcome {
+            TestOutcome::Run { run, .. } => run.kept_ledger_object(),
+            TestOutcome::Scenario(steps) => steps
+                .iter()
+                .rev()
+                .find_map(|step| step.run.kept_ledger_object()),
+            _ => None,
+        }
+    }
+
+    /// How the test case ended: `returned`, `setup_error`, or the [`VmError::kind`] of the
+    /// first run that did not return.
+    pub fn outcome_kind(&self) -> &'static str {
+        match &self.outcome {
+            TestOutcome::Failed { outcome, .. } => outcome,
+            _ => self
+                .runs()
+                .iter()
+                .find_map(|run| run.outcome.as_ref().err())
+                .map_or("returned", VmError::kind),
+        }
+    }
+
+    /// What stopped the test case before every run returned, if anything did.
+    pub fn error(&self) -> Option<String> {
+        match &self.outcome {
+            TestOutcome::Failed { error, .. } => Some(error.clone()),
+            _ => self
+                .runs()
+                .iter()
+                .find_map(|run| run.outcome.as_ref().err())
+                .map(|e| e.to_string()),
+        }
+    }
+
+    /// The value the function returned; for a scenario, in its last step.
+    pub fn return_value(&self) -> Option<i32> {
+        self.runs()
+            .last()
+            .and_then(|run| run.outcome.as_ref().ok().copied())
+    }
+
+    /// Gas the run used; for a scenario, the total of its steps.
+    pub fn gas_used(&self) -> Option<u32> {
+        match &self.outcome {
+            TestOutcome::Run { run, .. } => run.gas_used,
+            TestOutcome::Scenario(steps) => {
+                Some(steps.iter().filter_map(|step| step.run.gas_used).sum())
+            }
+            TestOutcome::Failed { .. } => None,
+        }
+    }
+
+    /// Every run of the test case in order: one, one per scenario step, or none if the test
+    /// case could not be run.
+    pub fn runs(&self) -> Vec<&RunResult> {
+        match &self.outcome {
+            TestOutcome::Run { run, .. } => vec![run],
+            TestOutcome::Scenario(steps) => steps.iter().map(|step| &step.run).collect(),
+            TestOutcome::Failed { .. } => vec![],
+        }
+    }
+
+    /// The JSON record of the test case.
+    pub fn to_json(&self) -> Value {
+        let mut record = json!({
+            "function": self.function,
+            "test_case": self.test_case,
+            "return_value": self.return_value(),
+            "gas_used": self.gas_used(),
+            "gas_profile": null,
+            "traces": [],
+            "host_errors": [],
+            "outcome": self.outcome_kind(),
+            "error": self.error(),
+            "failures": self.failures(),
+            "passed": self.passed(),
+        });
+
+        match &self.outcome {
+            TestOutcome::Run { run, .. } => {
+                record["gas_profile"] = run.log.gas_profile_json(run.gas_used);
+                record["traces"] = run.log.traces_json();
+                record["host_errors"] = run.log.host_errors_json();
+            }
+            TestOutcome::Scenario(steps) => {
+                let step_records: Vec<_> = steps
+                    .iter()
+                    .map(|step| {
+                        json!({
+                            "name": step.name,
+                            "return_value": step.run.outcome.as_ref().ok(),
+                            "gas_used": step.run.gas_used,
+                            "traces": step.run.log.traces_json(),
+                            "host_errors": step.run.log.host_errors_json(),
+                            "outcome": step.run.outcome.as_ref().map_or_else(|e| e.kind(), |_| "returned"),
+                            "error": step.run.outcome.as_ref().err().map(|e| e.to_string()),
+                            "failures": step.failures,
+                            "passed": step.failures.is_empty(),
+                        })
+                    })
+                    .collect();
+                let concat = |records: &str| -> Vec<Value> {
+                    step_records
+                        .iter()
+                        .flat_map(|r| r[records].as_array().cloned().unwrap_or_default())
+                        .collect()
+                };
+                record["traces"] = json!(concat("traces"));
+                record["host_errors"] = json!(concat("host_errors"));
+                record["steps"] = json!(step_records);
+            }
+            TestOutcome::Failed { .. } => {}
+        }
+        record
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A run that stored `Data` 01
+    fn returned(rv: i32) -> RunResult {
+        RunResult {
+            ledger_object: json!({ "Data": "01" }),
+            ..crate::test_support::returned(rv)
+        }
+    }
+
+    /// A module whose `finish` returns 1.
+    const RETURN_ONE_WASM: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // types
+        0x03, 0x02, 0x01, 0x00, // functions
+        0x07, 0x0a, 0x01, 0x06, b'f', b'i', b'n', b'i', b's', b'h', 0x00, 0x00, // exports
+        0x0a, 0x06, 0x01, 0x04, 0x00, 0x41, 0x01, 0x0b, // code
+    ];
+
+    #[test]
+    fn test_runs_on_several_threads_at_once() {
+        let wasm = std::env::temp_dir().join("several_threads.wasm");
+        fs::write(&wasm, RETURN_ONE_WASM).unwrap();
+        let simulator = Simulator::new(&wasm).unwrap();
+        let data = MockData::new("{}", "{}", "{}", "[]", "[]").unwrap();
+        let barrier = std::sync::Barrier::new(4);
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        barrier.wait();
+                        (0..3)
+                            .map(|_| simulator.run(data.clone()).unwrap().outcome.unwrap())
+                            .collect::<Vec<i32>>()
+                    })
+                })
+                .collect();
+            for worker in workers {
+                assert_eq!(worker.join().unwrap(), [1, 1, 1]);
+            }
+        });
+        fs::remove_file(&wasm).unwrap();
+    }
+
+    #[test]
+    fn test_new_requires_the_module() {
+        let err = Simulator::new(Path::new("/nonexistent/module.wasm")).unwrap_err();
+        assert_eq!(err, "WASM module not found: /nonexistent/module.wasm");
+    }
+
+    #[test]
+    fn test_gas_cap_for() {
+        let wasm = std::env::temp_dir().join("gas_cap_for.wasm");
+        fs::write(&wasm, b"").unwrap();
+        let simulator = Simulator::new(&wasm).unwrap();
+        let with_allowance =
+            MockData::new(r#"{ "ComputationAllowance": 500 }"#, "{}", "{}", "[]", "[]").unwrap();
+        let without = MockData::new("{}", "{}", "{}", "[]", "[]").unwrap();
+
+        assert_eq!(simulator.gas_cap_for(&with_allowance), Some(500));
+        assert_eq!(simulator.gas_cap_for(&without), Some(DEFAULT_GAS_CAP));
+        let simulator = simulator.gas_cap(Some(GasCap::Limit(7)));
+        assert_eq!(simulator.gas_cap_for(&with_allowance), Some(7));
+        let simulator = simulator.gas_cap(Some(GasCap::Unlimited));
+        assert_eq!(simulator.gas_cap_for(&with_allowance), None);
+        fs::remove_file(&wasm).unwrap();
+    }
+
+    #[test]
+    fn test_parse_gas_cap_and_hash() {
+        assert_eq!(parse_gas_cap("none"), Ok(GasCap::Unlimited));
+        assert_eq!(parse_gas_cap("NONE"), Ok(GasCap::Unlimited));
+        assert_eq!(parse_gas_cap("500"), Ok(GasCap::Limit(500)));
+        assert!(parse_gas_cap("-1").is_err());
+        assert!(parse_gas_cap("").is_err());
+        assert_eq!(parse_hash(&"ab".repeat(32)), Ok("AB".repeat(32)));
+        assert!(parse_hash("ab").is_err());
+    }
+
+    #[test]
+    fn test_report_records() {
+        let report = TestReport {
+            function: "finish".to_string(),
+            test_case: "success".to_string(),
+            outcome: TestOutcome::Run {
+                run: returned(0),
+                failures: vec!["returned 0, expected 1".to_string()],
+            },
+        };
+        assert!(!report.passed());
+        // A function that does not return a positive value keeps nothing
+        assert_eq!(report.ledger_object(), None);
+        let record = report.to_json();
+        assert_eq!(record["return_value"], 0);
+        assert_eq!(record["outcome"], "returned");
+        assert_eq!(record["failures"][0], "returned 0, expected 1");
+
+        let steps = vec![
+            StepRun {
+                name: "first".to_string(),
+                run: returned(1),
+                failures: vec![],
+            },
+            StepRun {
+                name: "second".to_string(),
+                run: returned(2),
+                failures: vec!["returned 2, expected 1".to_string()],
+            },
+        ];
+        let report = TestReport {
+            function: "finish".to_string(),
+            test_case: "counter".to_string(),
+            outcome: TestOutcome::Scenario(steps),
+        };
+        assert_eq!(report.failures(), vec!["second: returned 2, expected 1"]);
+        assert_eq!(report.ledger_object(), Some(&json!({ "Data": "01" })));
+        let record = report.to_json();
+        assert_eq!(record["gas_used"], 200);
+        assert_eq!(record["steps"][1]["passed"], false);
+
+        let report = TestReport::failed("finish", "broken", SETUP_ERROR, "no tx.json");
+        assert!(!report.passed());
+        assert_eq!(report.ledger_object(), None);
+        assert_eq!(report.to_json()["outcome"], SETUP_ERROR);
+    }
+}
@@ -0,0 +1,254 @@
+use crate::determinism::result_label;
+use crate::mock_data::MockData;
+use crate::vm_wamr::{RunResult, VmError};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+
+/// The ledger header field a sweep varies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SweepField {
+    LedgerSeq,
+    CloseTime,
+}
+
+impl SweepField {
+    /// The field's name in `ledger_header.json`.
+    pub fn header_field(self) -> &'static str {
+        match self {
+            SweepField::LedgerSeq => "ledger_index",
+            SweepField::CloseTime => "parent_close_time",
+        }
+    }
+}
+
+/// Runs a contract over a range of ledger sequences or close times to find where its result
+/// changes.
+///
+/// Every `step`-th value of the range is run, and wherever two neighbouring runs disagree the
+/// gap between them is bisected down to the exact values at which the result flips. With a
+/// `step` above 1, results that change and change back between two sampled values are missed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sweep {
+    pub field: SweepField,
+    pub from: u32,
+    pub to: u32,
+    pub step: u32,
+}
+
+/// A change in result between two neighbouring values of a sweep.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Flip {
+    /// The first value giving the new result.
+    pub at: u32,
+    pub before: String,
+    pub after: String,
+}
+
+/// What a sweep found: the result at its first value, then every flip in order.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SweepReport {
+    pub sweep: Sweep,
+    pub first: String,
+    pub flips: Vec<Flip>,
+    /// How many times the contract was run.
+    pub runs: usize,
+}
+
+/// Parses an inclusive `FROM..TO` range.
+pub fn parse_range(s: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("expected a range FROM..TO, got '{}'", s);
+    let (from, to) = s.split_once("..").ok_or_else(invalid)?;
+    let from: u32 = from.trim().parse().map_err(|_| invalid())?;
+    let to: u32 = to.trim().parse().map_err(|_| invalid())?;
+    if from > to {
+        return Err(format!("range '{}' is empty", s));
+    }
+    Ok((from, to))
+}
+
+impl Sweep {
+    /// Runs the sweep with `run`, each time on a copy of `state` with the swept header field set.
+    /// Failed runs are results like any other; only an error from `run` itself stops the sweep.
+    pub fn run(
+        &self,
+        state: &MockData,
+        mut run: impl FnMut(MockData) -> Result<RunResult, VmError>,
+    ) -> Result<SweepReport, VmError> {
+        let mut results: BTreeMap<u32, String> = BTreeMap::new();
+        let mut eval = |value: u32, results: &mut BTreeMap<u32, String>| {
+            if let Some(label) = results.get(&value) {
+                return Ok(label.clone());
+            }
+            let mut data = state.clone();
+            data.set_header_field(self.field.header_field(), Value::from(value));
+            let label = result_label(&run(data)?);
+            results.insert(value, label.clone());
+            Ok::<_, VmError>(label)
+        };
+
+        let step = self.step.max(1);
+        let mut value = self.from;
+        loop {
+            eval(value, &mut results)?;
+            match value.checked_add(step) {
+                Some(next) if next < self.to => value = next,
+                _ => break,
+            }
+        }
+        eval(self.to, &mut results)?;
+
+        let samples: Vec<(u32, String)> = results.clone().into_iter().collect();
+        let mut flips = Vec::new();
+        for pair in samples.windows(2) {
+            let (mut lo, mut before) = pair[0].clone();
+            let (end, end_label) = &pair[1];
+            // The gap may hold several flips, so keep bisecting what remains after each one
+            while before != *end_label {
+                let (mut hi, mut after) = (*end, end_label.clone());
+                while hi - lo > 1 {
+                    let mid = lo + (hi - lo) / 2;
+                    let label = eval(mid, &mut results)?;
+                    if label == before {
+                        lo = mid;
+                    } else {
+                        hi = mid;
+                        after = label;
+                    }
+                }
+                flips.push(Flip {
+                    at: hi,
+                    before,
+                    after: after.clone(),
+                });
+                (lo, before) = (hi, after);
+            }
+        }
+
+        Ok(SweepReport {
+            sweep: *self,
+            first: samples[0].1.clone(),
+            flips,
+            runs: results.len(),
+        })
+    }
+}
+
+impl SweepReport {
+    /// The swept range split into stretches with the same result, as `(from, to, result)`.
+    pub fn ranges(&self) -> Vec<(u32, u32, &str)> {
+        let mut ranges = Vec::new();
+        let mut from = self.sweep.from;
+        let mut result = self.first.as_str();
+        for flip in &self.flips {
+            ranges.push((from, flip.at - 1, result));
+            from = flip.at;
+            result = &flip.after;
+        }
+        ranges.push((from, self.sweep.to, result));
+        ranges
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "field": self.sweep.field.header_field(),
+            "from": self.sweep.from,
+            "to": self.sweep.to,
+            "step": self.sweep.step,
+            "runs": self.runs,
+            "ranges": self.ranges().iter().map(|(from, to, result)| {
+                json!({ "from": from, "to": to, "result": result })
+            }).collect::<Vec<_>>(),
+            "flips": self.flips.iter().map(|flip| {
+                json!({ "at": flip.at, "before": flip.before, "after": flip.after })
+            }).collect::<Vec<_>>(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{mock_data, returned};
+
+    fn state() -> MockData {
+        mock_data("{}", r#"{ "ledger_index": 1 }"#)
+    }
+
+    /// Finishes from ledger 150 on, except from 300 to 359 where it fails.
+    fn time_locked(data: MockData) -> Result<RunResult, VmError> {
+        let seq = data.get_ledger_sqn().and_then(Value::as_i64).unwrap();
+        let outcome = if (300..360).contains(&seq) {
+            Err(VmError::InvalidModule("trap".to_string()))
+        } else {
+            Ok((seq >= 150) as i32)
+        };
+        Ok(RunResult {
+            outcome,
+            ..returned(0)
+        })
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("100..200"), Ok((100, 200)));
+        assert_eq!(parse_range("7..7"), Ok((7, 7)));
+        assert!(parse_range("200..100").is_err());
+        assert!(parse_range("100-200").is_err());
+    }
+
+    #[test]
+    fn test_sweep_finds_exact_flips() {
+        let sweep = Sweep {
+            field: SweepField::LedgerSeq,
+            from: 100,
+            to: 400,
+            step: 64,
+        };
+        let report = sweep.run(&state(), time_locked).unwrap();
+        assert_eq!(report.first, "returned 0");
+        assert_eq!(
+            report.ranges(),
+            vec![
+                (100, 149, "returned 0"),
+                (150, 299, "returned 1"),
+                (300, 359, "error"),
+                (360, 400, "returned 1"),
+            ]
+        );
+        // Far fewer runs than the 301 values swept
+        assert!(report.runs < 40, "{} runs", report.runs);
+    }
+
+    #[test]
+    fn test_sweep_finds_every_flip_between_two_samples() {
+        let sweep = Sweep {
+            field: SweepField::LedgerSeq,
+            from: 100,
+            to: 340,
+            step: 300,
+        };
+        let report = sweep.run(&state(), time_locked).unwrap();
+        assert_eq!(
+            report.ranges(),
+            vec![
+                (100, 149, "returned 0"),
+                (150, 299, "returned 1"),
+                (300, 340, "error"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sweep_without_flips() {
+        let sweep = Sweep {
+            field: SweepField::LedgerSeq,
+            from: 10,
+            to: 20,
+            step: 1,
+        };
+        let report = sweep.run(&state(), time_locked).unwrap();
+        assert!(report.flips.is_empty());
+        assert_eq!(report.runs, 11);
+        assert_eq!(report.ranges(), vec![(10, 20, "returned 0")]);
+    }
+}
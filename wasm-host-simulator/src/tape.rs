@@ -0,0 +1,466 @@
+use crate::debugger::{Arg, Debugger, Guest, HostCall, Resume};
+use crate::determinism::result_label;
+use crate::mock_data::MockData;
+use crate::vm_wamr::RunResult;
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// One host function call on a tape: what the guest passed and what the host gave back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TapeCall {
+    pub name: String,
+    /// The call's numeric arguments, by parameter name. Pointers are left out, as they move
+    /// between builds of a contract.
+    pub args: BTreeMap<String, i64>,
+    /// The contents of each buffer the guest passed in, by the name of its pointer parameter.
+    pub input: BTreeMap<String, Vec<u8>>,
+    /// What the host wrote to the output buffer, if the call has one.
+    pub output: Vec<u8>,
+    pub result: i32,
+}
+
+impl TapeCall {
+    /// The call as the guest is making it, before the host has answered.
+    fn observe(call: &HostCall, guest: &dyn Guest) -> Self {
+        let args = call
+            .args
+            .iter()
+            .filter_map(|(name, arg)| match arg {
+                Arg::Int(value) => Some((name.to_string(), *value)),
+                Arg::Ptr(_) => None,
+            })
+            .collect();
+        let input = call
+            .buffers()
+            .into_iter()
+            .filter(|b| !b.output)
+            .map(|b| {
+                let bytes = guest.read(b.addr, b.len).unwrap_or_default();
+                (b.name.to_string(), bytes)
+            })
+            .collect();
+        TapeCall {
+            name: call.name.to_string(),
+            args,
+            input,
+            output: Vec::new(),
+            result: 0,
+        }
+    }
+
+    /// Whether the guest passed the same arguments and input as on the tape.
+    fn same_request(&self, other: &TapeCall) -> bool {
+        self.name == other.name && self.args == other.args && self.input == other.input
+    }
+
+    fn to_json(&self) -> Value {
+        let input: Map<String, Value> = self
+            .input
+            .iter()
+            .map(|(name, bytes)| (name.clone(), json!(hex::encode_upper(bytes))))
+            .collect();
+        json!({
+            "name": self.name,
+            "args": self.args,
+            "input": input,
+            "output": hex::encode_upper(&self.output),
+            "result": self.result,
+        })
+    }
+
+    fn from_json(value: &Value) -> Result<Self, String> {
+        let hex_field = |value: &Value, what: &str| {
+            let text = value.as_str().ok_or(format!("{} is not a string", what))?;
+            hex::decode(text).map_err(|e| format!("{} is not hex: {}", what, e))
+        };
+        let name = value["name"].as_str().ok_or("missing name")?.to_string();
+        let mut args = BTreeMap::new();
+        for (arg, v) in value["args"].as_object().into_iter().flatten() {
+            let v = v
+                .as_i64()
+                .ok_or(format!("argument {} is not a number", arg))?;
+            args.insert(arg.clone(), v);
+        }
+        let mut input = BTreeMap::new();
+        for (buffer, v) in value["input"].as_object().into_iter().flatten() {
+            input.insert(buffer.clone(), hex_field(v, buffer)?);
+        }
+        let output = match &value["output"] {
+            Value::Null => Vec::new(),
+            v => hex_field(v, "output")?,
+        };
+        let result = value["result"]
+            .as_i64()
+            .and_then(|r| i32::try_from(r).ok())
+            .ok_or("missing or invalid result")?;
+        Ok(TapeCall {
+            name,
+            args,
+            input,
+            output,
+            result,
+        })
+    }
+}
+
+impl fmt::Display for TapeCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let args = self
+            .args
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .chain(
+                self.input
+                    .iter()
+                    .map(|(name, bytes)| format!("{}={}", name, hex::encode_upper(bytes))),
+            )
+            .collect::<Vec<_>>();
+        write!(f, "{}({})", self.name, args.join(", "))
+    }
+}
+
+/// Every host function call of one run, in order, and how the run ended.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tape {
+    pub function: String,
+    /// The gas cap the run had, if any.
+    pub gas_cap: Option<u32>,
+    /// How the run ended, e.g. `returned 1` or `trap`.
+    pub outcome: String,
+    /// The fixture state the run started from, which a replay runs on. A tape without one, such
+    /// as one rebuilt from a log of a run on a network, replays on empty state.
+    pub state: MockData,
+    pub calls: Vec<TapeCall>,
+}
+
+impl Tape {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "function": self.function,
+            "gas_cap": self.gas_cap,
+            "outcome": self.outcome,
+            "state": self.state.to_json(),
+            "calls": self.calls.iter().map(TapeCall::to_json).collect::<Vec<_>>(),
+        })
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        let calls = value["calls"]
+            .as_array()
+            .ok_or("missing calls")?
+            .iter()
+            .enumerate()
+            .map(|(i, call)| {
+                TapeCall::from_json(call).map_err(|e| format!("call {}: {}", i + 1, e))
+            })
+            .collect::<Result<_, _>>()?;
+        let state = match &value["state"] {
+            Value::Null => MockData::from_json(&json!({})),
+            state => MockData::from_json(state),
+        }
+        .map_err(|e| format!("state: {}", e))?;
+        Ok(Tape {
+            function: value["function"].as_str().unwrap_or("finish").to_string(),
+            gas_cap: value["gas_cap"].as_u64().map(|cap| cap as u32),
+            outcome: value["outcome"].as_str().unwrap_or_default().to_string(),
+            state,
+            calls,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read tape {}: {}", path.display(), e))?;
+        let value: Value = serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid JSON in tape {}: {}", path.display(), e))?;
+        Tape::from_json(&value).map_err(|e| format!("Invalid tape {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(&self.to_json())? + "\n")
+    }
+}
+
+/// Records each host function call of a run.
+#[derive(Debug, Default)]
+pub struct Recorder {
+    calls: Vec<TapeCall>,
+}
+
+impl Recorder {
+    /// The tape of a run of `function` that started from `state`.
+    pub fn into_tape(
+        self,
+        function: &str,
+        gas_cap: Option<u32>,
+        state: &MockData,
+        run: &RunResult,
+    ) -> Tape {
+        Tape {
+            function: function.to_string(),
+            gas_cap,
+            outcome: result_label(run),
+            state: state.clone(),
+            calls: self.calls,
+        }
+    }
+}
+
+impl Debugger for Recorder {
+    fn before_call(&mut self, call: &HostCall, guest: &dyn Guest) -> Resume {
+        self.calls.push(TapeCall::observe(call, guest));
+        Resume::Continue
+    }
+
+    fn after_call(&mut self, call: &HostCall, result: i32, guest: &dyn Guest) {
+        let Some(recorded) = self.calls.last_mut() else {
+            return;
+        };
+        recorded.result = result;
+        if result > 0
+            && let Some(buffer) = call.buffers().into_iter().find(|b| b.output)
+        {
+            let len = buffer.len.min(result as u64);
+            recorded.output = guest.read(buffer.addr, len).unwrap_or_default();
+        }
+    }
+}
+
+/// Where a replayed run parted from its tape.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// The host call, counting from 1, or `None` for how the run ended.
+    pub call: Option<usize>,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// The outcome of replaying a tape.
+#[derive(Debug)]
+pub struct ReplayReport {
+    /// How many of the tape's calls were answered.
+    pub replayed: usize,
+    pub outcome: String,
+    pub differences: Vec<Divergence>,
+}
+
+impl ReplayReport {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "replayed": self.replayed,
+            "outcome": self.outcome,
+            "matches": self.differences.is_empty(),
+            "differences": self.differences.iter().map(|d| json!({
+                "call": d.call,
+                "expected": d.expected,
+                "actual": d.actual,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Answers each host function call with the next response on a tape. A call with other
+/// arguments than the tape's is still answered; a call to another function, or past the end of
+/// the tape, stops the run. `trace` functions run as usual, so their output is seen.
+pub struct Replayer {
+    calls: Vec<TapeCall>,
+    outcome: String,
+    next: usize,
+    differences: Vec<Divergence>,
+    stopped: bool,
+}
+
+impl Replayer {
+    pub fn new(tape: &Tape) -> Self {
+        Replayer {
+            calls: tape.calls.clone(),
+            outcome: tape.outcome.clone(),
+            next: 0,
+            differences: Vec::new(),
+            stopped: false,
+        }
+    }
+
+    /// The report once the run is over.
+    pub fn finish(mut self, run: &RunResult) -> ReplayReport {
+        let outcome = result_label(run);
+        if !self.stopped {
+            if let Some(expected) = self.calls.get(self.next) {
+                self.differences.push(Divergence {
+                    call: Some(self.next + 1),
+                    expected: expected.to_string(),
+                    actual: "no more host calls".to_string(),
+                });
+            }
+            if outcome != self.outcome {
+                self.differences.push(Divergence {
+                    call: None,
+                    expected: self.outcome.clone(),
+                    actual: outcome.clone(),
+                });
+            }
+        }
+        ReplayReport {
+            replayed: self.next - usize::from(self.stopped),
+            outcome,
+            differences: self.differences,
+        }
+    }
+}
+
+impl Debugger for Replayer {
+    fn before_call(&mut self, call: &HostCall, guest: &dyn Guest) -> Resume {
+        let actual = TapeCall::observe(call, guest);
+        let index = self.next;
+        self.next += 1;
+        let Some(expected) = self.calls.get(index) else {
+            self.differences.push(Divergence {
+                call: Some(index + 1),
+                expected: "end of tape".to_string(),
+                actual: actual.to_string(),
+            });
+            self.stopped = true;
+            return Resume::Abort;
+        };
+        if expected.name != actual.name {
+            self.differences.push(Divergence {
+                call: Some(index + 1),
+                expected: expected.to_string(),
+                actual: actual.to_string(),
+            });
+            self.stopped = true;
+            return Resume::Abort;
+        }
+        if !expected.same_request(&actual) {
+            self.differences.push(Divergence {
+                call: Some(index + 1),
+                expected: expected.to_string(),
+                actual: actual.to_string(),
+            });
+        }
+
+        if call.name.starts_with("trace") {
+            return Resume::Continue;
+        }
+        if let Some(buffer) = call.buffers().into_iter().find(|b| b.output) {
+            let len = expected.output.len().min(buffer.len as usize);
+            guest.write(buffer.addr, &expected.output[..len]);
+        }
+        Resume::Respond(expected.result)
+    }
+
+    fn after_call(&mut self, _call: &HostCall, _result: i32, _guest: &dyn Guest) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{Memory, mock_data, returned};
+
+    fn get_tx_field(field: i64) -> HostCall {
+        HostCall {
+            name: "get_tx_field",
+            args: vec![
+                ("field", Arg::Int(field)),
+                ("out_buf_ptr", Arg::Ptr(16)),
+                ("out_buf_cap", Arg::Int(8)),
+            ],
+        }
+    }
+
+    fn trace() -> HostCall {
+        HostCall {
+            name: "trace",
+            args: vec![("msg_read_ptr", Arg::Ptr(0)), ("msg_read_len", Arg::Int(4))],
+        }
+    }
+
+    fn recorded_tape() -> Tape {
+        let memory = Memory::with_bytes(b"done".repeat(8));
+        let mut recorder = Recorder::default();
+        let call = get_tx_field(0x80001);
+        recorder.before_call(&call, &memory);
+        memory.write(16, &[1, 2, 3, 4]);
+        recorder.after_call(&call, 4, &memory);
+        recorder.before_call(&trace(), &memory);
+        recorder.after_call(&trace(), 0, &memory);
+        let state = mock_data(r#"{ "Data": "01" }"#, r#"{ "ledger_index": 5 }"#);
+        recorder.into_tape("finish", Some(1000), &state, &returned(1))
+    }
+
+    #[test]
+    fn test_record_and_round_trip() {
+        let tape = recorded_tape();
+        assert_eq!(tape.outcome, "returned 1");
+        assert_eq!(tape.calls.len(), 2);
+        assert_eq!(tape.calls[0].args["field"], 0x80001);
+        assert!(!tape.calls[0].args.contains_key("out_buf_ptr"));
+        assert_eq!(tape.calls[0].output, vec![1, 2, 3, 4]);
+        assert_eq!(tape.calls[1].input["msg_read_ptr"], b"done");
+        assert_eq!(Tape::from_json(&tape.to_json()), Ok(tape.clone()));
+    }
+
+    #[test]
+    fn test_replay_without_state() {
+        let mut stateless = recorded_tape().to_json();
+        stateless.as_object_mut().unwrap().remove("state");
+        let tape = Tape::from_json(&stateless).unwrap();
+        assert_eq!(tape.state, MockData::from_json(&json!({})).unwrap());
+        assert_eq!(tape.calls, recorded_tape().calls);
+
+        let memory = Memory::with_bytes(b"done".repeat(8));
+        let mut replayer = Replayer::new(&tape);
+        assert_eq!(
+            replayer.before_call(&get_tx_field(0x80001), &memory),
+            Resume::Respond(4)
+        );
+        assert_eq!(memory.read(16, 4), Some(vec![1, 2, 3, 4]));
+        assert_eq!(replayer.before_call(&trace(), &memory), Resume::Continue);
+        assert!(replayer.finish(&returned(1)).differences.is_empty());
+    }
+
+    #[test]
+    fn test_replay_answers_from_tape() {
+        let tape = recorded_tape();
+        let memory = Memory::with_bytes(b"done".repeat(8));
+        let mut replayer = Replayer::new(&tape);
+        assert_eq!(
+            replayer.before_call(&get_tx_field(0x80001), &memory),
+            Resume::Respond(4)
+        );
+        assert_eq!(memory.read(16, 4), Some(vec![1, 2, 3, 4]));
+        assert_eq!(replayer.before_call(&trace(), &memory), Resume::Continue);
+
+        let report = replayer.finish(&returned(1));
+        assert_eq!(report.replayed, 2);
+        assert!(report.differences.is_empty());
+    }
+
+    #[test]
+    fn test_replay_flags_divergence() {
+        let tape = recorded_tape();
+        let memory = Memory::with_bytes(b"done".repeat(8));
+
+        // Other arguments are flagged but still answered
+        let mut replayer = Replayer::new(&tape);
+        assert_eq!(
+            replayer.before_call(&get_tx_field(0x80002), &memory),
+            Resume::Respond(4)
+        );
+        let report = replayer.finish(&returned(0));
+        let calls: Vec<_> = report.differences.iter().map(|d| d.call).collect();
+        assert_eq!(calls, vec![Some(1), Some(2), None]);
+        assert_eq!(report.differences[1].actual, "no more host calls");
+        assert_eq!(report.differences[2].expected, "returned 1");
+
+        // Another function stops the run
+        let mut replayer = Replayer::new(&tape);
+        assert_eq!(replayer.before_call(&trace(), &memory), Resume::Abort);
+        let report = replayer.finish(&returned(0));
+        assert_eq!(report.differences.len(), 1);
+        assert!(report.differences[0].expected.starts_with("get_tx_field("));
+    }
+}
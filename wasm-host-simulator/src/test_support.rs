@@ -0,0 +1,75 @@
+//! Stand-ins for runs, fixtures and guests shared by the unit tests.
+
+use crate::debugger::{Frame, Guest};
+use crate::mock_data::MockData;
+use crate::run_log::RunLog;
+use crate::vm_wamr::RunResult;
+use serde_json::Value;
+use std::cell::RefCell;
+
+/// A run that returned `rv` using 100 gas, without tracing or storing anything.
+pub fn returned(rv: i32) -> RunResult {
+    RunResult {
+        outcome: Ok(rv),
+        gas_used: Some(100),
+        log: RunLog::default(),
+        data: None,
+        ledger_object: Value::Null,
+    }
+}
+
+/// Fixture data with an empty transaction, ledger and NFT list.
+pub fn mock_data(ledger_object: &str, ledger_header: &str) -> MockData {
+    MockData::new("{}", ledger_object, ledger_header, "[]", "[]").unwrap()
+}
+
+/// A guest's linear memory, which refuses reads and writes past its end as WAMR's bounds checks do.
+pub struct Memory {
+    bytes: RefCell<Vec<u8>>,
+    call_stack: Vec<Frame>,
+}
+
+impl Memory {
+    /// `len` bytes, each holding the low byte of its address.
+    pub fn new(len: usize) -> Self {
+        Memory::with_bytes((0..len).map(|i| i as u8).collect())
+    }
+
+    pub fn with_bytes(bytes: Vec<u8>) -> Self {
+        Memory {
+            bytes: RefCell::new(bytes),
+            call_stack: Vec::new(),
+        }
+    }
+
+    /// The same memory, with `call_stack` as the guest's call stack.
+    pub fn with_call_stack(self, call_stack: Vec<Frame>) -> Self {
+        Memory { call_stack, ..self }
+    }
+}
+
+impl Guest for Memory {
+    fn read(&self, addr: u64, len: u64) -> Option<Vec<u8>> {
+        let end = addr.checked_add(len)?;
+        let memory = self.bytes.borrow();
+        memory.get(addr as usize..end as usize).map(<[u8]>::to_vec)
+    }
+
+    fn write(&self, addr: u64, bytes: &[u8]) -> bool {
+        let Some(end) = addr.checked_add(bytes.len() as u64) else {
+            return false;
+        };
+        let mut memory = self.bytes.borrow_mut();
+        match memory.get_mut(addr as usize..end as usize) {
+            Some(target) => {
+                target.copy_from_slice(bytes);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn call_stack(&self) -> Vec<Frame> {
+        self.call_stack.clone()
+    }
+}
@@ -1,119 +1,593 @@
-use crate::data_provider::DataProvider;
+use crate::data_provider::{DataProvider, HostConditions, RippledRoundingMode};
+use crate::debugger::{self, Debugger, ToArg, WamrGuest};
 use crate::host_functions_wamr::{
-    account_keylet, amm_keylet, cache_ledger_obj, check_keylet, compute_sha512_half,
-    credential_keylet, delegate_keylet, deposit_preauth_keylet, did_keylet, escrow_keylet,
-    float_add, float_compare, float_divide, float_from_int, float_from_uint, float_log,
-    float_multiply, float_pow, float_root, float_set, float_subtract,
-    get_current_ledger_obj_array_len, get_current_ledger_obj_field,
-    get_current_ledger_obj_nested_array_len, get_current_ledger_obj_nested_field,
+    GuestPtr, account_keylet, amendment_enabled, amm_keylet, cache_ledger_obj, check_keylet,
+    check_sig, compute_sha512_half, credential_keylet, delegate_keylet, deposit_preauth_keylet,
+    did_keylet, escrow_keylet, float_add, float_compare, float_divide, float_from_int,
+    float_from_uint, float_log, float_multiply, float_pow, float_root, float_set, float_subtract,
+    get_base_fee, get_current_ledger_obj_array_len, get_current_ledger_obj_field,
+    get_current_ledger_obj_nested_array_len, get_current_ledger_obj_nested_field, get_dp,
     get_ledger_obj_array_len, get_ledger_obj_field, get_ledger_obj_nested_array_len,
-    get_ledger_obj_nested_field, get_ledger_sqn, get_nft, get_parent_ledger_hash,
+    get_ledger_obj_nested_field, get_ledger_sqn, get_nft, get_nft_flags, get_nft_issuer,
+    get_nft_serial, get_nft_taxon, get_nft_transfer_fee, get_parent_ledger_hash,
     get_parent_ledger_time, get_tx_array_len, get_tx_field, get_tx_nested_array_len,
     get_tx_nested_field, line_keylet, mpt_issuance_keylet, mptoken_keylet, nft_offer_keylet,
     offer_keylet, oracle_keylet, paychan_keylet, permissioned_domain_keylet, signers_keylet,
     ticket_keylet, trace, trace_account, trace_amount, trace_num, trace_opaque_float, update_data,
     vault_keylet,
 };
+use crate::limits::{Limits, Violation};
 use crate::mock_data::MockData;
+use crate::run_log::RunLog;
 use log::{debug, info, warn};
 use std::ffi::c_void;
+use std::fmt;
 use std::path::PathBuf;
+use std::ptr;
+use std::sync::{Mutex, OnceLock, PoisonError};
 use wamr_rust_sdk::RuntimeError;
 use wamr_rust_sdk::function::Function;
 use wamr_rust_sdk::instance::Instance;
 use wamr_rust_sdk::module::Module;
 use wamr_rust_sdk::runtime::Runtime;
+use wamr_rust_sdk::sys::{
+    wasm_exec_env_t, wasm_runtime_destroy_thread_env, wasm_runtime_get_exec_env_singleton,
+    wasm_runtime_get_instruction_count_limit, wasm_runtime_init_thread_env,
+    wasm_runtime_set_custom_data, wasm_runtime_thread_env_inited,
+};
 use wamr_rust_sdk::value::WasmValue;
+use wasmparser::{ExternalKind, FuncType, Parser, Payload, TypeRef, ValType};
+use xrpld_number::{Number, RoundingMode};
+
+/// Errors that can occur while resolving and running a WASM entry point.
+#[derive(Debug)]
+pub enum VmError {
+    /// An error reported by the WAMR runtime.
+    Runtime(RuntimeError),
+    /// The WASM module could not be parsed.
+    InvalidModule(String),
+    /// The requested function is not exported by the module.
+    FunctionNotExported { name: String, exports: Vec<String> },
+    /// The requested function does not have the `() -> i32` signature.
+    InvalidSignature { name: String, signature: String },
+    /// The run consumed its whole gas cap before returning.
+    OutOfGas { gas_cap: u32 },
+    /// The module goes beyond the limits it is run under, so it was not run.
+    LimitsExceeded {
+        profile: &'static str,
+        violations: Vec<Violation>,
+    },
+}
+
+impl VmError {
+    /// A short, stable name for the kind of failure, used in the JSON run record.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            VmError::OutOfGas { .. } => "out_of_gas",
+            VmError::Runtime(RuntimeError::ExecutionError(_)) => "trap",
+            _ => "error",
+        }
+    }
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VmError::Runtime(e) => write!(f, "{}", e),
+            VmError::InvalidModule(msg) => write!(f, "Invalid WASM module: {}", msg),
+            VmError::FunctionNotExported { name, exports } => {
+                if exports.is_empty() {
+                    write!(
+                        f,
+                        "Function '{}' is not exported; the module exports no functions",
+                        name
+                    )
+                } else {
+                    write!(
+                        f,
+                        "Function '{}' is not exported; available exports: {}",
+                        name,
+                        exports.join(", ")
+                    )
+                }
+            }
+            VmError::InvalidSignature { name, signature } => write!(
+                f,
+                "Function '{}' has signature {}, expected (func (result i32))",
+                name, signature
+            ),
+            VmError::OutOfGas { gas_cap } => write!(f, "Ran out of gas (gas cap {})", gas_cap),
+            VmError::LimitsExceeded {
+                profile,
+                violations,
+            } => {
+                write!(f, "Module exceeds the '{}' limits:", profile)?;
+                for violation in violations {
+                    write!(f, "\n  - {}", violation)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for VmError {}
+
+impl From<RuntimeError> for VmError {
+    fn from(e: RuntimeError) -> Self {
+        VmError::Runtime(e)
+    }
+}
+
+/// Returns the name and type of every function exported by the module, in export order.
+pub fn exported_functions(wasm: &[u8]) -> Result<Vec<(String, FuncType)>, VmError> {
+    let invalid = |e: wasmparser::BinaryReaderError| VmError::InvalidModule(e.to_string());
+    let mut types = Vec::new();
+    let mut func_types = Vec::new();
+    let mut exports = Vec::new();
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload.map_err(invalid)? {
+            Payload::TypeSection(reader) => {
+                for ty in reader.into_iter_err_on_gc_types() {
+                    types.push(ty.map_err(invalid)?);
+                }
+            }
+            Payload::ImportSection(reader) => {
+                for import in reader {
+                    if let TypeRef::Func(idx) = import.map_err(invalid)?.ty {
+                        func_types.push(idx);
+                    }
+                }
+            }
+            Payload::FunctionSection(reader) => {
+                for idx in reader {
+                    func_types.push(idx.map_err(invalid)?);
+                }
+            }
+            Payload::ExportSection(reader) => {
+                for export in reader {
+                    let export = export.map_err(invalid)?;
+                    if export.kind == ExternalKind::Func {
+                        exports.push((export.name.to_string(), export.index));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    exports
+        .into_iter()
+        .map(|(name, func_idx)| {
+            func_types
+                .get(func_idx as usize)
+                .and_then(|type_idx| types.get(*type_idx as usize))
+                .map(|ty| (name.clone(), ty.clone()))
+                .ok_or_else(|| VmError::InvalidModule(format!("export '{}' has no type", name)))
+        })
+        .collect()
+}
+
+/// Checks that `func_name` is exported with the `() -> i32` signature expected of an entry point.
+pub fn resolve_entry_point(wasm: &[u8], func_name: &str) -> Result<(), VmError> {
+    let exports = exported_functions(wasm)?;
+    match exports.iter().find(|(name, _)| name == func_name) {
+        Some((_, ty)) if ty.params().is_empty() && ty.results() == [ValType::I32] => Ok(()),
+        Some((_, ty)) => Err(VmError::InvalidSignature {
+            name: func_name.to_string(),
+            signature: ty.to_string(),
+        }),
+        None => Err(VmError::FunctionNotExported {
+            name: func_name.to_string(),
+            exports: exports.into_iter().map(|(name, _)| name).collect(),
+        }),
+    }
+}
+
+/// Passes the host functions rippled provides, with their WAMR signatures and gas costs, to
+/// `$callback` after the tokens in `$prefix`, so that the list is kept in one place.
+macro_rules! with_host_functions {
+    ($callback:ident ! ( $($prefix:tt)* )) => {
+        $callback!(
+            $($prefix)*
+            get_ledger_sqn() => "()i", 60;
+            get_parent_ledger_time() => "()i", 60;
+            get_parent_ledger_hash(out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(ii)i", 60;
+            get_base_fee() => "()i", 60;
+            amendment_enabled(amendment_ptr: GuestPtr, amendment_len: usize) => "(ii)i", 60;
+            cache_ledger_obj(in_buf_ptr: GuestPtr, in_buf_cap: usize, cache_num: i32) => "(iii)i", 5000;
+            get_tx_field(field: i32, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iii)i", 70;
+            get_current_ledger_obj_field(field: i32, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iii)i", 70;
+            get_ledger_obj_field(slot: i32, field: i32, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiii)i", 70;
+            get_tx_nested_field(in_buf_ptr: GuestPtr, in_buf_len: usize, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiii)i", 110;
+            get_current_ledger_obj_nested_field(in_buf_ptr: GuestPtr, in_buf_len: usize, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiii)i", 110;
+            get_ledger_obj_nested_field(slot: i32, in_buf_ptr: GuestPtr, in_buf_len: usize, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiiii)i", 110;
+            get_tx_array_len(field: i32) => "(i)i", 40;
+            get_current_ledger_obj_array_len(field: i32) => "(i)i", 40;
+            get_ledger_obj_array_len(slot: i32, field: i32) => "(ii)i", 40;
+            get_tx_nested_array_len(in_buf_ptr: GuestPtr, in_buf_len: usize) => "(ii)i", 70;
+            get_current_ledger_obj_nested_array_len(in_buf_ptr: GuestPtr, in_buf_len: usize) => "(ii)i", 70;
+            get_ledger_obj_nested_array_len(slot: i32, in_buf_ptr: GuestPtr, in_buf_len: usize) => "(iii)i", 70;
+            update_data(in_buf_ptr: GuestPtr, in_buf_len: usize) => "(ii)i", 1000;
+            compute_sha512_half(in_buf_ptr: GuestPtr, in_buf_len: usize, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiii)i", 2000;
+            check_sig(message_ptr: GuestPtr, message_len: usize, signature_ptr: GuestPtr, signature_len: usize, pubkey_ptr: GuestPtr, pubkey_len: usize) => "(iiiiii)i", 2000;
+            account_keylet(account_buf_ptr: GuestPtr, account_buf_len: usize, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiii)i", 350;
+            amm_keylet(asset1_ptr: GuestPtr, asset1_len: usize, asset2_ptr: GuestPtr, asset2_len: usize, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiiiii)i", 350;
+            credential_keylet(subject_ptr: GuestPtr, subject_len: usize, issuer_ptr: GuestPtr, issuer_len: usize, cred_type_ptr: GuestPtr, cred_type_len: usize, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiiiiiii)i", 350;
+            check_keylet(account_buf_ptr: GuestPtr, account_buf_len: usize, sequence: i32, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiiii)i", 350;
+            delegate_keylet(account_ptr: GuestPtr, account_len: usize, authorize_ptr: GuestPtr, authorize_len: usize, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiiiii)i", 350;
+            deposit_preauth_keylet(account_ptr: GuestPtr, account_len: usize, authorize_ptr: GuestPtr, authorize_len: usize, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiiiii)i", 350;
+            did_keylet(account_ptr: GuestPtr, account_len: usize, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiii)i", 350;
+            escrow_keylet(account_ptr: GuestPtr, account_len: usize, sequence: u32, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiiii)i", 350;
+            line_keylet(account1_ptr: GuestPtr, account1_len: usize, account2_ptr: GuestPtr, account2_len: usize, currency_ptr: GuestPtr, currency_len: usize, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiiiiiii)i", 350;
+            mpt_issuance_keylet(issuer_buf_ptr: GuestPtr, issuer_buf_len: usize, sequence: i32, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiiii)i", 350;
+            mptoken_keylet(mpt_id_ptr: GuestPtr, mpt_id_len: usize, holder_ptr: GuestPtr, holder_len: usize, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiiiii)i", 350;
+            nft_offer_keylet(account_buf_ptr: GuestPtr, account_buf_len: usize, sequence: i32, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiiii)i", 350;
+            offer_keylet(account_buf_ptr: GuestPtr, account_buf_len: usize, sequence: i32, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiiii)i", 350;
+            oracle_keylet(account_ptr: GuestPtr, account_len: usize, document_id: u32, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiiii)i", 350;
+            paychan_keylet(account_ptr: GuestPtr, account_len: usize, destination_ptr: GuestPtr, destination_len: usize, sequence: i32, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiiiiii)i", 350;
+            permissioned_domain_keylet(account_buf_ptr: GuestPtr, account_buf_len: usize, sequence: i32, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiiii)i", 350;
+            signers_keylet(account_buf_ptr: GuestPtr, account_buf_len: usize, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiii)i", 350;
+            ticket_keylet(account_buf_ptr: GuestPtr, account_buf_len: usize, sequence: i32, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiiii)i", 350;
+            vault_keylet(account_buf_ptr: GuestPtr, account_buf_len: usize, sequence: i32, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiiii)i", 350;
+            get_nft(owner_ptr: GuestPtr, owner_len: usize, nft_id_ptr: GuestPtr, nft_id_len: usize, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiiiii)i", 1000;
+            get_nft_issuer(nft_id_ptr: GuestPtr, nft_id_len: usize, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiii)i", 60;
+            get_nft_taxon(nft_id_ptr: GuestPtr, nft_id_len: usize, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiii)i", 60;
+            get_nft_flags(nft_id_ptr: GuestPtr, nft_id_len: usize) => "(ii)i", 60;
+            get_nft_transfer_fee(nft_id_ptr: GuestPtr, nft_id_len: usize) => "(ii)i", 60;
+            get_nft_serial(nft_id_ptr: GuestPtr, nft_id_len: usize, out_buf_ptr: GuestPtr, out_buf_cap: usize) => "(iiii)i", 60;
+            float_from_int(in_int: i64, out_buf: GuestPtr, out_buff_len: usize, rounding_mode: i32) => "(Iiii)i", 1000;
+            float_from_uint(in_uint_ptr: GuestPtr, in_uint_len: usize, out_buff: GuestPtr, out_buff_len: usize, rounding_mode: i32) => "(iiiii)i", 1000;
+            float_set(exponent: i32, mantissa: i64, out_buff: GuestPtr, out_buff_len: usize, rounding_mode: i32) => "(iIiii)i", 1000;
+            float_compare(in_buff1: GuestPtr, in_buff1_len: usize, in_buff2: GuestPtr, in_buff2_len: usize) => "(iiii)i", 1000;
+            float_add(in_buff1: GuestPtr, in_buff1_len: usize, in_buff2: GuestPtr, in_buff2_len: usize, out_buff: GuestPtr, out_buff_len: usize, rounding_mode: i32) => "(iiiiiii)i", 1000;
+            float_subtract(in_buff1: GuestPtr, in_buff1_len: usize, in_buff2: GuestPtr, in_buff2_len: usize, out_buff: GuestPtr, out_buff_len: usize, rounding_mode: i32) => "(iiiiiii)i", 1000;
+            float_multiply(in_buff1: GuestPtr, in_buff1_len: usize, in_buff2: GuestPtr, in_buff2_len: usize, out_buff: GuestPtr, out_buff_len: usize, rounding_mode: i32) => "(iiiiiii)i", 1000;
+            float_divide(in_buff1: GuestPtr, in_buff1_len: usize, in_buff2: GuestPtr, in_buff2_len: usize, out_buff: GuestPtr, out_buff_len: usize, rounding_mode: i32) => "(iiiiiii)i", 1000;
+            float_pow(in_buff: GuestPtr, in_buff_len: usize, in_int: i32, out_buff: GuestPtr, out_buff_len: usize, rounding_mode: i32) => "(iiiiii)i", 1000;
+            float_root(in_buff: GuestPtr, in_buff_len: usize, in_int: i32, out_buff: GuestPtr, out_buff_len: usize, rounding_mode: i32) => "(iiiiii)i", 1000;
+            float_log(in_buff: GuestPtr, in_buff_len: usize, out_buff: GuestPtr, out_buff_len: usize, rounding_mode: i32) => "(iiiii)i", 1000;
+            trace(msg_read_ptr: GuestPtr, msg_read_len: usize, data_read_ptr: GuestPtr, data_read_len: usize, data_as_hex: i32) => "(iiiii)i", 500;
+            trace_num(msg_read_ptr: GuestPtr, msg_read_len: usize, number: i64) => "(iiI)i", 500;
+            trace_opaque_float(msg_read_ptr: GuestPtr, msg_read_len: usize, op_float: GuestPtr, float_len: usize) => "(iiii)i", 500;
+            trace_account(msg_read_ptr: GuestPtr, msg_read_len: usize, account_ptr: GuestPtr, account_len: usize) => "(iiii)i", 500;
+            trace_amount(msg_read_ptr: GuestPtr, msg_read_len: usize, amount_ptr: GuestPtr, amount_len: usize) => "(iiii)i", 500;
+        )
+    };
+}
+
+macro_rules! host_function_signatures {
+    ($( $name:ident ( $($arg:ident : $ty:ty),* ) => $sig:literal, $gas:literal; )*) => {
+        &[$((stringify!($name), $sig)),*]
+    };
+}
+
+/// The name and WAMR signature of each host function rippled provides, which modules import
+/// from `host_lib`.
+pub const HOST_FUNCTIONS: &[(&str, &str)] = with_host_functions!(host_function_signatures!());
+
+/// Registers each host function behind a wrapper that records its result in the run log. Pointers
+/// and lengths are registered as plain `i` parameters, left for the host functions to check.
+macro_rules! register_host_functions {
+    ($builder:expr, $( $name:ident ( $($arg:ident : $ty:ty),* ) => $sig:literal, $gas:literal; )*) => {{
+        let builder = $builder;
+        $(
+            let builder = {
+                extern "C" fn wrapper(env: wasm_exec_env_t $(, $arg: $ty)*) -> i32 {
+                    let result = debugger::intercept(
+                        env,
+                        stringify!($name),
+                        || vec![$((stringify!($arg), $arg.to_arg())),*],
+                        || $name(get_dp(env), &WamrGuest(env) $(, $arg)*),
+                    );
+                    get_dp(env).run_log.record_host_call(stringify!($name), $gas, result);
+                    result
+                }
+                builder.register_host_function(stringify!($name), wrapper as *mut c_void, $sig, $gas, ptr::null_mut())
+            };
+        )*
+        builder
+    }};
+}
+
+/// WAMR's runtime and the host functions registered with it are process-wide, so the runtime is
+/// built once and shared by every run. Each instance carries its own `DataProvider` as custom
+/// data, and `Number`'s rounding mode is per thread, so runs on different threads only take turns
+/// to build the runtime.
+static RUNTIME: OnceLock<SharedRuntime> = OnceLock::new();
+static RUNTIME_LOCK: Mutex<()> = Mutex::new(());
+
+struct SharedRuntime(Runtime);
+
+// SAFETY: the runtime's native symbols are only read once registered. WAMR allows modules to be
+// loaded, instantiated and called on threads the embedder creates, provided each has called
+// `wasm_runtime_init_thread_env` first (see `wasm_export.h`), which `execute` does through
+// `THREAD_ENV` before touching the runtime on any thread.
+unsafe impl Send for SharedRuntime {}
+unsafe impl Sync for SharedRuntime {}
+
+/// WAMR's per-thread state, such as the signal handling hardware bounds checks rely on. The thread
+/// that builds the runtime has it already; any other thread sets it up before its first run and
+/// tears it down when it exits.
+struct ThreadEnv {
+    ready: bool,
+    set_up_here: bool,
+}
+
+impl ThreadEnv {
+    fn new() -> Self {
+        // SAFETY: both only read or set up the calling thread's own WAMR state.
+        if unsafe { wasm_runtime_thread_env_inited() } {
+            return ThreadEnv {
+                ready: true,
+                set_up_here: false,
+            };
+        }
+        let ready = unsafe { wasm_runtime_init_thread_env() };
+        ThreadEnv {
+            ready,
+            set_up_here: ready,
+        }
+    }
+}
+
+impl Drop for ThreadEnv {
+    fn drop(&mut self) {
+        if self.set_up_here {
+            // SAFETY: tears down only the state this thread set up, once its last run is over.
+            unsafe { wasm_runtime_destroy_thread_env() };
+        }
+    }
+}
+
+thread_local! {
+    static THREAD_ENV: ThreadEnv = ThreadEnv::new();
+}
+
+/// The process-wide runtime, with every host function registered.
+fn runtime() -> Result<&'static Runtime, RuntimeError> {
+    let _runtime_lock = RUNTIME_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(shared) = RUNTIME.get() {
+        return Ok(&shared.0);
+    }
+    debug!("Setting up wamr runtime and registering host functions");
+    let runtime = with_host_functions!(register_host_functions!(
+        Runtime::builder().use_system_allocator(),
+    ))
+    .build()?;
+    Ok(&RUNTIME.get_or_init(|| SharedRuntime(runtime)).0)
+}
+
+/// The outcome of running an entry point, along with what the guest observed along the way.
+pub struct RunResult {
+    /// The entry point's return value, or the error that stopped it.
+    pub outcome: Result<i32, VmError>,
+    /// Gas consumed, when the run completed or ran out of gas under a gas cap.
+    pub gas_used: Option<u32>,
+    pub log: RunLog,
+    /// The ledger object's `Data` once the run finished, including any `update_data` writes.
+    pub data: Option<Vec<u8>>,
+    /// The hosting ledger object once the run finished, in `ledger_object.json` form.
+    pub ledger_object: serde_json::Value,
+}
+
+impl RunResult {
+    /// The hosting ledger object as rippled would keep it: only when the entry point returned a
+    /// positive value, since any other result leaves the escrow's `update_data` writes undone.
+    pub fn kept_ledger_object(&self) -> Option<&serde_json::Value> {
+        match self.outcome {
+            Ok(rv) if rv > 0 => Some(&self.ledger_object),
+            _ => None,
+        }
+    }
+}
 
 #[rustfmt::skip]
 #[allow(unused)]
-pub fn run_func(wasm_file: String, func_name: &str, gas_cap: Option<u32>, data_source: MockData) -> Result<bool, RuntimeError>{
-    debug!("Setting up wamr runtime and registering host functions");
+pub fn run_func(wasm_file: String, func_name: &str, gas_cap: Option<u32>, data_source: MockData, echo_traces: bool) -> Result<RunResult, VmError>{
+    run_func_with_conditions(wasm_file, func_name, gas_cap, data_source, echo_traces, HostConditions::default(), Limits::default().stack_size)
+}
+
+/// Runs the function like [`run_func`], but under the given host conditions instead of
+/// rippled's, with `stack_size` bytes of stack.
+pub fn run_func_with_conditions(
+    wasm_file: String,
+    func_name: &str,
+    gas_cap: Option<u32>,
+    data_source: MockData,
+    echo_traces: bool,
+    conditions: HostConditions,
+    stack_size: u32,
+) -> Result<RunResult, VmError> {
+    let mut data_provider = DataProvider::with_conditions(data_source, conditions);
+    data_provider.run_log = RunLog::new(echo_traces);
+    execute(
+        wasm_file,
+        func_name,
+        gas_cap,
+        data_provider,
+        conditions.rounding_mode,
+        stack_size,
+    )
+}
+
+/// Runs the function like [`run_func`], with `debugger` called at each host function call and
+/// `stack_size` bytes of stack.
+pub fn debug_func(
+    wasm_file: String,
+    func_name: &str,
+    gas_cap: Option<u32>,
+    data_source: MockData,
+    echo_traces: bool,
+    debugger: Box<dyn Debugger>,
+    stack_size: u32,
+) -> Result<RunResult, VmError> {
     let mut data_provider = DataProvider::new(data_source);
-    let runtime = Runtime::builder()
-        .use_system_allocator()
-        .register_host_function("get_ledger_sqn", get_ledger_sqn as *mut c_void, "()i", 60, data_provider.as_ptr())
-        .register_host_function("get_parent_ledger_time", get_parent_ledger_time as *mut c_void, "()i", 60, data_provider.as_ptr())
-        .register_host_function("get_parent_ledger_hash", get_parent_ledger_hash as *mut c_void, "(*~)i", 60, data_provider.as_ptr())
-        .register_host_function("cache_ledger_obj", cache_ledger_obj as *mut c_void, "(*~i)i", 5000, data_provider.as_ptr())
-        .register_host_function("get_tx_field", get_tx_field as *mut c_void, "(i*~)i", 70, data_provider.as_ptr())
-        .register_host_function("get_current_ledger_obj_field", get_current_ledger_obj_field as *mut c_void, "(i*~)i", 70, data_provider.as_ptr())
-        .register_host_function("get_ledger_obj_field", get_ledger_obj_field as *mut c_void, "(ii*~)i", 70, data_provider.as_ptr())
-        .register_host_function("get_tx_nested_field", get_tx_nested_field as *mut c_void, "(*~*~)i", 110, data_provider.as_ptr())
-        .register_host_function("get_current_ledger_obj_nested_field", get_current_ledger_obj_nested_field as *mut c_void, "(*~*~)i", 110, data_provider.as_ptr())
-        .register_host_function("get_ledger_obj_nested_field", get_ledger_obj_nested_field as *mut c_void, "(i*~*~)i", 110, data_provider.as_ptr())
-        .register_host_function("get_tx_array_len", get_tx_array_len as *mut c_void, "(i)i", 40, data_provider.as_ptr())
-        .register_host_function("get_current_ledger_obj_array_len", get_current_ledger_obj_array_len as *mut c_void, "(i)i", 40, data_provider.as_ptr())
-        .register_host_function("get_ledger_obj_array_len", get_ledger_obj_array_len as *mut c_void, "(ii)i", 40, data_provider.as_ptr())
-        .register_host_function("get_tx_nested_array_len", get_tx_nested_array_len as *mut c_void, "(*~)i", 70, data_provider.as_ptr())
-        .register_host_function("get_current_ledger_obj_nested_array_len", get_current_ledger_obj_nested_array_len as *mut c_void, "(*~)i", 70, data_provider.as_ptr())
-        .register_host_function("get_ledger_obj_nested_array_len", get_ledger_obj_nested_array_len as *mut c_void, "(i*~)i", 70, data_provider.as_ptr())
-        .register_host_function("update_data", update_data as *mut c_void, "(*~)i", 1000, data_provider.as_ptr())
-        .register_host_function("compute_sha512_half", compute_sha512_half as *mut c_void, "(*~*~)i", 2000, data_provider.as_ptr())
-        .register_host_function("account_keylet", account_keylet as *mut c_void, "(*~*~)i", 350, data_provider.as_ptr())
-        .register_host_function("amm_keylet", amm_keylet as *mut c_void, "(*~*~*~)i", 350, data_provider.as_ptr())
-        .register_host_function("credential_keylet", credential_keylet as *mut c_void, "(*~*~*~*~)i", 350, data_provider.as_ptr())
-        .register_host_function("check_keylet", check_keylet as *mut c_void, "(*~i*~)i", 350, data_provider.as_ptr())
-        .register_host_function("delegate_keylet", delegate_keylet as *mut c_void, "(*~*~*~)i", 350, data_provider.as_ptr())
-        .register_host_function("deposit_preauth_keylet", deposit_preauth_keylet as *mut c_void, "(*~*~*~)i", 350, data_provider.as_ptr())
-        .register_host_function("did_keylet", did_keylet as *mut c_void, "(*~*~)i", 350, data_provider.as_ptr())
-        .register_host_function("escrow_keylet", escrow_keylet as *mut c_void, "(*~i*~)i", 350, data_provider.as_ptr())
-        .register_host_function("line_keylet", line_keylet as *mut c_void, "(*~*~*~*~)i", 350, data_provider.as_ptr())
-        .register_host_function("mpt_issuance_keylet", mpt_issuance_keylet as *mut c_void, "(*~i*~)i", 350, data_provider.as_ptr())
-        .register_host_function("mptoken_keylet", mptoken_keylet as *mut c_void, "(*~*~*~)i", 350, data_provider.as_ptr())
-        .register_host_function("nft_offer_keylet", nft_offer_keylet as *mut c_void, "(*~i*~)i", 350, data_provider.as_ptr())
-        .register_host_function("offer_keylet", offer_keylet as *mut c_void, "(*~i*~)i", 350, data_provider.as_ptr())
-        .register_host_function("oracle_keylet", oracle_keylet as *mut c_void, "(*~i*~)i", 350, data_provider.as_ptr())
-        .register_host_function("paychan_keylet", paychan_keylet as *mut c_void, "(*~*~i*~)i", 350, data_provider.as_ptr())
-        .register_host_function("permissioned_domain_keylet", permissioned_domain_keylet as *mut c_void, "(*~i*~)i", 350, data_provider.as_ptr())
-        .register_host_function("signers_keylet", signers_keylet as *mut c_void, "(*~*~)i", 350, data_provider.as_ptr())
-        .register_host_function("ticket_keylet", ticket_keylet as *mut c_void, "(*~i*~)i", 350, data_provider.as_ptr())
-        .register_host_function("vault_keylet", vault_keylet as *mut c_void, "(*~i*~)i", 350, data_provider.as_ptr())
-        .register_host_function("get_nft", get_nft as *mut c_void, "(*~*~*~)i", 1000, data_provider.as_ptr())
-        .register_host_function("float_from_int", float_from_int as *mut c_void, "(I*~i)i", 1000, data_provider.as_ptr())
-        .register_host_function("float_from_uint", float_from_uint as *mut c_void, "(*~*~i)i", 1000, data_provider.as_ptr())
-        .register_host_function("float_set", float_set as *mut c_void, "(iI*~i)i", 1000, data_provider.as_ptr())
-        .register_host_function("float_compare", float_compare as *mut c_void, "(*~*~)i", 1000, data_provider.as_ptr())
-        .register_host_function("float_add", float_add as *mut c_void, "(*~*~*~i)i", 1000, data_provider.as_ptr())
-        .register_host_function("float_subtract", float_subtract as *mut c_void, "(*~*~*~i)i", 1000, data_provider.as_ptr())
-        .register_host_function("float_multiply", float_multiply as *mut c_void, "(*~*~*~i)i", 1000, data_provider.as_ptr())
-        .register_host_function("float_divide", float_divide as *mut c_void, "(*~*~*~i)i", 1000, data_provider.as_ptr())
-        .register_host_function("float_pow", float_pow as *mut c_void, "(*~i*~i)i", 1000, data_provider.as_ptr())
-        .register_host_function("float_root", float_root as *mut c_void, "(*~i*~i)i", 1000, data_provider.as_ptr())
-        .register_host_function("float_log", float_log as *mut c_void, "(*~*~i)i", 1000, data_provider.as_ptr())
-        .register_host_function("trace", trace as *mut c_void, "(*~*~i)i", 500, data_provider.as_ptr())
-        .register_host_function("trace_num", trace_num as *mut c_void, "(*~I)i", 500, data_provider.as_ptr())
-        .register_host_function("trace_opaque_float", trace_opaque_float as *mut c_void, "(*~*~)i", 500, data_provider.as_ptr())
-        .register_host_function("trace_account", trace_account as *mut c_void, "(*~*~)i", 500, data_provider.as_ptr())
-        .register_host_function("trace_amount", trace_amount as *mut c_void, "(*~*~)i", 500, data_provider.as_ptr())
-        .build()?;
-
-    debug!("Loading WASM module from file: {}", wasm_file);
-    let wasm_path = PathBuf::from(wasm_file);
-    let module = Module::from_file(&runtime, wasm_path.as_path())?;
-    // rippled currently allows 128kb for each VM instance, so we use the same here in Craft.
-    let instance = Instance::new(&runtime, &module, 1024 * 128)?;
+    data_provider.run_log = RunLog::new(echo_traces);
+    data_provider.debugger = Some(debugger);
+    execute(
+        wasm_file,
+        func_name,
+        gas_cap,
+        data_provider,
+        RippledRoundingMode::ToNearest,
+        stack_size,
+    )
+}
 
-    debug!("Executing WASM function: {}", func_name);
-    let func = Function::find_export_func(&instance, "finish")?;
-    let gas_begin = gas_cap.map_or(0, |x|x);
-    let results = func.call(&instance, &vec![], gas_cap)?;
-    match results {
-        (rv, gas_end) if rv.len() == 1 => {
-            if let WasmValue::I32(r1) = rv[0] {
-                info!("run_func result: {}", r1);
-                if gas_begin > 0 {
-                    info!("run_func gas cost: {}", gas_begin - gas_end);
+/// Turns the result of calling an entry point into its outcome and the gas it used. A failed
+/// call under a gas cap ran out of gas if `remaining_gas` reports the instruction counter spent.
+fn classify(
+    result: Result<(Vec<WasmValue>, u32), RuntimeError>,
+    gas_cap: Option<u32>,
+    remaining_gas: impl FnOnce() -> i64,
+) -> (Result<i32, VmError>, Option<u32>) {
+    match (result, gas_cap) {
+        (Ok((rv, gas_end)), _) => {
+            let gas_used = gas_cap.map(|gas_begin| gas_begin - gas_end);
+            if let Some(gas) = gas_used {
+                info!("run_func gas cost: {}", gas);
+            }
+            match rv.as_slice() {
+                [WasmValue::I32(r1)] => {
+                    info!("run_func result: {}", r1);
+                    (Ok(*r1), gas_used)
+                }
+                _ => {
+                    warn!("Unexpected run_func result vec: {:?}", rv);
+                    let error = VmError::InvalidModule(format!("unexpected result {:?}", rv));
+                    (Err(error), gas_used)
                 }
-                Ok(r1 == 1)
-            } else {
-                warn!("Unexpected run_func result vec: {:?}", rv[0]);
-                Ok(false)
             }
         }
-        _ => {
-            warn!("run_func error: {:?}", results);
-            Ok(false)
+        (Err(RuntimeError::ExecutionError(e)), Some(gas_cap)) if remaining_gas() <= 0 => {
+            warn!("run_func ran out of gas: {}", e.message);
+            (Err(VmError::OutOfGas { gas_cap }), Some(gas_cap))
+        }
+        (Err(e), _) => {
+            warn!("run_func error: {:?}", e);
+            (Err(e.into()), None)
         }
     }
 }
+
+fn execute(
+    wasm_file: String,
+    func_name: &str,
+    gas_cap: Option<u32>,
+    mut data_provider: DataProvider,
+    rounding_mode: RippledRoundingMode,
+    stack_size: u32,
+) -> Result<RunResult, VmError> {
+    let wasm_path = PathBuf::from(wasm_file);
+    let wasm_bytes = std::fs::read(&wasm_path).map_err(RuntimeError::WasmFileFSError)?;
+
+    let runtime = runtime()?;
+    if !THREAD_ENV.with(|env| env.ready) {
+        warn!("Could not set up WAMR's environment for this thread");
+        return Err(RuntimeError::InitializationFailure.into());
+    }
+
+    debug!("Loading WASM module from file: {}", wasm_path.display());
+    let module = Module::from_file(runtime, wasm_path.as_path())?;
+    resolve_entry_point(&wasm_bytes, func_name)?;
+    let instance = Instance::new(runtime, &module, stack_size)?;
+    // SAFETY: `data_provider` outlives the instance, and only this run's host calls read it.
+    unsafe { wasm_runtime_set_custom_data(instance.get_inner_instance(), data_provider.as_ptr()) };
+
+    debug!("Executing WASM function: {}", func_name);
+    let func = Function::find_export_func(&instance, func_name)?;
+    let previous_mode = Number::set_rounding_mode(match rounding_mode {
+        RippledRoundingMode::ToNearest => RoundingMode::ToNearest,
+        RippledRoundingMode::TowardsZero => RoundingMode::TowardsZero,
+        RippledRoundingMode::Downward => RoundingMode::Downward,
+        RippledRoundingMode::Upward => RoundingMode::Upward,
+    });
+    let result = func.call(&instance, &vec![], gas_cap);
+    Number::set_rounding_mode(previous_mode);
+    let (outcome, gas_used) = classify(result, gas_cap, || {
+        // The call runs on the instance's singleton exec env, whose instruction counter holds
+        // the gas left when the call stopped.
+        unsafe {
+            let exec_env = wasm_runtime_get_exec_env_singleton(instance.get_inner_instance());
+            wasm_runtime_get_instruction_count_limit(exec_env) as i64
+        }
+    });
+
+    Ok(RunResult {
+        outcome,
+        gas_used,
+        log: std::mem::take(&mut data_provider.run_log),
+        data: data_provider.get_current_ledger_obj_data(),
+        ledger_object: data_provider.get_current_ledger_obj(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wamr_rust_sdk::ExecError;
+
+    /// A module exporting `finish: () -> i32` and `helper: (i32) -> i32`.
+    const TWO_EXPORTS_WASM: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x0a, 0x02, 0x60, 0x00, 0x01, 0x7f, 0x60, 0x01, 0x7f, 0x01, 0x7f, // types
+        0x03, 0x03, 0x02, 0x00, 0x01, // functions
+        0x07, 0x13, 0x02, // exports
+        0x06, b'f', b'i', b'n', b'i', b's', b'h', 0x00, 0x00, //
+        0x06, b'h', b'e', b'l', b'p', b'e', b'r', 0x00, 0x01, //
+        0x0a, 0x0b, 0x02, 0x04, 0x00, 0x41, 0x01, 0x0b, 0x04, 0x00, 0x41, 0x00, 0x0b, // code
+    ];
+
+    fn trap() -> Result<(Vec<WasmValue>, u32), RuntimeError> {
+        Err(RuntimeError::ExecutionError(ExecError {
+            message: "Exception: unreachable".to_string(),
+            exit_code: 0,
+        }))
+    }
+
+    #[test]
+    fn test_classify() {
+        let (outcome, gas_used) = classify(Ok((vec![WasmValue::I32(1)], 40)), Some(100), || 40);
+        assert_eq!(outcome.unwrap(), 1);
+        assert_eq!(gas_used, Some(60));
+
+        let (outcome, gas_used) = classify(trap(), Some(100), || 0);
+        assert!(matches!(outcome, Err(VmError::OutOfGas { gas_cap: 100 })));
+        assert_eq!(gas_used, Some(100));
+
+        // A trap with gas left, or without a gas cap, is not running out of gas.
+        let (outcome, gas_used) = classify(trap(), Some(100), || 7);
+        assert_eq!(outcome.unwrap_err().kind(), "trap");
+        assert_eq!(gas_used, None);
+        let (outcome, _) = classify(trap(), None, || unreachable!());
+        assert_eq!(outcome.unwrap_err().kind(), "trap");
+
+        let (outcome, _) = classify(Ok((vec![], 0)), None, || unreachable!());
+        assert!(matches!(outcome, Err(VmError::InvalidModule(_))));
+    }
+
+    #[test]
+    fn test_resolve_entry_point_accepts_finish() {
+        assert!(resolve_entry_point(TWO_EXPORTS_WASM, "finish").is_ok());
+    }
+
+    #[test]
+    fn test_resolve_entry_point_rejects_wrong_signature() {
+        let err = resolve_entry_point(TWO_EXPORTS_WASM, "helper").unwrap_err();
+        assert!(matches!(err, VmError::InvalidSignature { ref name, .. } if name == "helper"));
+        assert_eq!(
+            err.to_string(),
+            "Function 'helper' has signature (func (param i32) (result i32)), expected (func (result i32))"
+        );
+    }
+
+    #[test]
+    fn test_resolve_entry_point_lists_exports_when_missing() {
+        let err = resolve_entry_point(TWO_EXPORTS_WASM, "missing").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Function 'missing' is not exported; available exports: finish, helper"
+        );
+    }
+}
@@ -162,12 +162,15 @@ pub mod current_ledger_object {
     /// * `Err(Error)` - If the field cannot be retrieved or has an unexpected size.
     #[inline]
     pub fn get_u64_field_optional(field_code: i32) -> Result<Option<u64>> {
-        let mut value: u64 = 0u64; // <-- Initialize 8 bytes (only works due to little endian encoding in WASM and WAMR-host
-        let value_ptr: *mut u8 = (&mut value as *mut u64).cast::<u8>();
+        // Unlike UInt16 and UInt32 fields, UInt64 fields are returned big-endian
+        let mut buffer = [0u8; 8];
 
-        let result_code = unsafe { get_current_ledger_obj_field(field_code, value_ptr, 8) };
+        let result_code =
+            unsafe { get_current_ledger_obj_field(field_code, buffer.as_mut_ptr(), 8) };
 
-        match_result_code_with_expected_bytes_optional(result_code, 8, || Some(value))
+        match_result_code_with_expected_bytes_optional(result_code, 8, || {
+            Some(u64::from_be_bytes(buffer))
+        })
     }
 
     /// Retrieves a `Hash256` field from the current ledger object.
@@ -458,12 +461,15 @@ pub mod ledger_object {
     /// * `Err(Error)` - If the field cannot be retrieved or has an unexpected size.
     #[inline]
     pub fn get_u64_field_optional(register_num: i32, field_code: i32) -> Result<Option<u64>> {
-        let mut value: u64 = 0; // <-- Initialize 8 bytes (only works due to little endian encoding in WASM and WAMR-host
-        let value_ptr: *mut u8 = (&mut value as *mut u64).cast::<u8>();
+        // Unlike UInt16 and UInt32 fields, UInt64 fields are returned big-endian
+        let mut buffer = [0u8; 8];
 
-        let result_code = unsafe { get_ledger_obj_field(register_num, field_code, value_ptr, 8) };
+        let result_code =
+            unsafe { get_ledger_obj_field(register_num, field_code, buffer.as_mut_ptr(), 8) };
 
-        match_result_code_with_expected_bytes_optional(result_code, 4, || Some(value))
+        match_result_code_with_expected_bytes_optional(result_code, 8, || {
+            Some(u64::from_be_bytes(buffer))
+        })
     }
 
     #[inline]
@@ -7,5 +7,6 @@ pub mod crypto_condition;
 pub mod hash_256;
 pub mod keylets;
 pub mod public_key;
+pub mod st_object;
 pub mod transaction_type;
 pub mod uint_128;
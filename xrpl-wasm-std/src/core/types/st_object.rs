@@ -0,0 +1,398 @@
+//! Parsers for serialized STObjects and STArrays.
+//!
+//! Reading an inner object field (such as a `Memo`) or an array field (such as `Memos` or
+//! `PriceDataSeries`) returns it in XRPL binary format: an object as its fields, an array as its
+//! elements, without the trailing end marker. [`STObject`] and [`STArray`] walk that data, so a
+//! contract can fetch a whole object in one host call and read its fields locally.
+//!
+//! ```no_run
+//! use xrpl_wasm_std::core::types::st_object::STArray;
+//! use xrpl_wasm_std::host::get_tx_field;
+//! use xrpl_wasm_std::sfield;
+//!
+//! let mut buf = [0u8; 1024];
+//! let len = unsafe { get_tx_field(sfield::Memos, buf.as_mut_ptr(), buf.len()) };
+//! if len > 0 {
+//!     for memo in STArray::new(&buf[..len as usize]).elements() {
+//!         let memo = memo.ok().unwrap().as_object();
+//!         let memo_data = memo.get(sfield::MemoData);
+//!     }
+//! }
+//! ```
+
+use crate::host::{Error, Result};
+
+pub const OBJECT_END_MARKER: u8 = 0xE1;
+pub const ARRAY_END_MARKER: u8 = 0xF1;
+
+const STI_OBJECT: i32 = 14;
+const STI_ARRAY: i32 = 15;
+
+const PATHSET_END: u8 = 0x00;
+const PATH_SEPARATOR: u8 = 0xFF;
+
+/// One field of a serialized object.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct STField<'a> {
+    /// The field code, as in [`crate::sfield`].
+    pub field_code: i32,
+    /// The field's value. Blobs, account IDs and `Vector256`s are given without their length
+    /// prefix, objects, arrays and path sets without their end marker.
+    pub value: &'a [u8],
+}
+
+impl<'a> STField<'a> {
+    /// The serialized type of the field, e.g. 14 for an object.
+    pub fn type_code(&self) -> i32 {
+        self.field_code >> 16
+    }
+
+    /// The value of an inner object field, or of an array element.
+    pub fn as_object(&self) -> STObject<'a> {
+        STObject::new(self.value)
+    }
+
+    /// The value of an array field.
+    pub fn as_array(&self) -> STArray<'a> {
+        STArray::new(self.value)
+    }
+}
+
+/// A serialized STObject: a sequence of fields in canonical order.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct STObject<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> STObject<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Iterates over the object's fields. Iteration ends after the first decoding error.
+    pub fn fields(&self) -> Fields<'a> {
+        Fields::new(self.data, OBJECT_END_MARKER)
+    }
+
+    /// The field with code `field_code`, or `None` if the object does not have it.
+    pub fn get(&self, field_code: i32) -> Result<Option<STField<'a>>> {
+        for field in self.fields() {
+            match field {
+                Result::Ok(field) if field.field_code == field_code => {
+                    return Result::Ok(Some(field));
+                }
+                Result::Ok(_) => {}
+                Result::Err(e) => return Result::Err(e),
+            }
+        }
+        Result::Ok(None)
+    }
+}
+
+/// A serialized STArray: a sequence of inner objects, each wrapped in a field such as `Memo`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct STArray<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> STArray<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Iterates over the array's elements, whose values are the wrapped objects. Iteration ends
+    /// after the first decoding error.
+    pub fn elements(&self) -> Fields<'a> {
+        Fields::new(self.data, ARRAY_END_MARKER)
+    }
+
+    /// The element at `index`, or `None` if the array is shorter.
+    pub fn get(&self, index: usize) -> Result<Option<STField<'a>>> {
+        match self.elements().nth(index) {
+            Some(Result::Ok(element)) => Result::Ok(Some(element)),
+            Some(Result::Err(e)) => Result::Err(e),
+            None => Result::Ok(None),
+        }
+    }
+
+    /// The number of elements in the array.
+    pub fn len(&self) -> Result<usize> {
+        let mut len = 0;
+        for element in self.elements() {
+            if let Result::Err(e) = element {
+                return Result::Err(e);
+            }
+            len += 1;
+        }
+        Result::Ok(len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty() || self.data[0] == ARRAY_END_MARKER
+    }
+}
+
+/// Iterator over the fields of an [`STObject`] or the elements of an [`STArray`].
+pub struct Fields<'a> {
+    data: &'a [u8],
+    pos: usize,
+    end_marker: u8,
+    done: bool,
+}
+
+impl<'a> Fields<'a> {
+    fn new(data: &'a [u8], end_marker: u8) -> Self {
+        Self {
+            data,
+            pos: 0,
+            end_marker,
+            done: false,
+        }
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<STField<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.pos >= self.data.len() || self.data[self.pos] == self.end_marker {
+            return None;
+        }
+        match parse_field(self.data, self.pos) {
+            Some((field, next)) => {
+                self.pos = next;
+                Some(Result::Ok(field))
+            }
+            None => {
+                self.done = true;
+                Some(Result::Err(Error::InvalidDecoding))
+            }
+        }
+    }
+}
+
+/// Parses the field starting at `pos`, returning it and the position right after it.
+fn parse_field(data: &[u8], pos: usize) -> Option<(STField<'_>, usize)> {
+    let first = *data.get(pos)?;
+    let mut pos = pos + 1;
+    let mut type_code = (first >> 4) as i32;
+    let mut nth = (first & 0x0F) as i32;
+    if type_code == 0 {
+        type_code = *data.get(pos)? as i32;
+        pos += 1;
+    }
+    if nth == 0 {
+        nth = *data.get(pos)? as i32;
+        pos += 1;
+    }
+
+    let (start, end, next) = match type_code {
+        STI_OBJECT => {
+            let end = skip_fields(data, pos, OBJECT_END_MARKER)?;
+            (pos, end, end + 1)
+        }
+        STI_ARRAY => {
+            let end = skip_fields(data, pos, ARRAY_END_MARKER)?;
+            (pos, end, end + 1)
+        }
+        // Blob, AccountID, Vector256
+        7 | 8 | 19 => {
+            let (len, prefix) = decode_vl_length(data, pos)?;
+            (pos + prefix, pos + prefix + len, pos + prefix + len)
+        }
+        18 => {
+            let end = skip_pathset(data, pos)?;
+            (pos, end, end + 1)
+        }
+        _ => {
+            let len = value_length(type_code, data, pos)?;
+            (pos, pos + len, pos + len)
+        }
+    };
+    let value = data.get(start..end)?;
+    let field = STField {
+        field_code: (type_code << 16) | nth,
+        value,
+    };
+    Some((field, next))
+}
+
+/// Skips fields from `pos` up to `end_marker`, returning the marker's position.
+fn skip_fields(data: &[u8], mut pos: usize, end_marker: u8) -> Option<usize> {
+    while *data.get(pos)? != end_marker {
+        pos = parse_field(data, pos)?.1;
+    }
+    Some(pos)
+}
+
+/// Skips the paths of a path set, returning the position of its end byte.
+fn skip_pathset(data: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        match *data.get(pos)? {
+            PATHSET_END => return Some(pos),
+            PATH_SEPARATOR => pos += 1,
+            step => {
+                // Account, currency and issuer are each 20 bytes
+                let parts = [0x01, 0x10, 0x20]
+                    .iter()
+                    .filter(|b| step & **b != 0)
+                    .count();
+                pos += 1 + parts * 20;
+            }
+        }
+    }
+}
+
+/// Length of a fixed-size value, or of an amount or issue, which depends on its first bytes.
+fn value_length(type_code: i32, data: &[u8], pos: usize) -> Option<usize> {
+    let len = match type_code {
+        1 => 2,   // UInt16
+        2 => 4,   // UInt32
+        3 => 8,   // UInt64
+        4 => 16,  // Hash128
+        5 => 32,  // Hash256
+        9 => 12,  // Number
+        16 => 1,  // UInt8
+        17 => 20, // Hash160
+        20 => 12, // UInt96
+        21 => 24, // Hash192
+        22 => 48, // UInt384
+        23 => 64, // UInt512
+        26 => 20, // Currency
+        6 => {
+            // Amount: IOU (not-XRP bit), MPT (MPT bit), or XRP
+            let first = *data.get(pos)?;
+            if first & 0x80 != 0 {
+                48
+            } else if first & 0x20 != 0 {
+                33
+            } else {
+                8
+            }
+        }
+        24 => issue_length(data, pos)?,
+        25 => {
+            // XChainBridge: locking door, locking issue, issuing door, issuing issue
+            let mut end = pos;
+            for _ in 0..2 {
+                let (len, prefix) = decode_vl_length(data, end)?;
+                end += prefix + len;
+                end += issue_length(data, end)?;
+            }
+            end - pos
+        }
+        _ => return None,
+    };
+    Some(len)
+}
+
+/// XRP is a bare zero currency, tokens add an issuer, and MPTs are an issuer, the `noAccount` ID
+/// and a sequence.
+fn issue_length(data: &[u8], pos: usize) -> Option<usize> {
+    let first = data.get(pos..pos + 20)?;
+    if first.iter().all(|b| *b == 0) {
+        return Some(20);
+    }
+    let second = data.get(pos + 20..pos + 40)?;
+    let no_account = second[..19].iter().all(|b| *b == 0) && second[19] == 1;
+    Some(if no_account { 44 } else { 40 })
+}
+
+/// Decodes a variable-length prefix, returning the value length and the size of the prefix.
+fn decode_vl_length(data: &[u8], pos: usize) -> Option<(usize, usize)> {
+    let b0 = *data.get(pos)? as usize;
+    match b0 {
+        0..=192 => Some((b0, 1)),
+        193..=240 => {
+            let b1 = *data.get(pos + 1)? as usize;
+            Some((193 + ((b0 - 193) << 8) + b1, 2))
+        }
+        241..=254 => {
+            let b1 = *data.get(pos + 1)? as usize;
+            let b2 = *data.get(pos + 2)? as usize;
+            Some((12481 + ((b0 - 241) << 16) + (b1 << 8) + b2, 3))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sfield;
+
+    // Memos: [{ Memo: { MemoType: "01", MemoData: "0203" } }, { Memo: { MemoData: "" } }]
+    const MEMOS: [u8; 12] = [
+        0xEA, 0x7C, 0x01, 0x01, 0x7D, 0x02, 0x02, 0x03, 0xE1, // first Memo
+        0xEA, 0x7D, 0x00, // second Memo, without its end marker
+    ];
+
+    #[test]
+    fn test_array_elements_and_object_fields() {
+        // The second Memo is cut short of its end marker
+        assert!(STArray::new(&MEMOS).len().is_err());
+
+        let mut data = [0u8; 13];
+        data[..12].copy_from_slice(&MEMOS);
+        data[12] = OBJECT_END_MARKER;
+        let memos_complete = STArray::new(&data);
+        assert_eq!(memos_complete.len().ok(), Some(2));
+
+        let first = memos_complete.get(0).ok().unwrap().unwrap();
+        assert_eq!(first.field_code, sfield::Memo);
+        let memo = first.as_object();
+        let memo_data = memo.get(sfield::MemoData).ok().unwrap().unwrap();
+        assert_eq!(memo_data.value, &[0x02, 0x03]);
+        assert_eq!(memo.get(sfield::MemoFormat).ok(), Some(None));
+
+        let second = memos_complete.get(1).ok().unwrap().unwrap().as_object();
+        let empty = second.get(sfield::MemoData).ok().unwrap().unwrap();
+        assert!(empty.value.is_empty());
+        assert_eq!(memos_complete.get(2).ok(), Some(None));
+    }
+
+    #[test]
+    fn test_field_headers_and_lengths() {
+        let data = [
+            0x22, 0, 0, 0, 1, // Flags, UInt32
+            0x20, 0x33, 0, 0, 0, 2, // OracleDocumentID, field code >= 16
+            0x61, 0x40, 0, 0, 0, 0, 0, 0, 10, // Amount, 10 drops
+            0x00, 0x10, 0x10, 5, // TickSize, type and field code >= 16
+            0x01, 0x10, 3, // CloseResolution, type code >= 16
+        ];
+        let object = STObject::new(&data);
+        let fields: [i32; 5] = [
+            sfield::Flags,
+            sfield::OracleDocumentID,
+            sfield::Amount,
+            sfield::TickSize,
+            sfield::CloseResolution,
+        ];
+        let mut count = 0;
+        for (field, expected) in object.fields().zip(fields) {
+            assert_eq!(field.ok().unwrap().field_code, expected);
+            count += 1;
+        }
+        assert_eq!(count, 5);
+        let amount = object.get(sfield::Amount).ok().unwrap().unwrap();
+        assert_eq!(amount.value, &[0x40, 0, 0, 0, 0, 0, 0, 10]);
+        assert_eq!(
+            object.get(sfield::TickSize).ok().unwrap().unwrap().value,
+            &[5]
+        );
+    }
+
+    #[test]
+    fn test_long_blob_prefix() {
+        let mut data = [0u8; 3 + 200];
+        data[0] = 0x7D; // MemoData
+        data[1] = 193;
+        data[2] = 200 - 193;
+        let field = STObject::new(&data)
+            .get(sfield::MemoData)
+            .ok()
+            .unwrap()
+            .unwrap();
+        assert_eq!(field.value.len(), 200);
+    }
+}